@@ -0,0 +1,344 @@
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+use prometheus::{CounterVec, IntGauge};
+use tracing::warn;
+
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// When the call probing the backend after the cooldown was let through
+    probing_since: Option<Instant>,
+}
+
+/// Stops calls to a backend for `cooldown` after `threshold` consecutive failures,
+/// so a struggling backend doesn't add its timeout to every call.
+///
+/// Once the cooldown has passed a single call is let through: the breaker closes
+/// when it succeeds and opens again right away when it fails.
+pub struct Breaker {
+    name: &'static str,
+    state: Mutex<BreakerState>,
+    threshold: u32,
+    cooldown: Duration,
+    open: IntGauge,
+}
+
+impl Breaker {
+    pub fn new(name: &'static str, threshold: u32, cooldown: Duration, open: IntGauge) -> Self {
+        Self {
+            name,
+            state: Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                open_until: None,
+                probing_since: None,
+            }),
+            threshold,
+            cooldown,
+            open,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        let mut state = self
+            .state
+            .lock()
+            .expect("breaker lock should not be poisoned");
+
+        let now = Instant::now();
+
+        match state.open_until {
+            Some(open_until) if open_until > now => true,
+            // a probe that never reported back, e.g. a dropped lookup, doesn't keep the breaker open
+            Some(_)
+                if state
+                    .probing_since
+                    .is_some_and(|since| now < since + self.cooldown) =>
+            {
+                true
+            }
+            Some(_) => {
+                // half-open, let this call through to probe the backend
+                state.probing_since = Some(now);
+
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("breaker lock should not be poisoned");
+
+        state.consecutive_failures = 0;
+
+        if state.probing_since.take().is_some() {
+            state.open_until = None;
+            self.open.set(0);
+        }
+    }
+
+    pub fn record_failure(&self, operation: &str, err: impl Display) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("breaker lock should not be poisoned");
+
+        state.consecutive_failures += 1;
+
+        if state.probing_since.is_some() || state.consecutive_failures >= self.threshold {
+            warn!(
+                "{} {operation} failed {} times in a row, bypassing {} for {:?}: {err}",
+                self.name, state.consecutive_failures, self.name, self.cooldown
+            );
+
+            state.open_until = Some(Instant::now() + self.cooldown);
+            state.consecutive_failures = 0;
+            state.probing_since = None;
+            self.open.set(1);
+        }
+    }
+}
+
+/// Counts cache failures in metrics and bypasses `inner` while its [`Breaker`] is open.
+pub struct CircuitBreaker {
+    inner: Box<dyn CacheLayer>,
+    breaker: Breaker,
+    errors: CounterVec,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        inner: Box<dyn CacheLayer>,
+        threshold: u32,
+        cooldown: Duration,
+        errors: CounterVec,
+        open: IntGauge,
+    ) -> Self {
+        Self {
+            inner,
+            breaker: Breaker::new("cache", threshold, cooldown, open),
+            errors,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.breaker.is_open()
+    }
+
+    fn record<T>(&self, operation: &str, result: Result<T, CacheError>) -> Result<T, CacheError> {
+        match &result {
+            Err(CacheError::Other(err)) => {
+                self.errors.with_label_values(&[operation]).inc();
+                self.breaker.record_failure(operation, err);
+            }
+            _ => self.breaker.record_success(),
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl CacheLayer for CircuitBreaker {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        if self.is_open() {
+            return Err(CacheError::Other("circuit open".to_string()));
+        }
+
+        self.record("get", self.inner.get(key).await)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        if self.is_open() {
+            return Err(CacheError::Other("circuit open".to_string()));
+        }
+
+        self.record("set", self.inner.set(key, value, expires).await)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        if self.is_open() {
+            return Err(CacheError::Other("circuit open".to_string()));
+        }
+
+        self.record("delete", self.inner.delete(key).await)
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        if self.is_open() {
+            return Err(CacheError::Other("circuit open".to_string()));
+        }
+
+        self.record("ttl", self.inner.ttl(key).await)
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        if self.is_open() {
+            return Err(CacheError::Other("circuit open".to_string()));
+        }
+
+        self.record("purge", self.inner.purge(prefix).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::async_trait;
+    use enstate_shared::cache::{CacheError, CacheLayer};
+
+    use super::CircuitBreaker;
+    use crate::telemetry::metrics::Metrics;
+
+    const COOLDOWN: Duration = Duration::from_millis(50);
+
+    /// Cache counting the calls that reach it, failing while `failing` is set
+    #[derive(Clone, Default)]
+    struct Backend {
+        failing: Arc<AtomicBool>,
+        calls: Arc<AtomicU32>,
+    }
+
+    impl Backend {
+        fn fail(&self, failing: bool) {
+            self.failing.store(failing, Ordering::SeqCst);
+        }
+
+        fn calls(&self) -> u32 {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl CacheLayer for Backend {
+        async fn get(&self, _key: &str) -> Result<Vec<u8>, CacheError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            if self.failing.load(Ordering::SeqCst) {
+                Err(CacheError::Other("unreachable".to_string()))
+            } else {
+                Err(CacheError::NotFound)
+            }
+        }
+
+        async fn set(&self, _key: &str, _value: &[u8], _expires: u32) -> Result<(), CacheError> {
+            unimplemented!()
+        }
+
+        async fn delete(&self, _key: &str) -> Result<(), CacheError> {
+            unimplemented!()
+        }
+
+        async fn ttl(&self, _key: &str) -> Result<Option<u32>, CacheError> {
+            unimplemented!()
+        }
+
+        async fn purge(&self, _prefix: &str) -> Result<u64, CacheError> {
+            unimplemented!()
+        }
+    }
+
+    fn breaker(backend: &Backend) -> (CircuitBreaker, Metrics) {
+        let metrics = Metrics::new();
+
+        let breaker = CircuitBreaker::new(
+            Box::new(backend.clone()),
+            3,
+            COOLDOWN,
+            metrics.cache_errors.clone(),
+            metrics.cache_circuit_open.clone(),
+        );
+
+        (breaker, metrics)
+    }
+
+    #[tokio::test]
+    async fn test_opens_after_threshold() {
+        let backend = Backend::default();
+        let (breaker, metrics) = breaker(&backend);
+
+        backend.fail(true);
+
+        for _ in 0..3 {
+            let _ = breaker.get("key").await;
+        }
+
+        assert_eq!(backend.calls(), 3);
+        assert_eq!(metrics.cache_circuit_open.get(), 1);
+
+        // open, the backend isn't called
+        assert!(matches!(
+            breaker.get("key").await,
+            Err(CacheError::Other(_))
+        ));
+        assert_eq!(backend.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_success_resets_failures() {
+        let backend = Backend::default();
+        let (breaker, metrics) = breaker(&backend);
+
+        for failing in [true, true, false, true, true] {
+            backend.fail(failing);
+
+            let _ = breaker.get("key").await;
+        }
+
+        // never 3 failures in a row
+        assert_eq!(metrics.cache_circuit_open.get(), 0);
+        assert!(matches!(
+            breaker.get("key").await,
+            Err(CacheError::Other(_))
+        ));
+        assert_eq!(backend.calls(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_probe() {
+        let backend = Backend::default();
+        let (breaker, metrics) = breaker(&backend);
+
+        backend.fail(true);
+
+        for _ in 0..3 {
+            let _ = breaker.get("key").await;
+        }
+
+        // after the cooldown a single failing probe opens the breaker again
+        tokio::time::sleep(COOLDOWN).await;
+
+        let _ = breaker.get("key").await;
+        assert_eq!(backend.calls(), 4);
+
+        let _ = breaker.get("key").await;
+        assert_eq!(backend.calls(), 4);
+        assert_eq!(metrics.cache_circuit_open.get(), 1);
+
+        // a succeeding probe closes it
+        tokio::time::sleep(COOLDOWN).await;
+        backend.fail(false);
+
+        assert!(matches!(
+            breaker.get("key").await,
+            Err(CacheError::NotFound)
+        ));
+        assert_eq!(metrics.cache_circuit_open.get(), 0);
+
+        assert!(matches!(
+            breaker.get("key").await,
+            Err(CacheError::NotFound)
+        ));
+        assert_eq!(backend.calls(), 6);
+    }
+}
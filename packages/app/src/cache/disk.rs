@@ -0,0 +1,368 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+// key -> big endian expiry (unix seconds) followed by the value
+const ENTRIES: TableDefinition<&str, &[u8]> = TableDefinition::new("entries");
+// (expiry, key) -> (), used to find expired and soonest expiring entries
+const EXPIRY: TableDefinition<(u64, &str), ()> = TableDefinition::new("expiry");
+
+/// Persistent cache backed by an embedded [redb](https://docs.rs/redb) database file.
+///
+/// Expired entries are ignored on read and removed by [`Disk::spawn_compaction`],
+/// which also evicts the soonest expiring entries once `max_entries` is exceeded.
+/// The pages they free are reused by later writes, so the file stays around the size
+/// `max_entries` needs without rewriting it.
+#[derive(Clone)]
+pub struct Disk {
+    db: Arc<Database>,
+    max_entries: u64,
+}
+
+impl Disk {
+    pub fn open(path: impl AsRef<Path>, max_entries: u64) -> Result<Self, redb::Error> {
+        let db = Database::create(path)?;
+
+        let txn = db.begin_write()?;
+        {
+            txn.open_table(ENTRIES)?;
+            txn.open_table(EXPIRY)?;
+        }
+        txn.commit()?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            max_entries,
+        })
+    }
+
+    pub fn spawn_compaction(&self, interval: Duration, shutdown: CancellationToken) {
+        let disk = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+
+            loop {
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+
+                let disk = disk.clone();
+                let result = tokio::task::spawn_blocking(move || disk.compact()).await;
+
+                match result {
+                    Ok(Ok(removed)) => info!("disk cache compacted, removed {removed} entries"),
+                    Ok(Err(err)) => warn!("disk cache compaction failed: {err}"),
+                    Err(err) => warn!("disk cache compaction panicked: {err}"),
+                }
+            }
+
+            info!("Disk cache compaction shutdown");
+        });
+    }
+
+    fn compact(&self) -> Result<u64, redb::Error> {
+        let now = unix_now();
+        let mut removed = 0;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut expiry = txn.open_table(EXPIRY)?;
+
+            let mut excess = entries.len()?.saturating_sub(self.max_entries);
+
+            loop {
+                let Some((expires_at, key)) = expiry.first()?.map(|(key, _)| {
+                    let (expires_at, key) = key.value();
+
+                    (expires_at, key.to_string())
+                }) else {
+                    break;
+                };
+
+                if expires_at > now && excess == 0 {
+                    break;
+                }
+
+                expiry.remove((expires_at, key.as_str()))?;
+                entries.remove(key.as_str())?;
+
+                excess = excess.saturating_sub(1);
+                removed += 1;
+            }
+        }
+        txn.commit()?;
+
+        Ok(removed)
+    }
+
+    fn get_blocking(&self, key: &str) -> Result<Option<Vec<u8>>, redb::Error> {
+        let txn = self.db.begin_read()?;
+        let entries = txn.open_table(ENTRIES)?;
+
+        let Some(entry) = entries.get(key)? else {
+            return Ok(None);
+        };
+
+        let (expires_at, value) = decode_entry(entry.value());
+
+        if expires_at <= unix_now() {
+            return Ok(None);
+        }
+
+        Ok(Some(value.to_vec()))
+    }
+
+    fn set_blocking(&self, key: &str, value: &[u8], expires: u32) -> Result<(), redb::Error> {
+        let expires_at = unix_now() + u64::from(expires);
+        let encoded = [&expires_at.to_be_bytes() as &[u8], value].concat();
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut expiry = txn.open_table(EXPIRY)?;
+
+            let previous = entries
+                .insert(key, encoded.as_slice())?
+                .map(|previous| decode_entry(previous.value()).0);
+
+            if let Some(previous) = previous {
+                expiry.remove((previous, key))?;
+            }
+
+            expiry.insert((expires_at, key), ())?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    fn delete_blocking(&self, key: &str) -> Result<(), redb::Error> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut expiry = txn.open_table(EXPIRY)?;
+
+            let previous = entries
+                .remove(key)?
+                .map(|previous| decode_entry(previous.value()).0);
+
+            if let Some(previous) = previous {
+                expiry.remove((previous, key))?;
+            }
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    fn ttl_blocking(&self, key: &str) -> Result<Option<u64>, redb::Error> {
+        let txn = self.db.begin_read()?;
+        let entries = txn.open_table(ENTRIES)?;
+
+        let Some(entry) = entries.get(key)? else {
+            return Ok(None);
+        };
+
+        let (expires_at, _) = decode_entry(entry.value());
+
+        Ok(expires_at
+            .checked_sub(unix_now())
+            .filter(|remaining| *remaining > 0))
+    }
+
+    fn purge_blocking(&self, prefix: &str) -> Result<u64, redb::Error> {
+        let txn = self.db.begin_write()?;
+        let removed = {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut expiry = txn.open_table(EXPIRY)?;
+
+            // keys are ordered, so every match sits in one contiguous range
+            let mut matching = Vec::new();
+            for entry in entries.range(prefix..)? {
+                let (key, value) = entry?;
+
+                if !key.value().starts_with(prefix) {
+                    break;
+                }
+
+                matching.push((key.value().to_string(), decode_entry(value.value()).0));
+            }
+
+            for (key, expires_at) in &matching {
+                entries.remove(key.as_str())?;
+                expiry.remove((*expires_at, key.as_str()))?;
+            }
+
+            matching.len() as u64
+        };
+        txn.commit()?;
+
+        Ok(removed)
+    }
+}
+
+fn decode_entry(entry: &[u8]) -> (u64, &[u8]) {
+    let (expires_at, value) = entry.split_at(8);
+
+    (
+        u64::from_be_bytes(expires_at.try_into().expect("entry should have an expiry")),
+        value,
+    )
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now()
+        .timestamp()
+        .try_into()
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl CacheLayer for Disk {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        let disk = self.clone();
+        let key = key.to_string();
+
+        tokio::task::spawn_blocking(move || disk.get_blocking(&key))
+            .await
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .ok_or(CacheError::NotFound)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        let disk = self.clone();
+        let (key, value) = (key.to_string(), value.to_vec());
+
+        tokio::task::spawn_blocking(move || disk.set_blocking(&key, &value, expires))
+            .await
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .map_err(|err| CacheError::Other(err.to_string()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        let disk = self.clone();
+        let key = key.to_string();
+
+        tokio::task::spawn_blocking(move || disk.delete_blocking(&key))
+            .await
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .map_err(|err| CacheError::Other(err.to_string()))
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        let disk = self.clone();
+        let key = key.to_string();
+
+        let remaining = tokio::task::spawn_blocking(move || disk.ttl_blocking(&key))
+            .await
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .ok_or(CacheError::NotFound)?;
+
+        Ok(Some(remaining.try_into().unwrap_or(u32::MAX)))
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        let disk = self.clone();
+        let prefix = prefix.to_string();
+
+        tokio::task::spawn_blocking(move || disk.purge_blocking(&prefix))
+            .await
+            .map_err(|err| CacheError::Other(err.to_string()))?
+            .map_err(|err| CacheError::Other(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use enstate_shared::cache::{CacheError, CacheLayer};
+    use tempfile::TempDir;
+
+    use super::Disk;
+
+    fn disk(max_entries: u64) -> (Disk, TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = Disk::open(dir.path().join("cache.redb"), max_entries).unwrap();
+
+        (disk, dir)
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_not_returned() {
+        let (cache, _dir) = disk(4);
+
+        cache.set("n:a.eth", b"a", 0).await.unwrap();
+        cache.set("n:b.eth", b"b", 60).await.unwrap();
+
+        assert!(matches!(
+            cache.get("n:a.eth").await,
+            Err(CacheError::NotFound)
+        ));
+        assert_eq!(cache.get("n:b.eth").await.unwrap(), b"b");
+    }
+
+    #[tokio::test]
+    async fn test_compaction_evicts_soonest_expiring() {
+        let (cache, _dir) = disk(2);
+
+        cache.set("n:expired.eth", b"e", 0).await.unwrap();
+        cache.set("n:a.eth", b"a", 60).await.unwrap();
+        cache.set("n:b.eth", b"b", 30).await.unwrap();
+        cache.set("n:c.eth", b"c", 120).await.unwrap();
+
+        // the expired entry first, then b as the one expiring soonest
+        assert_eq!(cache.compact().unwrap(), 2);
+
+        assert!(cache.get("n:b.eth").await.is_err());
+        assert_eq!(cache.get("n:a.eth").await.unwrap(), b"a");
+        assert_eq!(cache.get("n:c.eth").await.unwrap(), b"c");
+
+        assert_eq!(cache.compact().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_purge_only_removes_prefix() {
+        let (cache, _dir) = disk(4);
+
+        cache.set("n:a.eth", b"a", 60).await.unwrap();
+        cache.set("n:b.eth", b"b", 60).await.unwrap();
+        cache.set("a:0x00", b"c", 60).await.unwrap();
+        cache.set("o:n:c.eth", b"d", 60).await.unwrap();
+
+        assert_eq!(cache.purge("n:").await.unwrap(), 2);
+
+        assert!(cache.get("n:a.eth").await.is_err());
+        assert_eq!(cache.get("a:0x00").await.unwrap(), b"c");
+        assert_eq!(cache.get("o:n:c.eth").await.unwrap(), b"d");
+    }
+
+    #[tokio::test]
+    async fn test_ttl() {
+        let (cache, _dir) = disk(4);
+
+        cache.set("n:a.eth", b"a", 60).await.unwrap();
+        cache.set("n:b.eth", b"b", 0).await.unwrap();
+
+        let remaining = cache.ttl("n:a.eth").await.unwrap().unwrap();
+        assert!((59..=60).contains(&remaining));
+
+        assert!(matches!(
+            cache.ttl("n:b.eth").await,
+            Err(CacheError::NotFound)
+        ));
+        assert!(matches!(
+            cache.ttl("n:c.eth").await,
+            Err(CacheError::NotFound)
+        ));
+    }
+}
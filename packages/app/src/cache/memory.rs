@@ -0,0 +1,160 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+use lru::LruCache;
+
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Instant,
+}
+
+/// Bounded in-process cache, evicting the least recently used entry once
+/// `capacity` is reached. Expired entries are dropped lazily on read.
+pub struct Memory {
+    entries: Mutex<LruCache<String, Entry>>,
+}
+
+impl Memory {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+
+#[async_trait]
+impl CacheLayer for Memory {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("cache lock should not be poisoned");
+
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Ok(entry.value.clone()),
+            Some(_) => {
+                entries.pop(key);
+
+                Err(CacheError::NotFound)
+            }
+            None => Err(CacheError::NotFound),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("cache lock should not be poisoned");
+
+        entries.put(
+            key.to_string(),
+            Entry {
+                value: value.to_vec(),
+                expires_at: Instant::now() + Duration::from_secs(expires.into()),
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("cache lock should not be poisoned");
+
+        entries.pop(key);
+
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        let entries = self
+            .entries
+            .lock()
+            .expect("cache lock should not be poisoned");
+
+        // peek, so inspecting an entry doesn't promote it
+        let remaining = entries
+            .peek(key)
+            .and_then(|entry| entry.expires_at.checked_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or(CacheError::NotFound)?;
+
+        Ok(Some(remaining.as_secs().try_into().unwrap_or(u32::MAX)))
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("cache lock should not be poisoned");
+
+        let keys = entries
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for key in &keys {
+            entries.pop(key);
+        }
+
+        Ok(keys.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use enstate_shared::cache::CacheLayer;
+
+    use super::Memory;
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let cache = Memory::new(NonZeroUsize::new(2).unwrap());
+
+        cache.set("n:a.eth", b"a", 60).await.unwrap();
+        cache.set("n:b.eth", b"b", 60).await.unwrap();
+
+        // touch a, so b becomes the least recently used entry
+        assert_eq!(cache.get("n:a.eth").await.unwrap(), b"a");
+
+        cache.set("n:c.eth", b"c", 60).await.unwrap();
+
+        assert!(cache.get("n:b.eth").await.is_err());
+        assert_eq!(cache.get("n:a.eth").await.unwrap(), b"a");
+        assert_eq!(cache.get("n:c.eth").await.unwrap(), b"c");
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_not_returned() {
+        let cache = Memory::new(NonZeroUsize::new(2).unwrap());
+
+        cache.set("n:a.eth", b"a", 0).await.unwrap();
+
+        assert!(cache.get("n:a.eth").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purge_only_removes_prefix() {
+        let cache = Memory::new(NonZeroUsize::new(4).unwrap());
+
+        cache.set("n:a.eth", b"a", 60).await.unwrap();
+        cache.set("n:b.eth", b"b", 60).await.unwrap();
+        cache.set("a:0x00", b"c", 60).await.unwrap();
+
+        assert_eq!(cache.purge("n:").await.unwrap(), 2);
+
+        assert!(cache.get("n:a.eth").await.is_err());
+        assert_eq!(cache.get("a:0x00").await.unwrap(), b"c");
+        assert!(cache.ttl("a:0x00").await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,119 @@
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use enstate_shared::cache::{CacheLayer, PassthroughCacheLayer};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::{CacheBackend, CacheConfig};
+use crate::database;
+use crate::telemetry::metrics::Metrics;
+
+pub use self::breaker::{Breaker, CircuitBreaker};
+pub use self::disk::Disk;
+pub use self::memory::Memory;
+pub use self::namespaced::Namespaced;
+pub use self::reconnect::Reconnecting;
+pub use self::redis::Redis;
+pub use self::tiered::Tiered;
+
+mod breaker;
+mod disk;
+mod memory;
+mod namespaced;
+mod reconnect;
+mod redis;
+mod tiered;
+
+pub async fn setup(
+    config: &CacheConfig,
+    metrics: &Metrics,
+    shutdown: &CancellationToken,
+) -> Box<dyn CacheLayer> {
+    let backend = config.backend.unwrap_or(if config.redis_url.is_some() {
+        CacheBackend::Redis
+    } else {
+        CacheBackend::Memory
+    });
+
+    let memory = || {
+        let capacity =
+            NonZeroUsize::new(config.memory_capacity).expect("capacity should've been validated");
+
+        Box::new(Memory::new(capacity)) as Box<dyn CacheLayer>
+    };
+
+    let cache: Box<dyn CacheLayer> = match backend {
+        CacheBackend::None => {
+            warn!("caching disabled");
+
+            return Box::new(PassthroughCacheLayer {});
+        }
+        CacheBackend::Memory => {
+            info!("Using in-memory cache");
+
+            memory()
+        }
+        CacheBackend::Redis => Box::new(connect_redis(config, shutdown).await),
+        CacheBackend::Disk => match Disk::open(&config.disk_path, config.disk_max_entries) {
+            Ok(disk) => {
+                info!("Using disk cache at {}", config.disk_path.display());
+
+                disk.spawn_compaction(
+                    Duration::from_secs(config.disk_compaction_interval),
+                    shutdown.clone(),
+                );
+
+                Box::new(disk)
+            }
+            Err(err) => {
+                warn!("failed to open disk cache: {err}, using no cache");
+
+                return Box::new(PassthroughCacheLayer {});
+            }
+        },
+        CacheBackend::Tiered => Box::new(Tiered::new(
+            memory(),
+            Box::new(connect_redis(config, shutdown).await),
+            config.l1_ttl,
+        )),
+    };
+
+    Box::new(CircuitBreaker::new(
+        cache,
+        config.breaker_threshold,
+        Duration::from_secs(config.breaker_cooldown),
+        metrics.cache_errors.clone(),
+        metrics.cache_circuit_open.clone(),
+    ))
+}
+
+async fn connect_redis(config: &CacheConfig, shutdown: &CancellationToken) -> Reconnecting<Redis> {
+    let redis_url = config
+        .redis_url
+        .clone()
+        .expect("redis_url should've been validated");
+
+    info!("Connecting to Redis...");
+
+    match database::setup(&redis_url).await {
+        Ok(redis) => {
+            info!("Connected to Redis");
+
+            Reconnecting::connected(Redis::new(redis))
+        }
+        Err(err) => {
+            warn!("failed to connect to redis, running uncached until it becomes reachable: {err}");
+
+            Reconnecting::spawn(
+                move || {
+                    let redis_url = redis_url.clone();
+
+                    async move { database::setup(&redis_url).await.map(Redis::new) }
+                },
+                Duration::from_secs(config.reconnect_interval),
+                shutdown.clone(),
+            )
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+
+/// Prefixes every key, so networks can share a cache backend without their entries colliding.
+///
+/// The default network uses an empty prefix, keeping the keys it had before networks existed,
+/// purging all of its keys therefore purges those of the other networks too.
+pub struct Namespaced {
+    inner: Arc<dyn CacheLayer>,
+    prefix: String,
+}
+
+impl Namespaced {
+    pub fn new(inner: Arc<dyn CacheLayer>, prefix: String) -> Self {
+        Self { inner, prefix }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
+
+#[async_trait]
+impl CacheLayer for Namespaced {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        self.inner.get(&self.key(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        self.inner.set(&self.key(key), value, expires).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        self.inner.delete(&self.key(key)).await
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        self.inner.ttl(&self.key(key)).await
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        self.inner.purge(&self.key(prefix)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+
+    use enstate_shared::cache::CacheLayer;
+
+    use super::Namespaced;
+    use crate::cache::Memory;
+
+    #[tokio::test]
+    async fn test_networks_dont_collide() {
+        let shared: Arc<dyn CacheLayer> = Arc::new(Memory::new(NonZeroUsize::new(8).unwrap()));
+
+        let mainnet = Namespaced::new(shared.clone(), String::new());
+        let sepolia = Namespaced::new(shared.clone(), "sepolia:".to_string());
+
+        mainnet.set("n:luc.eth", b"mainnet", 60).await.unwrap();
+        sepolia.set("n:luc.eth", b"sepolia", 60).await.unwrap();
+
+        assert_eq!(mainnet.get("n:luc.eth").await.unwrap(), b"mainnet");
+        assert_eq!(sepolia.get("n:luc.eth").await.unwrap(), b"sepolia");
+
+        assert_eq!(sepolia.purge("n:").await.unwrap(), 1);
+        assert!(sepolia.get("n:luc.eth").await.is_err());
+        assert_eq!(mainnet.get("n:luc.eth").await.unwrap(), b"mainnet");
+    }
+}
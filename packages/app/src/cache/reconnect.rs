@@ -0,0 +1,194 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::Redis;
+
+/// Connection, e.g. to Redis, that can start disconnected.
+///
+/// Until a connection is established it's missing, while a background task keeps
+/// retrying every `interval` and swaps the connection in once reachable. As a cache it
+/// then behaves like a passthrough cache.
+pub struct Reconnecting<T> {
+    current: Arc<RwLock<Option<T>>>,
+}
+
+impl<T> Clone for Reconnecting<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Reconnecting<T> {
+    pub fn connected(connection: T) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Some(connection))),
+        }
+    }
+
+    /// Keeps calling `connect` every `interval` until it succeeds or `shutdown` is cancelled
+    pub fn spawn<F, Fut, E>(connect: F, interval: Duration, shutdown: CancellationToken) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send,
+        E: Display,
+    {
+        let layer = Self {
+            current: Arc::new(RwLock::new(None)),
+        };
+
+        let handle = layer.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    biased;
+
+                    () = shutdown.cancelled() => break,
+                    () = tokio::time::sleep(interval) => {}
+                }
+
+                match connect().await {
+                    Ok(connection) => {
+                        info!("Reconnected");
+
+                        *handle
+                            .current
+                            .write()
+                            .expect("connection lock should not be poisoned") = Some(connection);
+
+                        break;
+                    }
+                    Err(err) => warn!("failed to connect, retrying in {interval:?}: {err}"),
+                }
+            }
+        });
+
+        layer
+    }
+
+    pub fn current(&self) -> Option<T> {
+        self.current
+            .read()
+            .expect("connection lock should not be poisoned")
+            .clone()
+    }
+}
+
+#[async_trait]
+impl CacheLayer for Reconnecting<Redis> {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        match self.current() {
+            Some(redis) => redis.get(key).await,
+            None => Err(CacheError::NotFound),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        match self.current() {
+            Some(redis) => redis.set(key, value, expires).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        match self.current() {
+            Some(redis) => redis.delete(key).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        match self.current() {
+            Some(redis) => redis.ttl(key).await,
+            None => Err(CacheError::NotFound),
+        }
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        match self.current() {
+            Some(redis) => redis.purge(prefix).await,
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::Reconnecting;
+
+    /// Connects on the `succeed_at`th attempt, counting the attempts
+    fn connect(
+        attempts: &Arc<AtomicU32>,
+        succeed_at: u32,
+    ) -> impl Fn() -> std::future::Ready<Result<u32, String>> {
+        let attempts = attempts.clone();
+
+        move || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+
+            std::future::ready(if attempt >= succeed_at {
+                Ok(attempt)
+            } else {
+                Err("refused".to_string())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_every_interval() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let interval = Duration::from_millis(20);
+
+        let started = Instant::now();
+        let layer = Reconnecting::spawn(connect(&attempts, 3), interval, CancellationToken::new());
+
+        assert_eq!(layer.current(), None);
+
+        while layer.current().is_none() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // waits an interval before every attempt, stops after the first success
+        assert_eq!(layer.current(), Some(3));
+        assert!(started.elapsed() >= interval * 3);
+
+        tokio::time::sleep(interval * 3).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_shutdown() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let shutdown = CancellationToken::new();
+
+        let layer = Reconnecting::spawn(
+            connect(&attempts, u32::MAX),
+            Duration::from_millis(10),
+            shutdown.clone(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(35)).await;
+        shutdown.cancel();
+
+        let tried = attempts.load(Ordering::SeqCst);
+        assert!(tried > 0);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), tried);
+        assert_eq!(layer.current(), None);
+    }
+}
@@ -0,0 +1,125 @@
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use crate::webhooks;
+
+#[derive(Clone)]
+pub struct Redis {
+    redis: ConnectionManager,
+}
+
+impl Redis {
+    pub const fn new(redis: ConnectionManager) -> Self {
+        Self { redis }
+    }
+}
+
+#[async_trait]
+impl CacheLayer for Redis {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        let mut redis = self.redis.clone();
+
+        let x: Result<Option<Vec<u8>>, _> = redis.get(key).await;
+
+        match x {
+            Ok(Some(x)) => Ok(x),
+            Ok(None) => Err(CacheError::NotFound),
+            Err(error) => Err(CacheError::Other(error.to_string())),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        let mut redis = self.redis.clone();
+
+        let x: Result<(), _> = redis
+            .set_ex(
+                key,
+                value,
+                expires.into(),
+            )
+            .await;
+
+        match x {
+            Ok(()) => Ok(()),
+            Err(error) => Err(CacheError::Other(error.to_string())),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        let mut redis = self.redis.clone();
+
+        let x: Result<(), _> = redis.del(key).await;
+
+        x.map_err(|error| CacheError::Other(error.to_string()))
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        let mut redis = self.redis.clone();
+
+        let x: Result<i64, _> = redis.ttl(key).await;
+
+        match x {
+            // -2 means the key does not exist, -1 that it has no expiry
+            Ok(-2) => Err(CacheError::NotFound),
+            Ok(-1) => Ok(None),
+            Ok(ttl) => Ok(Some(ttl.try_into().unwrap_or_default())),
+            Err(error) => Err(CacheError::Other(error.to_string())),
+        }
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        let mut redis = self.redis.clone();
+
+        let pattern = format!("{}*", escape_pattern(prefix));
+        let mut cursor = 0u64;
+        let mut deleted = 0u64;
+
+        loop {
+            let (next, mut keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query_async(&mut redis)
+                .await
+                .map_err(|error| CacheError::Other(error.to_string()))?;
+
+            // webhook subscriptions may share the database, they're not part of the cache
+            keys.retain(|key| !key.starts_with(webhooks::KEY_PREFIX));
+
+            if !keys.is_empty() {
+                let removed: u64 = redis
+                    .del(&keys)
+                    .await
+                    .map_err(|error| CacheError::Other(error.to_string()))?;
+
+                deleted += removed;
+            }
+
+            if next == 0 {
+                break;
+            }
+
+            cursor = next;
+        }
+
+        Ok(deleted)
+    }
+}
+
+// escapes glob characters so the prefix is matched literally by SCAN
+fn escape_pattern(prefix: &str) -> String {
+    let mut escaped = String::with_capacity(prefix.len());
+
+    for char in prefix.chars() {
+        if matches!(char, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+
+        escaped.push(char);
+    }
+
+    escaped
+}
@@ -0,0 +1,171 @@
+use axum::async_trait;
+use enstate_shared::cache::{CacheError, CacheLayer};
+
+/// Two-level cache: a small, short-lived `l1` (usually [`super::Memory`])
+/// in front of a shared `l2` (usually [`super::Redis`]).
+///
+/// Writes go to both levels independently, so `l1` keeps serving while `l2` is
+/// unreachable. `l1` entries never outlive `l1_ttl` or their `l2` entry, so
+/// instances converge on `l2` quickly.
+pub struct Tiered {
+    l1: Box<dyn CacheLayer>,
+    l2: Box<dyn CacheLayer>,
+    l1_ttl: u32,
+}
+
+impl Tiered {
+    pub fn new(l1: Box<dyn CacheLayer>, l2: Box<dyn CacheLayer>, l1_ttl: u32) -> Self {
+        Self { l1, l2, l1_ttl }
+    }
+}
+
+#[async_trait]
+impl CacheLayer for Tiered {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError> {
+        if let Ok(value) = self.l1.get(key).await {
+            return Ok(value);
+        }
+
+        let value = self.l2.get(key).await?;
+
+        // an entry without expiry only lives `l1_ttl`, one that can't be inspected isn't copied
+        let ttl = match self.l2.ttl(key).await {
+            Ok(Some(ttl)) => ttl.min(self.l1_ttl),
+            Ok(None) => self.l1_ttl,
+            Err(_) => 0,
+        };
+
+        if ttl > 0 {
+            let _ = self.l1.set(key, &value, ttl).await;
+        }
+
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError> {
+        let l1 = self.l1.set(key, value, expires.min(self.l1_ttl)).await;
+
+        self.l2.set(key, value, expires).await.and(l1)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CacheError> {
+        // never leave a stale copy behind, also when `l2` fails
+        let l1 = self.l1.delete(key).await;
+
+        self.l2.delete(key).await.and(l1)
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError> {
+        self.l2.ttl(key).await
+    }
+
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError> {
+        let deleted = self.l2.purge(prefix).await?;
+
+        // l1 only ever holds copies of l2 entries, so its count isn't added
+        self.l1.purge(prefix).await?;
+
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+
+    use axum::async_trait;
+    use enstate_shared::cache::{CacheError, CacheLayer};
+
+    use super::Tiered;
+    use crate::cache::{Memory, Namespaced};
+
+    /// Shared cache that can't be reached
+    struct Unreachable;
+
+    #[async_trait]
+    impl CacheLayer for Unreachable {
+        async fn get(&self, _key: &str) -> Result<Vec<u8>, CacheError> {
+            Err(CacheError::Other("unreachable".to_string()))
+        }
+
+        async fn set(&self, _key: &str, _value: &[u8], _expires: u32) -> Result<(), CacheError> {
+            Err(CacheError::Other("unreachable".to_string()))
+        }
+
+        async fn delete(&self, _key: &str) -> Result<(), CacheError> {
+            Err(CacheError::Other("unreachable".to_string()))
+        }
+
+        async fn ttl(&self, _key: &str) -> Result<Option<u32>, CacheError> {
+            Err(CacheError::Other("unreachable".to_string()))
+        }
+
+        async fn purge(&self, _prefix: &str) -> Result<u64, CacheError> {
+            Err(CacheError::Other("unreachable".to_string()))
+        }
+    }
+
+    fn memory() -> Arc<dyn CacheLayer> {
+        Arc::new(Memory::new(NonZeroUsize::new(16).unwrap()))
+    }
+
+    /// Tiered cache over `l1` and `l2`, which stay inspectable
+    fn tiered(l1: &Arc<dyn CacheLayer>, l2: &Arc<dyn CacheLayer>) -> Tiered {
+        Tiered::new(
+            Box::new(Namespaced::new(l1.clone(), String::new())),
+            Box::new(Namespaced::new(l2.clone(), String::new())),
+            60,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_refill_keeps_l2_expiry() {
+        let (l1, l2) = (memory(), memory());
+        let cache = tiered(&l1, &l2);
+
+        l2.set("n:luc.eth", b"profile", 5).await.unwrap();
+
+        assert_eq!(cache.get("n:luc.eth").await.unwrap(), b"profile");
+
+        // copied to l1, but not for longer than the l2 entry lives
+        assert_eq!(l1.get("n:luc.eth").await.unwrap(), b"profile");
+        assert!(l1.ttl("n:luc.eth").await.unwrap().unwrap() <= 5);
+    }
+
+    #[tokio::test]
+    async fn test_set_without_l2() {
+        let l1 = memory();
+        let l2: Arc<dyn CacheLayer> = Arc::new(Unreachable);
+        let cache = tiered(&l1, &l2);
+
+        assert!(cache.set("n:luc.eth", b"profile", 600).await.is_err());
+
+        // l1 still serves the entry, for at most l1_ttl
+        assert_eq!(cache.get("n:luc.eth").await.unwrap(), b"profile");
+        assert!(l1.ttl("n:luc.eth").await.unwrap().unwrap() <= 60);
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_both_levels() {
+        let (l1, l2) = (memory(), memory());
+        let cache = tiered(&l1, &l2);
+
+        cache.set("n:luc.eth", b"profile", 600).await.unwrap();
+        assert!(l1.get("n:luc.eth").await.is_ok());
+        assert!(l2.get("n:luc.eth").await.is_ok());
+
+        cache.delete("n:luc.eth").await.unwrap();
+        assert!(l1.get("n:luc.eth").await.is_err());
+        assert!(l2.get("n:luc.eth").await.is_err());
+
+        // a failing l2 doesn't leave the l1 copy behind
+        let unreachable: Arc<dyn CacheLayer> = Arc::new(Unreachable);
+        let cache = tiered(&l1, &unreachable);
+
+        l1.set("n:luc.eth", b"profile", 60).await.unwrap();
+
+        assert!(cache.delete("n:luc.eth").await.is_err());
+        assert!(l1.get("n:luc.eth").await.is_err());
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use enstate_shared::cache::envelope;
+use enstate_shared::core::address::AddressResolveError;
+use enstate_shared::core::error::ProfileError;
+use enstate_shared::core::profile::PROFILE_SCHEMA_VERSION;
+use enstate_shared::core::Profile;
+use enstate_shared::observer::Observer;
+use enstate_shared::utils::eip55::EIP55Address;
+use ethers_core::types::Address;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::state::AppState;
+
+/// Snapshots outlive any profile TTL, so changes are noticed however long a name goes unresolved.
+const SNAPSHOT_TTL: u32 = 30 * 24 * 60 * 60;
+
+/// Publishes every profile resolved from chain to `resolved`.
+pub struct ResolvedFeed(pub broadcast::Sender<Profile>);
+
+impl Observer for ResolvedFeed {
+    fn profile_resolved(&self, profile: &Profile) {
+        // no receivers is fine, nobody is watching yet
+        let _ = self.0.send(profile.clone());
+    }
+}
+
+/// Names and addresses someone wants changes for, keyed like the cache (`n:` and `a:`).
+///
+/// Every owner (a webhook registry, a connection, ...) replaces its own set of keys,
+/// a key stays watched as long as any owner still watches it.
+#[derive(Default)]
+pub struct Watchlist {
+    owners: Mutex<HashMap<String, HashSet<String>>>,
+    counts: DashMap<String, usize>,
+}
+
+impl Watchlist {
+    pub fn set(&self, owner: &str, keys: HashSet<String>) {
+        let mut owners = self
+            .owners
+            .lock()
+            .expect("watchlist lock should not be poisoned");
+
+        let previous = owners.remove(owner).unwrap_or_default();
+
+        for key in keys.difference(&previous) {
+            *self.counts.entry(key.clone()).or_default() += 1;
+        }
+
+        for key in previous.difference(&keys) {
+            self.decrement(key);
+        }
+
+        if !keys.is_empty() {
+            owners.insert(owner.to_string(), keys);
+        }
+    }
+
+    pub fn remove(&self, owner: &str) {
+        self.set(owner, HashSet::new());
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.counts.contains_key(key)
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.counts
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    pub fn is_watched(&self, profile: &Profile) -> bool {
+        self.counts.contains_key(&name_key(&profile.name))
+            || profile
+                .address
+                .as_ref()
+                .is_some_and(|address| self.counts.contains_key(&address_key(&address.0)))
+    }
+
+    fn decrement(&self, key: &str) {
+        self.counts.remove_if_mut(key, |_, count| {
+            *count -= 1;
+
+            *count == 0
+        });
+    }
+}
+
+pub fn name_key(name: &str) -> String {
+    format!("n:{name}")
+}
+
+pub fn address_key(address: &Address) -> String {
+    format!("a:{address:?}")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileChange {
+    pub name: String,
+    pub address: Option<EIP55Address>,
+    /// Changed fields, text records are prefixed with `records.` and addresses with `chains.`
+    pub changes: BTreeMap<String, FieldChange>,
+    pub profile: Profile,
+}
+
+/// Field by field difference between two profiles, ignoring lookup metadata
+/// like `fresh`, `errors` and `ccip_urls`.
+pub fn diff(old: &Profile, new: &Profile) -> BTreeMap<String, FieldChange> {
+    let mut changes = BTreeMap::new();
+
+    let mut compare = |key: String, old: Option<String>, new: Option<String>| {
+        if old != new {
+            changes.insert(key, FieldChange { old, new });
+        }
+    };
+
+    compare(
+        "address".to_string(),
+        old.address.as_ref().map(ToString::to_string),
+        new.address.as_ref().map(ToString::to_string),
+    );
+    compare("avatar".to_string(), old.avatar.clone(), new.avatar.clone());
+    compare("header".to_string(), old.header.clone(), new.header.clone());
+    compare(
+        "display".to_string(),
+        Some(old.display.clone()),
+        Some(new.display.clone()),
+    );
+    compare(
+        "contenthash".to_string(),
+        old.contenthash.clone(),
+        new.contenthash.clone(),
+    );
+    compare(
+        "resolver".to_string(),
+        Some(old.resolver.to_string()),
+        Some(new.resolver.to_string()),
+    );
+
+    for (prefix, old, new) in [
+        ("records", &old.records, &new.records),
+        ("chains", &old.chains, &new.chains),
+    ] {
+        for key in old.keys().chain(new.keys()).collect::<HashSet<_>>() {
+            compare(
+                format!("{prefix}.{key}"),
+                old.get(key).cloned(),
+                new.get(key).cloned(),
+            );
+        }
+    }
+
+    changes
+}
+
+/// Compares every freshly resolved profile of a watched name against its last
+/// snapshot in the cache, and publishes the differences to `state.changes`.
+pub struct ChangeDetector;
+
+impl ChangeDetector {
+    pub fn spawn(state: Arc<AppState>, shutdown: CancellationToken) -> JoinHandle<()> {
+        let mut resolved = state.resolved.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                let profile = tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    profile = resolved.recv() => profile,
+                };
+
+                match profile {
+                    Ok(profile) => Self::detect(&state, profile).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("change detector lagging behind, skipped {skipped} profiles");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+
+            info!("Change detector shutdown");
+        })
+    }
+
+    /// Stores `profile` as the snapshot of its name, unless one already exists,
+    /// so the next resolution has something to compare against.
+    pub async fn baseline(state: &AppState, profile: &Profile) {
+        if profile.partial {
+            return;
+        }
+
+        if Self::load_snapshot(state, &profile.name).await.is_none() {
+            Self::store_snapshot(state, profile).await;
+        }
+    }
+
+    async fn detect(state: &AppState, profile: Profile) {
+        // records missing from a partial profile would show up as removed, and as added
+        // again on the next full resolution
+        if profile.partial || !state.watchlist.is_watched(&profile) {
+            return;
+        }
+
+        let previous = Self::load_snapshot(state, &profile.name).await;
+
+        Self::store_snapshot(state, &profile).await;
+
+        // the first snapshot of a name has nothing to compare against
+        let Some(previous) = previous else {
+            return;
+        };
+
+        let changes = diff(&previous, &profile);
+
+        if changes.is_empty() {
+            return;
+        }
+
+        let _ = state.changes.send(Arc::new(ProfileChange {
+            name: profile.name.clone(),
+            address: profile.address.clone(),
+            changes,
+            profile,
+        }));
+    }
+
+    async fn load_snapshot(state: &AppState, name: &str) -> Option<Profile> {
+        let value = state.service.cache.get(&snapshot_key(name)).await.ok()?;
+
+        envelope::decode::<Profile>(&value, PROFILE_SCHEMA_VERSION)
+            .ok()
+            .map(|(_, profile)| profile)
+    }
+
+    async fn store_snapshot(state: &AppState, profile: &Profile) {
+        let key = snapshot_key(&profile.name);
+
+        match envelope::encode(profile, PROFILE_SCHEMA_VERSION, true, SNAPSHOT_TTL) {
+            Ok(encoded) => {
+                if let Err(err) = state.service.cache.set(&key, &encoded, SNAPSHOT_TTL).await {
+                    warn!(key, "failed to store profile snapshot: {err}");
+                }
+            }
+            Err(err) => warn!(key, "failed to encode profile snapshot: {err}"),
+        }
+    }
+}
+
+/// Re-resolves every watched name and the primary name of every watched address
+/// each `websocket.refresh_interval` seconds, so changes are noticed without the log
+/// follower, which re-resolves watched names as soon as their records change instead.
+pub struct WatchRefresher {
+    state: Arc<AppState>,
+}
+
+impl WatchRefresher {
+    pub fn spawn(
+        state: Arc<AppState>,
+        config: &Config,
+        shutdown: CancellationToken,
+    ) -> Option<JoinHandle<()>> {
+        if config.follower.enabled || config.websocket.refresh_interval == 0 {
+            return None;
+        }
+
+        let refresher = Self { state };
+        let period = Duration::from_secs(config.websocket.refresh_interval);
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    () = refresher.run() => {}
+                }
+            }
+
+            info!("Watch refresher shutdown");
+        }))
+    }
+
+    async fn run(&self) {
+        let service = &self.state.service;
+        let (mut names, addresses) = watched(self.state.watchlist.keys());
+
+        for address in addresses {
+            match service.primary_name(&address, true).await {
+                Ok(name) => {
+                    names.insert(name);
+                }
+                Err(AddressResolveError::NotFound) => {}
+                Err(err) => {
+                    warn!(address = ?address, "failed to refresh watched primary name: {err}")
+                }
+            }
+        }
+
+        for name in names {
+            match service.resolve_name_profile(name.clone(), true).await {
+                Ok(_) | Err(ProfileError::NotFound) => {}
+                Err(err) => warn!(name, "failed to refresh watched profile: {err}"),
+            }
+        }
+    }
+}
+
+/// Names and addresses of watchlist keys
+fn watched(keys: Vec<String>) -> (HashSet<String>, Vec<Address>) {
+    let mut names = HashSet::new();
+    let mut addresses = Vec::new();
+
+    for key in keys {
+        if let Some(name) = key.strip_prefix("n:") {
+            names.insert(name.to_string());
+        } else if let Some(address) = key.strip_prefix("a:").and_then(|it| it.parse().ok()) {
+            addresses.push(address);
+        }
+    }
+
+    (names, addresses)
+}
+
+fn snapshot_key(name: &str) -> String {
+    format!("snapshot:{name}")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use enstate_shared::core::Profile;
+    use enstate_shared::utils::eip55::EIP55Address;
+    use ethers_core::types::Address;
+
+    use super::{address_key, diff, watched, FieldChange, Watchlist};
+
+    /// Profile shared by the tests of the modules acting on changes
+    pub(crate) fn profile() -> Profile {
+        Profile {
+            name: "luc.eth".to_string(),
+            address: None,
+            avatar: Some("https://example.com/a.png".to_string()),
+            header: None,
+            display: "luc.eth".to_string(),
+            contenthash: None,
+            records: BTreeMap::from([("com.twitter".to_string(), "lucemans".to_string())]),
+            chains: BTreeMap::new(),
+            fresh: 0,
+            resolver: EIP55Address(Address::zero()),
+            ccip_urls: Vec::new(),
+            backend: None,
+            errors: BTreeMap::new(),
+            error_codes: BTreeMap::new(),
+            partial: false,
+            safety: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = profile();
+        let mut new = profile();
+
+        new.fresh = 1;
+        new.avatar = None;
+        new.records
+            .insert("com.github".to_string(), "lucemans".to_string());
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(
+            changes.keys().collect::<Vec<_>>(),
+            ["avatar", "records.com.github"]
+        );
+        assert_eq!(
+            changes["records.com.github"],
+            FieldChange {
+                old: None,
+                new: Some("lucemans".to_string()),
+            }
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_watchlist_counts_owners() {
+        let watchlist = Watchlist::default();
+        let keys = HashSet::from(["n:luc.eth".to_string()]);
+
+        watchlist.set("a", keys.clone());
+        watchlist.set("b", keys);
+        watchlist.remove("a");
+
+        assert!(watchlist.is_watched(&profile()));
+
+        watchlist.remove("b");
+
+        assert!(!watchlist.is_watched(&profile()));
+    }
+
+    #[test]
+    fn test_watched() {
+        let watchlist = Watchlist::default();
+        let address = Address::repeat_byte(1);
+
+        watchlist.set(
+            "a",
+            HashSet::from(["n:luc.eth".to_string(), address_key(&address)]),
+        );
+        watchlist.set("b", HashSet::from(["n:luc.eth".to_string()]));
+
+        let (names, addresses) = watched(watchlist.keys());
+
+        assert_eq!(names, HashSet::from(["luc.eth".to_string()]));
+        assert_eq!(addresses, [address]);
+    }
+}
@@ -0,0 +1,1294 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use enstate_shared::core::resolvers::registry::ENS_REGISTRY;
+use enstate_shared::models::{multicoin::cointype::Coins, records::Records};
+use ethers_core::types::{H160, U256};
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// First path segments of the routes, which networks can't be named after
+/// as they're selectable with a `/{network}` path prefix
+const RESERVED_NETWORK_NAMES: &[&str] = &[
+    "a", "n", "u", "i", "h", "bulk", "sse", "ws", "v2", "docs", "this", "metrics", "admin",
+];
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse config file {path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid configuration:\n{}", .0.iter().map(|it| format!("  - {it}")).collect::<Vec<_>>().join("\n"))]
+    Invalid(Vec<String>),
+}
+
+/// Typed application configuration.
+///
+/// Loaded from a TOML file (`CONFIG_PATH`, or `config.toml` if present),
+/// after which the legacy environment variables are applied as overrides.
+/// Only the `rate_limit` section is re-applied on SIGHUP.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub max_bulk_size: usize,
+    /// Name of the network configured by `rpc`, used when a request doesn't select one
+    pub network: String,
+    pub profile: ProfileConfig,
+    pub rpc: RpcConfig,
+    /// Additional networks by name, selectable with `?network=` or a `/{network}` path prefix
+    pub networks: BTreeMap<String, RpcConfig>,
+    /// Network the default network resolves names under a suffix (a TLD or any parent name)
+    /// on, e.g. `gno = "gnosis"`
+    pub routes: BTreeMap<String, String>,
+    pub gateways: GatewayConfig,
+    pub cache: CacheConfig,
+    pub rate_limit: RateLimitConfig,
+    pub opensea: OpenSeaConfig,
+    pub discovery: DiscoveryConfig,
+    pub admin: AdminConfig,
+    pub popularity: PopularityConfig,
+    pub warmer: WarmerConfig,
+    pub follower: FollowerConfig,
+    pub webhooks: WebhooksConfig,
+    pub websocket: WebSocketConfig,
+    pub multicall: MulticallConfig,
+    pub concurrency: ConcurrencyConfig,
+    pub healing: HealingConfig,
+    pub safety: SafetyConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// Text records looked up for every profile
+    pub records: Vec<String>,
+    /// Coin types looked up for every profile
+    pub chains: Vec<u64>,
+    /// Leave records of reverting resolvers and failing gateways unset, rather than listing them in `errors`
+    pub lenient_records: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// RPC endpoints, picked at random per lookup
+    pub urls: Vec<Url>,
+    /// Required with the `universal` resolution
+    pub universal_resolver: Option<H160>,
+    pub resolution: Resolution,
+    /// ENS registry resolvers are looked up in
+    pub registry: H160,
+    /// Reverse registrar asked for the reverse node of addresses, instead of using `addr.reverse`
+    pub reverse_registrar: Option<H160>,
+    /// Milliseconds a lookup may take before it's returned with the records that arrived
+    pub lookup_timeout: Option<u64>,
+    /// Milliseconds a single resolver call may take, gateway requests included
+    pub call_timeout: Option<u64>,
+}
+
+/// How names are resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// In a single call to the universal resolver
+    #[default]
+    Universal,
+    /// By walking the registry to the name's resolver and calling it directly,
+    /// for deployments without a universal resolver
+    Registry,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "universal" => Ok(Self::Universal),
+            "registry" => Ok(Self::Registry),
+            _ => Err("expected one of universal, registry".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GatewayConfig {
+    pub ipfs: Url,
+    pub arweave: Url,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Defaults to `redis` when a redis url is set, `memory` otherwise
+    pub backend: Option<CacheBackend>,
+    pub redis_url: Option<String>,
+    /// Time in seconds a resolved profile stays cached
+    pub profile_ttl: u32,
+    /// Time in seconds a profile with timed out records stays cached
+    pub partial_ttl: u32,
+    /// Deflate cached profiles
+    pub compression: bool,
+    /// Maximum number of entries kept by the in-memory cache
+    pub memory_capacity: usize,
+    /// Time in seconds entries stay in the in-memory level of the `tiered` backend
+    pub l1_ttl: u32,
+    /// Database file used by the `disk` backend
+    pub disk_path: PathBuf,
+    /// Maximum number of entries kept by the `disk` backend
+    pub disk_max_entries: u64,
+    /// Time in seconds between sweeps of expired and excess entries of the `disk` backend
+    pub disk_compaction_interval: u64,
+    /// Consecutive cache failures after which the cache is bypassed
+    pub breaker_threshold: u32,
+    /// Time in seconds the cache is bypassed for once the breaker opens
+    pub breaker_cooldown: u64,
+    /// Time in seconds between attempts to reach an unavailable Redis
+    pub reconnect_interval: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    None,
+    Memory,
+    Redis,
+    Tiered,
+    Disk,
+}
+
+impl FromStr for CacheBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "memory" => Ok(Self::Memory),
+            "redis" => Ok(Self::Redis),
+            "tiered" => Ok(Self::Tiered),
+            "disk" => Ok(Self::Disk),
+            _ => Err("expected one of none, memory, redis, tiered, disk".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Limits keyed by matched route path, e.g. `/n/:name`
+    pub routes: BTreeMap<String, RouteLimitConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteLimitConfig {
+    pub requests: u32,
+    /// Window length in seconds
+    pub window: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenSeaConfig {
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    pub meilisearch_url: Option<Url>,
+    pub meilisearch_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token for the `/admin` routes, which are not mounted when unset
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PopularityConfig {
+    pub enabled: bool,
+    /// Window name -> half life in seconds, selectable with `/v2/popular?window=`
+    pub windows: BTreeMap<String, u64>,
+    /// Maximum number of names or addresses tracked per window
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarmerConfig {
+    pub enabled: bool,
+    /// Time in seconds between warming runs
+    pub interval: u64,
+    /// Number of most popular names in `window` to keep warm, requires `popularity`
+    pub top_n: usize,
+    pub window: String,
+    /// Names to always keep warm
+    pub names: Vec<String>,
+    /// File with additional names to keep warm, one per line, re-read every run
+    pub names_file: Option<PathBuf>,
+    /// Re-resolve profiles whose cache entry expires within this many seconds
+    pub refresh_before: u32,
+    /// Maximum number of profiles re-resolved per run
+    pub rpc_budget: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FollowerConfig {
+    pub enabled: bool,
+    /// ENS registry emitting `NewResolver` and `Transfer`, the default network's when unset
+    pub registry: Option<H160>,
+    /// Time in seconds between `eth_getLogs` polls
+    pub poll_interval: u64,
+    /// Maximum number of blocks requested per poll
+    pub max_block_range: u64,
+    /// Number of blocks re-scanned when a reorg is detected
+    pub reorg_window: u64,
+    /// File the last processed block is persisted to
+    pub cursor_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    pub enabled: bool,
+    /// Delivery attempts per event before giving up
+    pub max_attempts: u32,
+    /// Delay in seconds before the first retry, doubled on every following attempt
+    pub backoff_base: u64,
+    /// Upper bound in seconds for the retry delay
+    pub backoff_max: u64,
+    /// Request timeout in seconds for a single delivery
+    pub timeout: u64,
+    pub max_subscriptions: usize,
+    /// Time in seconds between reloading subscriptions from the store
+    pub refresh_interval: u64,
+    /// Where subscriptions and delivery state are kept, defaults to `redis` when a redis
+    /// url is set, `disk` otherwise
+    pub store: Option<WebhookStore>,
+    /// Database file of the `disk` store
+    pub store_path: PathBuf,
+}
+
+/// Stores of webhook subscriptions, neither of which evicts them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookStore {
+    /// `cache.redis_url`, shared by every instance, its `maxmemory-policy` should be `noeviction`
+    Redis,
+    /// Local database file at `store_path`
+    Disk,
+}
+
+impl FromStr for WebhookStore {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "redis" => Ok(Self::Redis),
+            "disk" => Ok(Self::Disk),
+            _ => Err("expected one of redis, disk".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// Maximum number of names and addresses a single `/ws` connection can subscribe to
+    pub max_subscriptions: usize,
+    /// Time in seconds between re-resolving watched names while the log follower is
+    /// disabled, so `/ws` and webhooks still notice changes, 0 to not re-resolve them
+    pub refresh_interval: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MulticallConfig {
+    /// Batch the universal resolver calls of concurrent lookups into Multicall3 `aggregate3` calls
+    pub enabled: bool,
+    /// Multicall3 deployment
+    pub address: H160,
+    /// Time in milliseconds to wait for more calls before sending a batch
+    pub window: u64,
+    /// Maximum number of calls per batch
+    pub max_calls: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    pub enabled: bool,
+    /// Maximum number of lookups resolving from chain at once, across all providers
+    pub max_in_flight: usize,
+    /// Maximum number of lookups resolving at once per RPC url
+    pub per_provider: usize,
+    /// Lookups waiting for a slot before new ones are rejected with a 503
+    pub max_queue: usize,
+    /// `Retry-After` in seconds sent with a 503
+    pub retry_after: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealingConfig {
+    /// Rainbow table file encoded labels are healed from, loaded at startup
+    pub rainbow_table: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafetyConfig {
+    /// Add a report of confusable and invisible characters to every profile
+    pub enabled: bool,
+    /// Number of most popular names in `window` lookalikes are reported for, requires `popularity`
+    pub top_n: usize,
+    pub window: String,
+    /// Time in seconds between refreshes of the popular names
+    pub refresh_interval: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 3000,
+            max_bulk_size: 10,
+            network: "mainnet".to_string(),
+            profile: ProfileConfig::default(),
+            rpc: RpcConfig::default(),
+            networks: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            gateways: GatewayConfig::default(),
+            cache: CacheConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            opensea: OpenSeaConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            admin: AdminConfig::default(),
+            popularity: PopularityConfig::default(),
+            warmer: WarmerConfig::default(),
+            follower: FollowerConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            websocket: WebSocketConfig::default(),
+            multicall: MulticallConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            healing: HealingConfig::default(),
+            safety: SafetyConfig::default(),
+        }
+    }
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            records: Records::default().records,
+            chains: Coins::default()
+                .coins
+                .into_iter()
+                .map(|coin| U256::from(coin).as_u64())
+                .collect(),
+            lenient_records: false,
+        }
+    }
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            universal_resolver: None,
+            resolution: Resolution::Universal,
+            registry: ENS_REGISTRY,
+            reverse_registrar: None,
+            lookup_timeout: None,
+            call_timeout: None,
+        }
+    }
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            ipfs: Url::parse("https://ipfs.io/ipfs/").expect("should be a valid url"),
+            arweave: Url::parse("https://arweave.net/").expect("should be a valid url"),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            redis_url: None,
+            profile_ttl: 600,
+            partial_ttl: 30,
+            compression: true,
+            memory_capacity: 10_000,
+            l1_ttl: 30,
+            disk_path: PathBuf::from("cache.redb"),
+            disk_max_entries: 100_000,
+            disk_compaction_interval: 300,
+            breaker_threshold: 5,
+            breaker_cooldown: 30,
+            reconnect_interval: 10,
+        }
+    }
+}
+
+impl Default for PopularityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            windows: BTreeMap::from([
+                ("hour".to_string(), 3600),
+                ("day".to_string(), 86400),
+                ("week".to_string(), 604_800),
+            ]),
+            max_entries: 10_000,
+        }
+    }
+}
+
+impl Default for WarmerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 60,
+            top_n: 100,
+            window: "day".to_string(),
+            names: Vec::new(),
+            names_file: None,
+            refresh_before: 90,
+            rpc_budget: 200,
+        }
+    }
+}
+
+impl Default for FollowerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            registry: None,
+            poll_interval: 12,
+            max_block_range: 500,
+            reorg_window: 12,
+            cursor_path: PathBuf::from("follower.cursor"),
+        }
+    }
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_attempts: 5,
+            backoff_base: 2,
+            backoff_max: 300,
+            timeout: 10,
+            max_subscriptions: 100,
+            refresh_interval: 30,
+            store: None,
+            store_path: PathBuf::from("webhooks.redb"),
+        }
+    }
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_subscriptions: 50,
+            refresh_interval: 60,
+        }
+    }
+}
+
+impl Default for MulticallConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "0xcA11bde05977b3631167028862bE2a173976CA11"
+                .parse()
+                .expect("multicall address should be valid"),
+            window: 10,
+            max_calls: 20,
+        }
+    }
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_in_flight: 64,
+            per_provider: 32,
+            max_queue: 256,
+            retry_after: 1,
+        }
+    }
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            top_n: 1000,
+            window: "week".to_string(),
+            refresh_interval: 300,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let routes = [
+            ("/n/:name", 160),
+            ("/a/:address", 160),
+            ("/bulk/a", 9),
+            ("/bulk/n", 9),
+        ]
+        .into_iter()
+        .map(|(path, requests)| {
+            (
+                path.to_string(),
+                RouteLimitConfig {
+                    requests,
+                    window: 60,
+                },
+            )
+        })
+        .collect();
+
+        Self {
+            enabled: false,
+            routes,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env::var("CONFIG_PATH") {
+            Ok(path) => Self::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(DEFAULT_CONFIG_PATH)?
+            }
+            Err(_) => Self::default(),
+        };
+
+        let mut errors = Vec::new();
+
+        config.apply_env(&mut errors);
+        config.validate(&mut errors);
+
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_string(),
+            source,
+        })?;
+
+        toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+            path: path.to_string(),
+            source,
+        })
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        env_parse("PORT", &mut self.port, errors);
+        env_parse("MAX_BULK_SIZE", &mut self.max_bulk_size, errors);
+
+        if let Ok(network) = env::var("NETWORK") {
+            self.network = network;
+        }
+
+        if let Ok(records) = env::var("PROFILE_RECORDS") {
+            self.profile.records = records.split(',').map(ToString::to_string).collect();
+        }
+
+        env_list("MULTICOIN_CHAINS", &mut self.profile.chains, errors);
+        env_parse("LENIENT_RECORDS", &mut self.profile.lenient_records, errors);
+        env_list("RPC_URL", &mut self.rpc.urls, errors);
+
+        env_parse("UNIVERSAL_RESOLVER", &mut self.rpc.universal_resolver, errors);
+        env_parse("RESOLUTION", &mut self.rpc.resolution, errors);
+        env_parse("REGISTRY", &mut self.rpc.registry, errors);
+        env_parse("REVERSE_REGISTRAR", &mut self.rpc.reverse_registrar, errors);
+        env_parse("LOOKUP_TIMEOUT", &mut self.rpc.lookup_timeout, errors);
+        env_parse("CALL_TIMEOUT", &mut self.rpc.call_timeout, errors);
+
+        env_parse("IPFS_GATEWAY", &mut self.gateways.ipfs, errors);
+        env_parse("AR_GATEWAY", &mut self.gateways.arweave, errors);
+
+        if let Ok(redis_url) = env::var("REDIS_URL") {
+            self.cache.redis_url = Some(redis_url);
+        }
+
+        env_parse("CACHE_BACKEND", &mut self.cache.backend, errors);
+        env_parse("PROFILE_CACHE_TTL", &mut self.cache.profile_ttl, errors);
+        env_parse("CACHE_COMPRESSION", &mut self.cache.compression, errors);
+
+        if let Ok(disk_path) = env::var("CACHE_DISK_PATH") {
+            self.cache.disk_path = PathBuf::from(disk_path);
+        }
+
+        env_parse("RATE_LIMIT_ENABLED", &mut self.rate_limit.enabled, errors);
+        env_parse("POPULARITY_ENABLED", &mut self.popularity.enabled, errors);
+        env_parse("WARMER_ENABLED", &mut self.warmer.enabled, errors);
+        env_parse("FOLLOWER_ENABLED", &mut self.follower.enabled, errors);
+        env_parse("WEBHOOKS_ENABLED", &mut self.webhooks.enabled, errors);
+        env_parse("WEBHOOKS_STORE", &mut self.webhooks.store, errors);
+        env_parse("MULTICALL_ENABLED", &mut self.multicall.enabled, errors);
+        env_parse("CONCURRENCY_ENABLED", &mut self.concurrency.enabled, errors);
+        env_parse("SAFETY_ENABLED", &mut self.safety.enabled, errors);
+
+        if let Ok(api_key) = env::var("OPENSEA_API_KEY") {
+            self.opensea.api_key = Some(api_key);
+        }
+
+        env_parse("MEILI_ENDPOINT", &mut self.discovery.meilisearch_url, errors);
+
+        if let Ok(meilisearch_key) = env::var("MEILI_KEY") {
+            self.discovery.meilisearch_key = Some(meilisearch_key);
+        }
+
+        if let Ok(token) = env::var("ADMIN_TOKEN") {
+            self.admin.token = Some(token);
+        }
+
+        if let Ok(rainbow_table) = env::var("RAINBOW_TABLE") {
+            self.healing.rainbow_table = Some(PathBuf::from(rainbow_table));
+        }
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.rpc.urls.is_empty() {
+            errors.push("rpc.urls (RPC_URL) should contain at least one url".to_string());
+        }
+
+        for url in &self.rpc.urls {
+            if !matches!(url.scheme(), "http" | "https") {
+                errors.push(format!("rpc.urls: {url} should be a http(s) url"));
+            }
+        }
+
+        match self.rpc.universal_resolver {
+            None if self.rpc.resolution == Resolution::Registry => {}
+            None => errors.push(
+                "rpc.universal_resolver (UNIVERSAL_RESOLVER) should've been set, or rpc.resolution (RESOLUTION) to registry".to_string(),
+            ),
+            Some(address) if address.is_zero() => errors.push(
+                "rpc.universal_resolver (UNIVERSAL_RESOLVER) should not be the zero address"
+                    .to_string(),
+            ),
+            Some(_) => {}
+        }
+
+        if self.rpc.lookup_timeout == Some(0) || self.rpc.call_timeout == Some(0) {
+            errors.push(
+                "rpc.lookup_timeout and rpc.call_timeout should be greater than 0".to_string(),
+            );
+        }
+
+        if self.rpc.registry.is_zero() {
+            errors.push("rpc.registry (REGISTRY) should not be the zero address".to_string());
+        }
+
+        if self
+            .rpc
+            .reverse_registrar
+            .is_some_and(|address| address.is_zero())
+        {
+            errors.push(
+                "rpc.reverse_registrar (REVERSE_REGISTRAR) should not be the zero address"
+                    .to_string(),
+            );
+        }
+
+        for name in std::iter::once(&self.network).chain(self.networks.keys()) {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                errors.push(format!(
+                    "network {name:?} should only contain lowercase letters, digits and hyphens"
+                ));
+            }
+
+            if RESERVED_NETWORK_NAMES.contains(&name.as_str()) {
+                errors.push(format!(
+                    "network {name:?} is reserved, it's the prefix of a route"
+                ));
+            }
+        }
+
+        if self.networks.contains_key(&self.network) {
+            errors.push(format!(
+                "networks.{}: already the default network (NETWORK), configured by rpc",
+                self.network
+            ));
+        }
+
+        for (name, rpc) in &self.networks {
+            if rpc.urls.is_empty() {
+                errors.push(format!(
+                    "networks.{name}.urls should contain at least one url"
+                ));
+            }
+
+            for url in &rpc.urls {
+                if !matches!(url.scheme(), "http" | "https") {
+                    errors.push(format!(
+                        "networks.{name}.urls: {url} should be a http(s) url"
+                    ));
+                }
+            }
+
+            let universal_resolver = match rpc.universal_resolver {
+                None => rpc.resolution == Resolution::Registry,
+                Some(address) => !address.is_zero(),
+            };
+
+            if !universal_resolver {
+                errors.push(format!(
+                    "networks.{name}.universal_resolver should be set to a non-zero address, or networks.{name}.resolution to registry"
+                ));
+            }
+
+            if rpc.registry.is_zero() {
+                errors.push(format!(
+                    "networks.{name}.registry should not be the zero address"
+                ));
+            }
+
+            if rpc
+                .reverse_registrar
+                .is_some_and(|address| address.is_zero())
+            {
+                errors.push(format!(
+                    "networks.{name}.reverse_registrar should not be the zero address"
+                ));
+            }
+
+            if rpc.lookup_timeout == Some(0) || rpc.call_timeout == Some(0) {
+                errors.push(format!(
+                    "networks.{name}.lookup_timeout and networks.{name}.call_timeout should be greater than 0"
+                ));
+            }
+        }
+
+        for (suffix, network) in &self.routes {
+            if suffix.is_empty()
+                || suffix.split('.').any(str::is_empty)
+                || suffix.chars().any(char::is_uppercase)
+            {
+                errors.push(format!(
+                    "routes.{suffix:?} should be a lowercase name without leading or trailing dots"
+                ));
+            }
+
+            if !self.networks.contains_key(network) {
+                errors.push(format!(
+                    "routes.{suffix:?}: {network:?} should be one of networks"
+                ));
+            }
+        }
+
+        if self.max_bulk_size == 0 {
+            errors.push("max_bulk_size (MAX_BULK_SIZE) should be greater than 0".to_string());
+        }
+
+        if self.cache.profile_ttl == 0 {
+            errors.push(
+                "cache.profile_ttl (PROFILE_CACHE_TTL) should be greater than 0".to_string(),
+            );
+        }
+
+        if self.cache.memory_capacity == 0 {
+            errors.push("cache.memory_capacity should be greater than 0".to_string());
+        }
+
+        if self.cache.disk_max_entries == 0 || self.cache.disk_compaction_interval == 0 {
+            errors.push(
+                "cache.disk_max_entries and cache.disk_compaction_interval should be greater than 0"
+                    .to_string(),
+            );
+        }
+
+        if self.cache.breaker_threshold == 0 || self.cache.reconnect_interval == 0 {
+            errors.push(
+                "cache.breaker_threshold and cache.reconnect_interval should be greater than 0"
+                    .to_string(),
+            );
+        }
+
+        match self.cache.backend {
+            Some(CacheBackend::Redis | CacheBackend::Tiered) if self.cache.redis_url.is_none() => {
+                errors.push(
+                    "cache.redis_url (REDIS_URL) should've been set for the redis and tiered backends"
+                        .to_string(),
+                );
+            }
+            _ => {}
+        }
+
+        for (path, limit) in &self.rate_limit.routes {
+            if limit.requests == 0 || limit.window == 0 {
+                errors.push(format!(
+                    "rate_limit.routes.\"{path}\": requests and window should be greater than 0"
+                ));
+            }
+        }
+
+        if self.popularity.enabled {
+            if self.popularity.windows.is_empty() || self.popularity.max_entries == 0 {
+                errors.push(
+                    "popularity.windows should not be empty and popularity.max_entries should be greater than 0"
+                        .to_string(),
+                );
+            }
+
+            for (name, half_life) in &self.popularity.windows {
+                if *half_life == 0 {
+                    errors.push(format!(
+                        "popularity.windows.{name}: half life should be greater than 0"
+                    ));
+                }
+            }
+        }
+
+        if self.warmer.enabled {
+            if self.warmer.interval == 0 || self.warmer.rpc_budget == 0 {
+                errors.push(
+                    "warmer.interval and warmer.rpc_budget should be greater than 0".to_string(),
+                );
+            }
+
+            if self.warmer.refresh_before >= self.cache.profile_ttl {
+                errors.push(
+                    "warmer.refresh_before should be less than cache.profile_ttl".to_string(),
+                );
+            }
+
+            if self.warmer.top_n > 0
+                && self.popularity.enabled
+                && !self.popularity.windows.contains_key(&self.warmer.window)
+            {
+                errors.push(format!(
+                    "warmer.window: {} is not one of the popularity.windows",
+                    self.warmer.window
+                ));
+            }
+        }
+
+        if self.follower.enabled
+            && (self.follower.poll_interval == 0 || self.follower.max_block_range == 0)
+        {
+            errors.push(
+                "follower.poll_interval and follower.max_block_range should be greater than 0"
+                    .to_string(),
+            );
+        }
+
+        if self.webhooks.enabled {
+            if self.webhooks.max_attempts == 0
+                || self.webhooks.timeout == 0
+                || self.webhooks.refresh_interval == 0
+            {
+                errors.push(
+                    "webhooks.max_attempts, webhooks.timeout and webhooks.refresh_interval should be greater than 0"
+                        .to_string(),
+                );
+            }
+
+            if matches!(self.cache.backend, Some(CacheBackend::None)) {
+                errors.push(
+                    "webhooks compare profiles against snapshots kept in the cache, cache.backend shouldn't be none"
+                        .to_string(),
+                );
+            }
+
+            if self.webhooks.store == Some(WebhookStore::Redis) && self.cache.redis_url.is_none() {
+                errors.push(
+                    "webhooks.store redis keeps subscriptions in cache.redis_url (REDIS_URL), which should've been set"
+                        .to_string(),
+                );
+            }
+
+            if self.admin.token.is_none() {
+                errors.push(
+                    "webhooks are managed through the admin API, admin.token (ADMIN_TOKEN) should've been set"
+                        .to_string(),
+                );
+            }
+        }
+
+        if self.multicall.enabled && self.multicall.max_calls == 0 {
+            errors.push("multicall.max_calls should be greater than 0".to_string());
+        }
+
+        if self.concurrency.enabled
+            && (self.concurrency.max_in_flight == 0 || self.concurrency.per_provider == 0)
+        {
+            errors.push(
+                "concurrency.max_in_flight and concurrency.per_provider should be greater than 0"
+                    .to_string(),
+            );
+        }
+
+        if self.safety.enabled && self.safety.top_n > 0 && self.popularity.enabled {
+            if self.safety.refresh_interval == 0 {
+                errors.push("safety.refresh_interval should be greater than 0".to_string());
+            }
+
+            if !self.popularity.windows.contains_key(&self.safety.window) {
+                errors.push(format!(
+                    "safety.window: {} is not one of the popularity.windows",
+                    self.safety.window
+                ));
+            }
+        }
+
+        if self.websocket.max_subscriptions == 0 {
+            errors.push("websocket.max_subscriptions should be greater than 0".to_string());
+        }
+
+        if matches!(&self.admin.token, Some(token) if token.len() < 16) {
+            errors.push(
+                "admin.token (ADMIN_TOKEN) should be at least 16 characters long".to_string(),
+            );
+        }
+    }
+}
+
+fn env_parse<T>(key: &str, target: &mut T, errors: &mut Vec<String>)
+where
+    T: EnvValue,
+{
+    let Ok(value) = env::var(key) else {
+        return;
+    };
+
+    match T::parse_env(&value) {
+        Ok(value) => *target = value,
+        Err(err) => errors.push(format!("{key}: {err}")),
+    }
+}
+
+fn env_list<T>(key: &str, target: &mut Vec<T>, errors: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Ok(value) = env::var(key) else {
+        return;
+    };
+
+    let parsed = value
+        .split(',')
+        .map(str::trim)
+        .filter(|it| !it.is_empty())
+        .map(|it| it.parse::<T>().map_err(|err| format!("{key}: {it}: {err}")))
+        .collect::<Result<Vec<_>, _>>();
+
+    match parsed {
+        Ok(parsed) => *target = parsed,
+        Err(err) => errors.push(err),
+    }
+}
+
+trait EnvValue: Sized {
+    fn parse_env(value: &str) -> Result<Self, String>;
+}
+
+macro_rules! env_value_from_str {
+    ($($ty:ty),*) => {
+        $(impl EnvValue for $ty {
+            fn parse_env(value: &str) -> Result<Self, String> {
+                value.trim().parse().map_err(|err| format!("{value}: {err}"))
+            }
+        })*
+    };
+}
+
+env_value_from_str!(u16, u32, u64, usize, bool, Url);
+env_value_from_str!(CacheBackend, Resolution, WebhookStore);
+
+impl<T: EnvValue> EnvValue for Option<T> {
+    fn parse_env(value: &str) -> Result<Self, String> {
+        T::parse_env(value).map(Some)
+    }
+}
+
+impl EnvValue for H160 {
+    fn parse_env(value: &str) -> Result<Self, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|err| format!("{value} should be a valid address: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::sync::Mutex;
+
+    use super::{CacheBackend, Config, Resolution};
+
+    // the environment is shared by every test thread
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const VALID: &str = r#"
+        [rpc]
+        urls = ["https://rpc.example.com"]
+        universal_resolver = "0xce01f8eee7E479C928F8919abD53E553a36CeF67"
+    "#;
+
+    fn parse(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn errors(config: &Config) -> Vec<String> {
+        let mut errors = Vec::new();
+        config.validate(&mut errors);
+
+        errors
+    }
+
+    fn with_env(vars: &[(&str, &str)], f: impl FnOnce()) {
+        let _guard = ENV
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        for (key, value) in vars {
+            env::set_var(key, value);
+        }
+
+        f();
+
+        for (key, _) in vars {
+            env::remove_var(key);
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = parse("");
+
+        assert_eq!(config.port, 3000);
+        assert_eq!(config.max_bulk_size, 10);
+        assert_eq!(config.network, "mainnet");
+        assert_eq!(config.rpc.resolution, Resolution::Universal);
+        assert_eq!(config.cache.backend, None);
+        assert_eq!(config.cache.profile_ttl, 600);
+        assert!(!config.rate_limit.enabled);
+        assert_eq!(config.rate_limit.routes["/n/:name"].requests, 160);
+        assert!(config.popularity.enabled);
+        assert!(!config.concurrency.enabled);
+
+        // only the rpc has no usable default
+        assert_eq!(errors(&config).len(), 2);
+        assert!(errors(&parse(VALID)).is_empty());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = parse(&format!("port = 4000\nmax_bulk_size = 20\n{VALID}"));
+
+        with_env(
+            &[
+                ("PORT", "5000"),
+                ("RPC_URL", "https://a.example.com, https://b.example.com"),
+                ("CACHE_BACKEND", "disk"),
+                ("RESOLUTION", "registry"),
+            ],
+            || {
+                let mut errors = Vec::new();
+                config.apply_env(&mut errors);
+
+                assert!(errors.is_empty(), "{errors:?}");
+            },
+        );
+
+        assert_eq!(config.port, 5000);
+        assert_eq!(config.max_bulk_size, 20);
+        assert_eq!(config.rpc.urls.len(), 2);
+        assert_eq!(config.rpc.urls[1].as_str(), "https://b.example.com/");
+        assert_eq!(config.cache.backend, Some(CacheBackend::Disk));
+        assert_eq!(config.rpc.resolution, Resolution::Registry);
+        assert!(config.rpc.universal_resolver.is_some());
+    }
+
+    #[test]
+    fn test_invalid_env_values() {
+        let mut config = parse(VALID);
+        let mut errors = Vec::new();
+
+        with_env(&[("PORT", "abc"), ("CACHE_BACKEND", "sqlite")], || {
+            config.apply_env(&mut errors);
+        });
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("PORT: abc"));
+        assert!(errors[1].starts_with("CACHE_BACKEND: sqlite"));
+        assert_eq!(config.port, 3000);
+    }
+
+    #[test]
+    fn test_invalid_urls() {
+        let config = parse(
+            r#"
+            [rpc]
+            urls = ["wss://rpc.example.com"]
+            universal_resolver = "0xce01f8eee7E479C928F8919abD53E553a36CeF67"
+
+            [networks.gnosis]
+            urls = ["ftp://rpc.example.com"]
+            resolution = "registry"
+            "#,
+        );
+
+        assert_eq!(
+            errors(&config),
+            [
+                "rpc.urls: wss://rpc.example.com/ should be a http(s) url",
+                "networks.gnosis.urls: ftp://rpc.example.com/ should be a http(s) url",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zero_limits() {
+        let config = parse(&format!(
+            r#"
+            max_bulk_size = 0
+            {VALID}
+            [cache]
+            profile_ttl = 0
+
+            [rate_limit.routes."/n/:name"]
+            requests = 0
+            window = 60
+
+            [websocket]
+            max_subscriptions = 0
+            "#
+        ));
+
+        assert_eq!(
+            errors(&config),
+            [
+                "max_bulk_size (MAX_BULK_SIZE) should be greater than 0",
+                "cache.profile_ttl (PROFILE_CACHE_TTL) should be greater than 0",
+                "rate_limit.routes.\"/n/:name\": requests and window should be greater than 0",
+                "websocket.max_subscriptions should be greater than 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_route_network() {
+        let config = parse(&format!(
+            r#"
+            {VALID}
+            [routes]
+            gno = "gnosis"
+            "Base.eth" = "mainnet"
+            "#
+        ));
+
+        assert_eq!(
+            errors(&config),
+            [
+                "routes.\"Base.eth\" should be a lowercase name without leading or trailing dots",
+                "routes.\"Base.eth\": \"mainnet\" should be one of networks",
+                "routes.\"gno\": \"gnosis\" should be one of networks",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reserved_network_names() {
+        let config = parse(&format!(
+            r#"
+            network = "bulk"
+            {VALID}
+            [networks.sse]
+            urls = ["https://rpc.example.com"]
+            resolution = "registry"
+            "#
+        ));
+
+        assert_eq!(
+            errors(&config),
+            [
+                "network \"bulk\" is reserved, it's the prefix of a route",
+                "network \"sse\" is reserved, it's the prefix of a route",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_webhooks_store() {
+        let config = parse(&format!(
+            r#"
+            [admin]
+            token = "0123456789abcdef"
+
+            [webhooks]
+            enabled = true
+            store = "redis"
+            {VALID}
+            "#
+        ));
+
+        assert_eq!(
+            errors(&config),
+            ["webhooks.store redis keeps subscriptions in cache.redis_url (REDIS_URL), which should've been set"]
+        );
+
+        // subscriptions don't live in the cache, so the memory backend is fine
+        let config = parse(&format!(
+            r#"
+            [admin]
+            token = "0123456789abcdef"
+
+            [webhooks]
+            enabled = true
+
+            [cache]
+            backend = "memory"
+            {VALID}
+            "#
+        ));
+
+        assert!(errors(&config).is_empty());
+    }
+}
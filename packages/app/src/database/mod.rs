@@ -1,10 +1,8 @@
-use std::env;
-
 use anyhow::Result;
 use redis::aio::ConnectionManager;
 
-pub async fn setup() -> Result<ConnectionManager> {
-    let redis = redis::Client::open(env::var("REDIS_URL")?)?;
+pub async fn setup(redis_url: &str) -> Result<ConnectionManager> {
+    let redis = redis::Client::open(redis_url)?;
 
     Ok(ConnectionManager::new(redis).await?)
 }
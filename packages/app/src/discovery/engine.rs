@@ -4,7 +4,7 @@ use axum::async_trait;
 use enstate_shared::core::lookup_data::LookupInfo;
 use enstate_shared::core::{ENSService, Profile};
 use enstate_shared::discovery::{Discovery, SearchResult};
-use ethers::providers::namehash;
+use enstate_shared::utils::name::namehash;
 use futures::future::join_all;
 use serde::{Deserialize, Serialize};
 
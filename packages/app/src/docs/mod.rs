@@ -1,12 +1,21 @@
 use crate::models::bulk::{BulkResponse, ListResponse};
-use crate::models::error::ErrorResponse;
+use crate::models::error::{ErrorCode, ErrorResponse};
 use crate::models::profile::ENSProfile;
 use crate::routes::address::AddressGetBulkQuery;
 use crate::routes::name::NameGetBulkQuery;
 use crate::routes::universal::UniversalGetBulkQuery;
+use crate::routes::v2::heal::HealResponse;
+use crate::routes::v2::name::NameInfoResponse;
+use crate::routes::v2::popular::{PopularName, PopularResponse};
+use enstate_shared::core::error::{RecordError, RecordErrorCode};
+use enstate_shared::core::resolver::{ResolverInfo, ResolverKind};
+use enstate_shared::core::resolvers::interfaces::ResolverInterfaces;
 use enstate_shared::meta::AppMeta;
+use enstate_shared::safety::{SafetyIssue, SafetyIssueKind, SafetyReport};
+use enstate_shared::utils::name::{LabelInfo, NameError, NameErrorCode, NameInfo};
 use enstate_shared::utils::vec;
-use utoipa::openapi::{ExternalDocs, License, Tag};
+use utoipa::openapi::path::{ParameterBuilder, ParameterIn};
+use utoipa::openapi::{ExternalDocs, License, ObjectBuilder, Required, SchemaType, Tag};
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
@@ -23,8 +32,12 @@ use utoipa::OpenApi;
         crate::routes::header::get,
         crate::routes::image::get,
         crate::routes::root::get,
+        crate::routes::v2::popular::get,
+        crate::routes::v2::resolver::get,
+        crate::routes::v2::name::get_info,
+        crate::routes::v2::heal::get,
     ),
-    components(schemas(ENSProfile, ListResponse<BulkResponse<ENSProfile>>, ErrorResponse, AppMeta, UniversalGetBulkQuery)),
+    components(schemas(ENSProfile, RecordError, RecordErrorCode, ListResponse<BulkResponse<ENSProfile>>, ErrorResponse, ErrorCode, AppMeta, UniversalGetBulkQuery, PopularResponse, PopularName, ResolverInfo, ResolverKind, ResolverInterfaces, NameInfoResponse, NameInfo, LabelInfo, NameError, NameErrorCode, HealResponse, SafetyReport, SafetyIssue, SafetyIssueKind)),
 )]
 pub struct ApiDoc;
 
@@ -50,8 +63,37 @@ pub async fn openapi() -> String {
     tag4.description = Some("To save you the hassle of loading profiles, and extracting json fields, we have made a few endpoints that will make it easy for you to directly use image urls in your app.".to_string());
     let mut tag5 = Tag::default();
     tag5.name = "Deployment Information".to_string();
+    let mut tag6 = Tag::default();
+    tag6.name = "Popularity".to_string();
+    tag6.description = Some("Names ranked by how often they were looked up, with older lookups counting less.".to_string());
 
-    doc.tags = Some(vec![tag1, tag2, tag3, tag4, tag5]);
+    let mut tag7 = Tag::default();
+    tag7.name = "Resolver".to_string();
+    tag7.description = Some("Which resolver answers for a name, how it was found and what it supports, to find out why a record doesn't show up.".to_string());
+
+    let mut tag8 = Tag::default();
+    tag8.name = "Names".to_string();
+    tag8.description = Some("Normalization, hashes and encodings of a name, computed without touching the chain, and healing of unknown labels.".to_string());
+
+    doc.tags = Some(vec![tag1, tag2, tag3, tag4, tag5, tag6, tag7, tag8]);
+
+    // every route can be asked about another configured network
+    let network = ParameterBuilder::new()
+        .name("network")
+        .parameter_in(ParameterIn::Query)
+        .required(Required::False)
+        .description(Some("Network to look up on, e.g. `sepolia`, defaults to the deployment's default network. Alternatively prefix the path with the network, as in `/sepolia/n/luc.eth`."))
+        .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String).build()))
+        .build();
+
+    for path in doc.paths.paths.values_mut() {
+        for operation in path.operations.values_mut() {
+            operation
+                .parameters
+                .get_or_insert_with(Vec::new)
+                .push(network.clone());
+        }
+    }
 
     doc.to_json().unwrap()
 }
@@ -0,0 +1,384 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use enstate_shared::cache::envelope;
+use enstate_shared::cache::node_key;
+use enstate_shared::core::profile::PROFILE_SCHEMA_VERSION;
+use enstate_shared::core::Profile;
+use ethers::providers::{Middleware, ProviderError};
+use ethers::types::{Address, Filter, Log, H256};
+use ethers::utils::keccak256;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::FollowerConfig;
+use crate::state::AppState;
+
+// only trusted when emitted by the configured registry
+const REGISTRY_EVENTS: [&str; 2] = ["NewResolver(bytes32,address)", "Transfer(bytes32,address)"];
+
+const RESOLVER_EVENTS: [&str; 6] = [
+    "AddrChanged(bytes32,address)",
+    "AddressChanged(bytes32,uint256,bytes)",
+    "ContenthashChanged(bytes32,bytes)",
+    "NameChanged(bytes32,string)",
+    // legacy and current PublicResolver signatures
+    "TextChanged(bytes32,string,string)",
+    "TextChanged(bytes32,string,string,string)",
+];
+
+/// Last processed block, its hash is compared on the next poll to detect reorgs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    block: u64,
+    hash: H256,
+}
+
+impl Cursor {
+    async fn load(path: &Path) -> Option<Self> {
+        let contents = tokio::fs::read_to_string(path).await.ok()?;
+        let (block, hash) = contents.trim().split_once(' ')?;
+
+        Some(Self {
+            block: block.parse().ok()?,
+            hash: hash.parse().ok()?,
+        })
+    }
+
+    async fn save(&self, path: &Path) -> std::io::Result<()> {
+        // write and rename, so a crash never leaves a truncated cursor behind
+        let tmp = path.with_extension("tmp");
+
+        tokio::fs::write(&tmp, format!("{} {:?}", self.block, self.hash)).await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+}
+
+/// Topics of the followed events, and the registry the registry events are trusted from.
+struct Events {
+    registry: Address,
+    registry_events: HashSet<H256>,
+    addr_changed: H256,
+}
+
+impl Events {
+    fn new(registry: Address) -> Self {
+        Self {
+            registry,
+            registry_events: REGISTRY_EVENTS.iter().map(|it| topic(it)).collect(),
+            addr_changed: topic(RESOLVER_EVENTS[0]),
+        }
+    }
+
+    /// Node a log touches, with the address it was set to for `AddrChanged`
+    fn affected_node(&self, log: &Log) -> Option<(H256, Option<Address>)> {
+        let (event, node) = (log.topics.first()?, log.topics.get(1)?);
+
+        if self.registry_events.contains(event) && log.address != self.registry {
+            return None;
+        }
+
+        let address = (*event == self.addr_changed && log.data.len() >= 32)
+            .then(|| Address::from_slice(&log.data[12..32]));
+
+        Some((*node, address))
+    }
+}
+
+/// Follows registry and resolver events with `eth_getLogs` and deletes the
+/// cached entries of every node they touch, instead of waiting for the TTL.
+pub struct Follower {
+    state: Arc<AppState>,
+    config: FollowerConfig,
+    events: Events,
+}
+
+impl Follower {
+    pub fn spawn(
+        state: Arc<AppState>,
+        config: FollowerConfig,
+        shutdown: CancellationToken,
+    ) -> Option<JoinHandle<()>> {
+        if !config.enabled {
+            return None;
+        }
+
+        let follower = Self {
+            events: Events::new(config.registry.unwrap_or(state.service.registry)),
+            state,
+            config,
+        };
+
+        Some(tokio::spawn(async move {
+            let mut cursor = Cursor::load(&follower.config.cursor_path).await;
+
+            if let Some(cursor) = cursor {
+                info!("Following logs from block {}", cursor.block);
+            }
+
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(follower.config.poll_interval));
+
+            'poll: loop {
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+
+                // keep going without waiting while catching up on a backlog
+                loop {
+                    if shutdown.is_cancelled() {
+                        break 'poll;
+                    }
+
+                    match follower.poll(cursor).await {
+                        Ok((next, caught_up)) => {
+                            if let Err(err) = next.save(&follower.config.cursor_path).await {
+                                warn!("failed to persist log follower cursor: {err}");
+                            }
+
+                            follower
+                                .state
+                                .metrics
+                                .follower_block
+                                .set(next.block.try_into().unwrap_or(i64::MAX));
+
+                            cursor = Some(next);
+
+                            if caught_up {
+                                break;
+                            }
+                        }
+                        Err(err) => {
+                            warn!("log follower poll failed: {err}");
+
+                            break;
+                        }
+                    }
+                }
+            }
+
+            info!("Log follower shutdown");
+        }))
+    }
+
+    async fn poll(&self, cursor: Option<Cursor>) -> Result<(Cursor, bool), ProviderError> {
+        let rpc = &self.state.service.rpc;
+
+        // dropped before invalidating, which may re-resolve names on the same providers
+        let (provider, permit) = rpc.lease().await;
+
+        let latest = provider.get_block_number().await?.as_u64();
+
+        let from = match cursor {
+            // no cursor yet, start following from the head
+            None => latest,
+            Some(cursor) => {
+                let canonical = provider
+                    .get_block(cursor.block)
+                    .await?
+                    .and_then(|block| block.hash);
+
+                if canonical == Some(cursor.hash) {
+                    cursor.block + 1
+                } else {
+                    warn!(
+                        "reorg detected at block {}, re-scanning the last {} blocks",
+                        cursor.block, self.config.reorg_window
+                    );
+
+                    cursor.block.saturating_sub(self.config.reorg_window)
+                }
+            }
+        };
+
+        if let Some(cursor) = cursor.filter(|_| from > latest) {
+            return Ok((cursor, true));
+        }
+
+        let to = latest.min(from + self.config.max_block_range - 1);
+
+        let filter = Filter::new()
+            .from_block(from)
+            .to_block(to)
+            .events(REGISTRY_EVENTS.iter().chain(RESOLVER_EVENTS.iter()));
+
+        let logs = provider.get_logs(&filter).await?;
+
+        drop(permit);
+
+        let mut nodes = HashSet::new();
+        for log in &logs {
+            let Some((node, address)) = self.events.affected_node(log) else {
+                continue;
+            };
+
+            // the new address might now have a valid primary name
+            if let Some(address) = address {
+                let _ = self.delete(&format!("a:{address:?}")).await;
+            }
+
+            nodes.insert(node);
+        }
+
+        for node in nodes {
+            self.invalidate_node(node).await;
+        }
+
+        let (provider, _permit) = rpc.lease().await;
+
+        let hash = provider
+            .get_block(to)
+            .await?
+            .and_then(|block| block.hash)
+            .unwrap_or_default();
+
+        Ok((Cursor { block: to, hash }, to == latest))
+    }
+
+    async fn invalidate_node(&self, node: H256) {
+        let cache = &self.state.service.cache;
+
+        let index_key = node_key(&node);
+
+        // nodes that were never cached have no index entry
+        let Some(key) = cache
+            .get(&index_key)
+            .await
+            .ok()
+            .and_then(|key| String::from_utf8(key).ok())
+        else {
+            return;
+        };
+
+        let profile = if key.starts_with("n:") {
+            cache.get(&key).await.ok().and_then(|value| {
+                envelope::decode::<Profile>(&value, PROFILE_SCHEMA_VERSION)
+                    .ok()
+                    .map(|(_, profile)| profile)
+            })
+        } else {
+            None
+        };
+
+        // primary names are checked against the name's address, so that entry is stale too
+        if let Some(address) = profile.as_ref().and_then(|profile| profile.address.as_ref()) {
+            let _ = self.delete(&format!("a:{:?}", address.0)).await;
+        }
+
+        if self.delete(&key).await {
+            self.state.metrics.follower_invalidations.inc();
+        }
+
+        let _ = self.delete(&index_key).await;
+
+        // someone is waiting for updates to this name, resolve it right away
+        let watched = profile.as_ref().map_or_else(
+            || self.state.watchlist.contains(&key),
+            |profile| self.state.watchlist.is_watched(profile),
+        );
+
+        if let Some(name) = key.strip_prefix("n:").filter(|_| watched) {
+            if let Err(err) = self
+                .state
+                .service
+                .resolve_name_profile(name.to_string(), true)
+                .await
+            {
+                warn!(name, "failed to re-resolve watched profile: {err}");
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> bool {
+        match self.state.service.cache.delete(key).await {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(key, "failed to invalidate cache entry: {err}");
+
+                false
+            }
+        }
+    }
+}
+
+fn topic(signature: &str) -> H256 {
+    H256::from(keccak256(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, Bytes, Log, H256};
+
+    use super::{topic, Cursor, Events, REGISTRY_EVENTS, RESOLVER_EVENTS};
+
+    fn log(address: Address, event: &str, node: H256, data: Vec<u8>) -> Log {
+        Log {
+            address,
+            topics: vec![topic(event), node],
+            data: Bytes::from(data),
+            ..Log::default()
+        }
+    }
+
+    #[test]
+    fn test_affected_node() {
+        let registry = Address::repeat_byte(1);
+        let resolver = Address::repeat_byte(2);
+        let node = H256::repeat_byte(3);
+        let events = Events::new(registry);
+
+        // registry events are only trusted from the registry
+        let new_resolver = log(registry, REGISTRY_EVENTS[0], node, Vec::new());
+        assert_eq!(events.affected_node(&new_resolver), Some((node, None)));
+
+        let spoofed = log(resolver, REGISTRY_EVENTS[0], node, Vec::new());
+        assert_eq!(events.affected_node(&spoofed), None);
+
+        // resolver events come from any resolver, the node is their first indexed topic
+        let text_changed = log(resolver, RESOLVER_EVENTS[4], node, vec![0; 96]);
+        assert_eq!(events.affected_node(&text_changed), Some((node, None)));
+
+        // the address is the abi encoded word of the data
+        let address = Address::repeat_byte(4);
+        let mut data = vec![0; 12];
+        data.extend_from_slice(address.as_bytes());
+
+        let addr_changed = log(resolver, RESOLVER_EVENTS[0], node, data);
+        assert_eq!(
+            events.affected_node(&addr_changed),
+            Some((node, Some(address)))
+        );
+
+        let unindexed = Log {
+            topics: vec![topic(RESOLVER_EVENTS[0])],
+            ..addr_changed
+        };
+        assert_eq!(events.affected_node(&unindexed), None);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("follower.cursor");
+
+        assert_eq!(Cursor::load(&path).await, None);
+
+        let cursor = Cursor {
+            block: 19_000_000,
+            hash: H256::repeat_byte(5),
+        };
+
+        cursor.save(&path).await.unwrap();
+
+        assert_eq!(Cursor::load(&path).await, Some(cursor));
+        assert!(!path.with_extension("tmp").exists());
+
+        tokio::fs::write(&path, "19000000").await.unwrap();
+
+        assert_eq!(Cursor::load(&path).await, None);
+    }
+}
@@ -1,12 +1,15 @@
 use axum::extract::{MatchedPath, State};
-use axum::http::{Request, StatusCode};
+use axum::http::uri::PathAndQuery;
+use axum::http::{header, HeaderValue, Request, StatusCode, Uri};
 use axum::middleware::{self, Next};
 use axum::response::{Html, Redirect, Response};
-use std::env;
+use std::collections::HashMap;
+use std::sync::RwLock;
 use std::{net::SocketAddr, sync::Arc};
 
 use axum::{routing::get, Router};
 use dashmap::DashMap;
+use serde_json::json;
 use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
 use tokio_util::sync::CancellationToken;
@@ -14,8 +17,12 @@ use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing::{info, info_span};
 
+use crate::config::RateLimitConfig;
+use crate::http_cache;
+use crate::models::error::{ErrorCode, ErrorResponse};
 use crate::routes;
 use crate::routes::v2::setup_v2_router;
+use crate::routes::{RouteError, SelectedNetwork};
 use crate::state::AppState;
 use crate::telemetry::metrics::{self};
 
@@ -34,49 +41,54 @@ struct RateLimitState {
 
 // Add this to your AppState
 pub struct RateLimiter {
-    limits: DashMap<String, RateLimit>,
+    // swapped as a whole on reload, so requests never see a partially applied config
+    limits: RwLock<HashMap<String, RateLimit>>,
     states: DashMap<(String, String), RateLimitState>, // (path, ip) -> state
 }
 
 impl RateLimiter {
-    pub fn new() -> Self {
-        let limits = DashMap::new();
-
-        if env::var("RATE_LIMIT_ENABLED").unwrap_or_else(|_| "false".to_owned()) == "true" {
-            limits.insert(
-                "/n/:name".to_string(),
-                RateLimit {
-                    requests: 160,
-                    window: Duration::from_secs(60),
-                },
-            );
-            limits.insert(
-                "/a/:address".to_string(),
-                RateLimit {
-                    requests: 160,
-                    window: Duration::from_secs(60),
-                },
-            );
-            limits.insert(
-                "/bulk/a".to_string(),
-                RateLimit {
-                    requests: 9,
-                    window: Duration::from_secs(60),
-                },
-            );
-            limits.insert(
-                "/bulk/n".to_string(),
-                RateLimit {
-                    requests: 9,
-                    window: Duration::from_secs(60),
-                },
-            );
-        }
-
-        Self {
-            limits,
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let limiter = Self {
+            limits: RwLock::default(),
             states: DashMap::new(),
-        }
+        };
+
+        limiter.reload(config);
+
+        limiter
+    }
+
+    pub fn reload(&self, config: &RateLimitConfig) {
+        let limits = if config.enabled {
+            config
+                .routes
+                .iter()
+                .map(|(path, limit)| {
+                    (
+                        path.clone(),
+                        RateLimit {
+                            requests: limit.requests,
+                            window: Duration::from_secs(limit.window),
+                        },
+                    )
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        *self
+            .limits
+            .write()
+            .expect("rate limits lock should not be poisoned") = limits;
+    }
+
+    fn limit(&self, path: &str) -> Option<RateLimit> {
+        self.limits
+            .read()
+            .expect("rate limits lock should not be poisoned")
+            .get(path)
+            .cloned()
     }
 }
 
@@ -85,7 +97,7 @@ async fn rate_limit_middleware(
     State(state): State<Arc<AppState>>,
     req: Request<axum::body::Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, RouteError> {
     let ip = req
         .headers()
         .get("CF-Connecting-IP")
@@ -111,7 +123,7 @@ async fn rate_limit_middleware(
 
     let rate_limiter = &state.rate_limiter;
 
-    if let Some(limit) = rate_limiter.limits.get(&path) {
+    if let Some(limit) = rate_limiter.limit(&path) {
         let key = (path.clone(), ip.clone());
         let now = Instant::now();
 
@@ -144,13 +156,77 @@ async fn rate_limit_middleware(
                 .with_label_values(&[&ip])
                 .inc();
 
-            return Err(StatusCode::TOO_MANY_REQUESTS);
+            return Err(ErrorResponse::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorCode::RateLimited,
+                "Too many requests",
+            )
+            .with_details(json!({
+                "requests": limit.requests,
+                "window": limit.window.as_secs(),
+            }))
+            .into());
         }
     }
 
     Ok(next.run(req).await)
 }
 
+/// Tells clients shed by the concurrency limiter when to come back, after `retry_after` seconds.
+async fn retry_after_middleware(
+    State(retry_after): State<u64>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+
+    if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+        response
+            .headers_mut()
+            .entry(header::RETRY_AFTER)
+            .or_insert(HeaderValue::from(retry_after));
+    }
+
+    response
+}
+
+/// Selects the network of a request from a `/{network}` path prefix, which is stripped
+/// before routing, or the `network` query parameter.
+async fn network_middleware(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    let (prefix, rest) = path[1..].split_once('/').unwrap_or((&path[1..], ""));
+
+    if state.networks.contains_key(prefix) {
+        let network = prefix.to_string();
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("/{rest}?{query}"),
+            None => format!("/{rest}"),
+        };
+
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = uri;
+            req.extensions_mut().insert(SelectedNetwork(network));
+        }
+    } else if let Some(network) = req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("network="))
+    }) {
+        let network = network.to_string();
+
+        req.extensions_mut().insert(SelectedNetwork(network));
+    }
+
+    next.run(req).await
+}
+
 pub struct App {
     router: Router,
 }
@@ -182,18 +258,16 @@ impl App {
     }
 }
 
-pub fn setup(mut state: AppState) -> App {
+pub fn setup(state: Arc<AppState>) -> App {
     let docs = Router::new()
         .route("/openapi.json", get(crate::docs::openapi))
         .route("/", get(scalar_handler))
         .route("/favicon.png", get(scalar_favicon_handler))
         .route("/opengraph.png", get(scalar_opengraph_handler));
 
-    let state = Arc::new(state);
-
     let v2 = setup_v2_router(state.clone());
 
-    let router = Router::new()
+    let mut router = Router::new()
         .route("/", get(|| async { Redirect::temporary("/docs") }))
         .nest("/docs", docs)
         .route("/this", get(routes::root::get))
@@ -217,9 +291,23 @@ pub fn setup(mut state: AppState) -> App {
             "/sse/u",
             get(routes::universal::get_bulk_sse).post(routes::universal::post_bulk_sse),
         )
+        .route("/ws", get(routes::ws::get))
         .nest("/v2", v2)
-        .route("/metrics", get(metrics::handle))
+        .route("/metrics", get(metrics::handle));
+
+    if state.admin_token.is_some() {
+        router = router.nest("/admin", routes::admin::setup_admin_router(state.clone()));
+    } else {
+        info!("admin.token not set, admin routes disabled");
+    }
+
+    let router = router
         .fallback(routes::four_oh_four::handler)
+        .layer(middleware::from_fn(http_cache::middleware))
+        .layer(middleware::from_fn_with_state(
+            state.concurrency.retry_after,
+            retry_after_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             rate_limit_middleware,
@@ -242,7 +330,12 @@ pub fn setup(mut state: AppState) -> App {
                 )
             }),
         )
-        .with_state(state);
+        .with_state(state.clone());
+
+    // networks are selected before routing, so a path prefix can be stripped
+    let router = Router::new()
+        .fallback_service(router)
+        .layer(middleware::from_fn_with_state(state, network_middleware));
 
     App { router }
 }
@@ -262,3 +355,87 @@ async fn scalar_opengraph_handler() -> impl axum::response::IntoResponse {
     let contents = include_bytes!("./docs/html/opengraph.png");
     contents
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::State;
+    use axum::middleware;
+    use axum::routing::get;
+    use axum::Router;
+    use enstate_shared::core::error::ProfileError;
+    use enstate_shared::limiter::Limiter;
+    use tokio::net::TcpListener;
+
+    use super::{retry_after_middleware, RateLimiter};
+    use crate::config::{ConcurrencyConfig, RateLimitConfig};
+    use crate::limiter::RpcLimiter;
+    use crate::routes::{profile_http_error_mapper, RouteError};
+    use crate::telemetry::metrics::Metrics;
+
+    #[tokio::test]
+    async fn test_shed_lookup_retry_after() {
+        let limiter = RpcLimiter::new(
+            &ConcurrencyConfig {
+                enabled: true,
+                max_in_flight: 1,
+                per_provider: 1,
+                max_queue: 0,
+                retry_after: 3,
+            },
+            Metrics::new(),
+        );
+
+        let _permit = limiter.acquire().await.unwrap();
+
+        let router = Router::new()
+            .route(
+                "/n/:name",
+                get(|State(limiter): State<RpcLimiter>| async move {
+                    limiter.acquire().await.map(drop).map_err(|err| {
+                        RouteError::from(profile_http_error_mapper(ProfileError::Overloaded(err)))
+                    })
+                }),
+            )
+            .layer(middleware::from_fn_with_state(3, retry_after_middleware))
+            .with_state(limiter.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, router.into_make_service()).await });
+
+        let response = reqwest::get(format!("http://{addr}/n/luc.eth"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(response.headers()["retry-after"], "3");
+
+        let body: serde_json::Value = response.json().await.unwrap();
+
+        assert_eq!(body["code"], "OVERLOADED");
+        assert_eq!(body["details"]["retry_after"], 3);
+    }
+
+    #[test]
+    fn test_reload_replaces_limits() {
+        let mut config = RateLimitConfig {
+            enabled: true,
+            ..RateLimitConfig::default()
+        };
+
+        let limiter = RateLimiter::new(&config);
+        assert_eq!(limiter.limit("/n/:name").unwrap().requests, 160);
+
+        config.routes.remove("/n/:name");
+        limiter.reload(&config);
+
+        assert!(limiter.limit("/n/:name").is_none());
+        assert_eq!(limiter.limit("/bulk/n").unwrap().requests, 9);
+
+        config.enabled = false;
+        limiter.reload(&config);
+
+        assert!(limiter.limit("/bulk/n").is_none());
+    }
+}
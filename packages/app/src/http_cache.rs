@@ -0,0 +1,287 @@
+//! HTTP caching of profile and image responses.
+//!
+//! Handlers describe how long a response stays valid with [`CacheHeaders`], the
+//! [`middleware`] hashes the bodies of those responses into an `ETag` and answers
+//! matching `If-None-Match` requests with `304 Not Modified`.
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, IntoResponseParts, Response, ResponseParts};
+use chrono::{TimeZone, Utc};
+use enstate_shared::core::Profile;
+use sha2::{Digest, Sha256};
+
+use crate::models::bulk::BulkResponse;
+
+/// Headers sent along a 304, as they would've been with the full response.
+///
+/// Responses don't depend on request headers, the `Vary` of the CORS layer
+/// wrapping this one is added to 304s as well.
+const NOT_MODIFIED_HEADERS: [header::HeaderName; 3] =
+    [header::CACHE_CONTROL, header::ETAG, header::LAST_MODIFIED];
+
+/// `Cache-Control` and `Last-Modified` of a response, which gets an `ETag` too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheHeaders {
+    /// Seconds the response stays valid, 0 to always revalidate
+    pub max_age: u64,
+    /// Unix timestamp (milliseconds) the data was loaded at
+    pub last_modified: Option<i64>,
+}
+
+impl CacheHeaders {
+    /// Valid for as long as the profile stays cached
+    pub fn of_profile(profile: &Profile) -> Self {
+        Self {
+            max_age: remaining_ttl(profile),
+            last_modified: Some(profile.fresh),
+        }
+    }
+
+    /// Valid until the soonest expiring profile expires, failed lookups are always revalidated
+    pub fn of_bulk(responses: &[BulkResponse<Profile>]) -> Self {
+        let profiles = || {
+            responses.iter().filter_map(|response| match response {
+                BulkResponse::Ok(profile) => Some(profile),
+                BulkResponse::Err(_) => None,
+            })
+        };
+
+        let max_age = if profiles().count() == responses.len() {
+            profiles().map(remaining_ttl).min().unwrap_or_default()
+        } else {
+            0
+        };
+
+        Self {
+            max_age,
+            last_modified: profiles().map(|profile| profile.fresh).max(),
+        }
+    }
+
+    /// Records looked up on their own aren't cached, they're reused as long as a profile would be
+    pub fn of_record(ttl: u32) -> Self {
+        Self {
+            max_age: u64::from(ttl),
+            last_modified: None,
+        }
+    }
+}
+
+impl IntoResponseParts for CacheHeaders {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let cache_control = match self.max_age {
+            0 => "public, no-cache".to_string(),
+            max_age => format!("public, max-age={max_age}"),
+        };
+
+        res.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&cache_control).expect("cache control should be valid"),
+        );
+
+        let last_modified = self
+            .last_modified
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+            .and_then(|date| {
+                HeaderValue::from_str(&date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).ok()
+            });
+
+        if let Some(last_modified) = last_modified {
+            res.headers_mut()
+                .insert(header::LAST_MODIFIED, last_modified);
+        }
+
+        Ok(res)
+    }
+}
+
+/// Seconds until the profile expires from the cache, 0 when that's unknown
+fn remaining_ttl(profile: &Profile) -> u64 {
+    let now: u64 = Utc::now().timestamp().try_into().unwrap_or_default();
+
+    profile
+        .expires_at
+        .map_or(0, |expires_at| expires_at.saturating_sub(now))
+}
+
+/// Adds an `ETag` to successful responses carrying [`CacheHeaders`] and answers
+/// requests whose `If-None-Match` lists it with `304 Not Modified`.
+pub async fn middleware(req: Request<Body>, next: Next) -> Response {
+    // HEAD responses lose their body before reaching here, their etag wouldn't match GET's
+    let conditional = req.method() == Method::GET;
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(req).await;
+
+    // streamed responses, e.g. /sse, never carry cache headers
+    if !conditional
+        || response.status() != StatusCode::OK
+        || !response.headers().contains_key(header::CACHE_CONTROL)
+        || response.headers().contains_key(header::ETAG)
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let etag = etag(&body);
+
+    parts.headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("etag should be valid"),
+    );
+
+    if if_none_match.is_some_and(|value| matches_etag(&value, &etag)) {
+        return not_modified(&parts.headers);
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Strong validator of a response body, a truncated SHA-256 of its content
+fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(&Sha256::digest(body)[..16]))
+}
+
+/// Whether an `If-None-Match` value lists the etag, compared weakly as RFC 9110 asks
+fn matches_etag(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+fn not_modified(headers: &HeaderMap) -> Response {
+    let mut response = StatusCode::NOT_MODIFIED.into_response();
+
+    for name in NOT_MODIFIED_HEADERS {
+        for value in headers.get_all(&name) {
+            response.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use chrono::Utc;
+    use enstate_shared::core::Profile;
+    use reqwest::{header, StatusCode};
+    use tokio::net::TcpListener;
+
+    use super::{etag, matches_etag, CacheHeaders};
+    use crate::changes::tests::profile;
+    use crate::models::bulk::BulkResponse;
+    use crate::models::error::{ErrorCode, ErrorResponse};
+
+    #[test]
+    fn test_matches_etag() {
+        let etag = etag(b"{\"name\":\"luc.eth\"}");
+
+        let matches = |value: &str| matches_etag(&HeaderValue::from_str(value).unwrap(), &etag);
+
+        assert!(matches(&etag));
+        assert!(matches(&format!("W/{etag}")));
+        assert!(matches(&format!("\"other\", {etag}")));
+        assert!(matches("*"));
+        assert!(!matches("\"other\""));
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        let router = Router::new()
+            .route(
+                "/n/luc.eth",
+                get(|| async {
+                    (
+                        CacheHeaders {
+                            max_age: 60,
+                            last_modified: None,
+                        },
+                        "{\"name\":\"luc.eth\"}",
+                    )
+                }),
+            )
+            .layer(middleware::from_fn(super::middleware));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/n/luc.eth", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let client = reqwest::Client::new();
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let etag = response.headers()[header::ETAG].clone();
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+        assert_eq!(response.text().await.unwrap(), "{\"name\":\"luc.eth\"}");
+
+        let response = client
+            .get(&url)
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+        assert!(response.bytes().await.unwrap().is_empty());
+
+        // a changed body doesn't match
+        let response = client
+            .get(&url)
+            .header(header::IF_NONE_MATCH, "\"other\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_of_bulk() {
+        let expires_at = u64::try_from(Utc::now().timestamp()).unwrap() + 600;
+        let cached = || Profile {
+            expires_at: Some(expires_at),
+            ..profile()
+        };
+
+        let ok = CacheHeaders::of_bulk(&[BulkResponse::Ok(cached()), BulkResponse::Ok(cached())]);
+        assert!(ok.max_age > 0 && ok.max_age <= 600);
+
+        // a failed lookup is revalidated on every request
+        let failed = CacheHeaders::of_bulk(&[
+            BulkResponse::Ok(cached()),
+            BulkResponse::Err(ErrorResponse {
+                status: 404,
+                code: ErrorCode::NameNotFound,
+                error: "Not Found".to_string(),
+                details: None,
+            }),
+        ]);
+        assert_eq!(failed.max_age, 0);
+        assert_eq!(failed.last_modified, Some(profile().fresh));
+    }
+}
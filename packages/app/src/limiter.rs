@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::async_trait;
+use enstate_shared::limiter::{LimitError, Limiter, Permit};
+use prometheus::IntGauge;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::ConcurrencyConfig;
+use crate::telemetry::metrics::Metrics;
+
+/// Bounds the number of lookups resolving from chain at once, queueing the
+/// rest and shedding new lookups once `concurrency.max_queue` are waiting.
+///
+/// Clones share their slots, so a single limiter bounds every network. Each
+/// provider is bounded on its own by [`crate::provider::RoundRobin`].
+#[derive(Clone)]
+pub struct RpcLimiter {
+    semaphore: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    max_queue: usize,
+    retry_after: u64,
+    metrics: Metrics,
+}
+
+struct InFlight {
+    _permit: OwnedSemaphorePermit,
+    gauge: IntGauge,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
+
+/// Counts a queued lookup, also when it's dropped while waiting (e.g. on disconnect)
+struct Waiting<'a>(&'a RpcLimiter);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::Relaxed);
+        self.0.metrics.rpc_queue_depth.dec();
+    }
+}
+
+impl RpcLimiter {
+    pub fn new(config: &ConcurrencyConfig, metrics: Metrics) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(config.max_in_flight)),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            max_queue: config.max_queue,
+            retry_after: config.retry_after,
+            metrics,
+        }
+    }
+
+    fn in_flight(&self, permit: OwnedSemaphorePermit) -> Permit {
+        self.metrics.rpc_in_flight.inc();
+
+        Box::new(InFlight {
+            _permit: permit,
+            gauge: self.metrics.rpc_in_flight.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Limiter for RpcLimiter {
+    async fn acquire(&self) -> Result<Permit, LimitError> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            self.metrics.rpc_queue_seconds.observe(0.0);
+
+            return Ok(self.in_flight(permit));
+        }
+
+        if self.waiting.fetch_add(1, Ordering::Relaxed) >= self.max_queue {
+            self.waiting.fetch_sub(1, Ordering::Relaxed);
+            self.metrics.rpc_shed.inc();
+
+            return Err(LimitError::Overloaded {
+                retry_after: self.retry_after,
+            });
+        }
+
+        self.metrics.rpc_queue_depth.inc();
+        let waiting = Waiting(self);
+
+        let start = Instant::now();
+
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("limiter semaphore should never be closed");
+
+        drop(waiting);
+
+        self.metrics
+            .rpc_queue_seconds
+            .observe(start.elapsed().as_secs_f64());
+
+        Ok(self.in_flight(permit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use enstate_shared::limiter::{LimitError, Limiter};
+
+    use super::RpcLimiter;
+    use crate::config::ConcurrencyConfig;
+    use crate::telemetry::metrics::Metrics;
+
+    fn limiter(max_in_flight: usize, max_queue: usize) -> RpcLimiter {
+        RpcLimiter::new(
+            &ConcurrencyConfig {
+                enabled: true,
+                max_in_flight,
+                per_provider: max_in_flight,
+                max_queue,
+                retry_after: 3,
+            },
+            Metrics::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_queueing() {
+        let limiter = limiter(1, 1);
+
+        let permit = limiter.acquire().await.unwrap();
+
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+
+            async move { limiter.acquire().await.map(drop) }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+        assert_eq!(limiter.metrics.rpc_queue_depth.get(), 1);
+        assert_eq!(limiter.metrics.rpc_in_flight.get(), 1);
+
+        drop(permit);
+
+        queued.await.unwrap().unwrap();
+        assert_eq!(limiter.metrics.rpc_queue_depth.get(), 0);
+        assert_eq!(limiter.metrics.rpc_in_flight.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shedding() {
+        let limiter = limiter(1, 1);
+
+        let _permit = limiter.acquire().await.unwrap();
+
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+
+            async move { limiter.acquire().await.map(drop) }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the queue is full
+        assert!(matches!(
+            limiter.acquire().await,
+            Err(LimitError::Overloaded { retry_after: 3 })
+        ));
+        assert_eq!(limiter.metrics.rpc_shed.get(), 1);
+
+        // a lookup dropped while queued frees its place
+        queued.abort();
+        let _ = queued.await;
+
+        let requeued = tokio::spawn({
+            let limiter = limiter.clone();
+
+            async move { limiter.acquire().await.map(drop) }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!requeued.is_finished());
+        assert_eq!(limiter.metrics.rpc_shed.get(), 1);
+
+        requeued.abort();
+    }
+}
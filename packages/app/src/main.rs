@@ -1,6 +1,7 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
-use std::env;
+use std::process::exit;
+use std::sync::Arc;
 
 use dotenvy::dotenv;
 use futures::FutureExt;
@@ -8,18 +9,30 @@ use tokio::signal::unix::{signal, SignalKind};
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 
+use config::Config;
 use state::AppState;
 
 mod abi;
 mod cache;
+mod changes;
+mod config;
 mod database;
 mod docs;
+mod follower;
 mod http;
+mod http_cache;
+mod limiter;
 mod models;
+mod multicall;
+mod popularity;
 mod provider;
+mod rainbow;
 mod routes;
+mod safety;
 mod state;
 mod telemetry;
+mod warmer;
+mod webhooks;
 mod discovery;
 
 #[tokio::main]
@@ -35,14 +48,56 @@ async fn main() {
     let mut sigint_signal =
         signal(SignalKind::interrupt()).expect("SIGINT handler should've registered");
 
-    let state = AppState::new().await;
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{err}");
+            exit(1);
+        }
+    };
+
+    let state = Arc::new(AppState::new(&config, &shutdown_signal).await);
+
+    let mut sighup_signal =
+        signal(SignalKind::hangup()).expect("SIGHUP handler should've registered");
+
+    let reload_state = state.clone();
+    tokio::spawn(async move {
+        while sighup_signal.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+
+            match Config::load() {
+                Ok(config) => reload_state.reload(&config),
+                Err(err) => error!("failed to reload configuration, keeping the current one: {err}"),
+            }
+        }
+    });
+
+    let warmer_thread = warmer::Warmer::spawn(state.clone(), shutdown_signal.clone());
+
+    let follower_thread = follower::Follower::spawn(
+        state.clone(),
+        config.follower.clone(),
+        shutdown_signal.clone(),
+    );
+
+    let popular_names_thread = safety::PopularNames::spawn(
+        state.clone(),
+        config.safety.clone(),
+        shutdown_signal.clone(),
+    );
+
+    let changes_thread = changes::ChangeDetector::spawn(state.clone(), shutdown_signal.clone());
+
+    let refresher_thread =
+        changes::WatchRefresher::spawn(state.clone(), &config, shutdown_signal.clone());
+
+    let webhooks_thread =
+        webhooks::dispatcher::Dispatcher::spawn(state.clone(), shutdown_signal.clone());
 
     let shutdown_clone = shutdown_signal.clone();
 
-    let port: u16 = env::var("PORT")
-        .unwrap_or_else(|_| "3000".to_string())
-        .parse()
-        .expect("PORT should be a number");
+    let port = config.port;
 
     let server_future = async move {
         let http_result = http::setup(state).listen(port, shutdown_clone).await;
@@ -70,5 +125,20 @@ async fn main() {
 
     let _ = server_thread.await;
 
+    let _ = changes_thread.await;
+
+    for thread in [
+        warmer_thread,
+        follower_thread,
+        webhooks_thread,
+        popular_names_thread,
+        refresher_thread,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        let _ = thread.await;
+    }
+
     info!("exited successfully");
 }
@@ -1,7 +1,86 @@
+use axum::http::StatusCode;
+use serde_json::Value;
 use utoipa::ToSchema;
 
 #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, ToSchema)]
 pub struct ErrorResponse {
     pub(crate) status: u16,
+    pub(crate) code: ErrorCode,
     pub(crate) error: String,
+    /// Structured context for some codes, e.g. `retry_after` when `OVERLOADED`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[schema(value_type = Option<Object>)]
+    pub(crate) details: Option<Value>,
+}
+
+impl ErrorResponse {
+    pub fn new(status: StatusCode, code: ErrorCode, error: impl Into<String>) -> Self {
+        Self {
+            status: status.as_u16(),
+            code,
+            error: error.into(),
+            details: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_details(self, details: Value) -> Self {
+        Self {
+            details: Some(details),
+            ..self
+        }
+    }
+}
+
+/// Stable, machine-readable reason of an [`ErrorResponse`].
+///
+/// Clients should match on these rather than on `error`, which is meant for humans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The name doesn't exist or has no resolver
+    NameNotFound,
+    /// The address has no primary name set
+    NoPrimaryName,
+    /// The primary name of the address doesn't resolve back to it
+    ReverseMismatch,
+    /// An offchain (CCIP-Read) gateway failed
+    CcipGatewayError,
+    /// The RPC provider failed
+    RpcUnavailable,
+    InvalidName,
+    InvalidAddress,
+    /// The request is malformed in some other way
+    InvalidInput,
+    RateLimited,
+    /// Too many lookups are in progress, see `details.retry_after`
+    Overloaded,
+    /// The lookup took too long
+    Timeout,
+    Unauthorized,
+    /// The route or resource doesn't exist
+    NotFound,
+    Conflict,
+    UnsupportedMediaType,
+    /// A backing service other than the RPC provider failed, e.g. the cache
+    UpstreamError,
+    InternalError,
+}
+
+impl ErrorCode {
+    /// Generic code of a status, for errors without a more specific one
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Self::InvalidInput,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::Unauthorized,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            StatusCode::BAD_GATEWAY => Self::UpstreamError,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Overloaded,
+            StatusCode::GATEWAY_TIMEOUT => Self::Timeout,
+            _ => Self::InternalError,
+        }
+    }
 }
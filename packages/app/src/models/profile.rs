@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
 
+use enstate_shared::core::error::RecordError;
 use enstate_shared::core::Profile;
+use enstate_shared::safety::SafetyReport;
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, ToSchema)]
@@ -31,8 +33,22 @@ pub struct ENSProfile {
     // Resolver the information was fetched from
     #[schema(example = "0x4976fb03C32e5B8cfe2b6cCB31c09Ba78EBaBa41")]
     pub resolver: String,
+    // Backend the name was resolved through, for names routed to another registry
+    #[schema(example = "gnosis")]
+    #[serde(default)]
+    pub backend: Option<String>,
     // Errors encountered while fetching & decoding
+    #[schema(example = "{\"avatar\":\"Timed out\"}")]
     pub errors: BTreeMap<String, String>,
+    // The same errors with a machine-readable code
+    #[serde(default)]
+    pub error_codes: BTreeMap<String, RecordError>,
+    // Some records timed out and are missing, only present when true
+    #[serde(default)]
+    pub partial: bool,
+    // Whether the name can be mistaken for another, unless disabled
+    #[serde(default)]
+    pub safety: Option<SafetyReport>,
 }
 
 impl From<Profile> for ENSProfile {
@@ -47,7 +63,11 @@ impl From<Profile> for ENSProfile {
             chains: profile.chains,
             fresh: profile.fresh,
             resolver: profile.resolver.to_string(),
+            backend: profile.backend,
             errors: profile.errors,
+            error_codes: profile.error_codes,
+            partial: profile.partial,
+            safety: profile.safety,
         }
     }
 }
@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use enstate_shared::batch::{BatchError, CallBatcher, CallOutcome};
+use enstate_shared::utils::factory::Factory;
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes, TransactionRequest};
+use ethers_core::abi::{self, ParamType, Token};
+use hex_literal::hex;
+use tokio::sync::{mpsc, oneshot};
+use tracing::warn;
+
+use crate::config::MulticallConfig;
+use crate::provider::RoundRobin;
+use crate::telemetry::metrics::Metrics;
+
+// aggregate3((address,bool,bytes)[])
+const AGGREGATE3_SELECTOR: [u8; 4] = hex!("82ad56cb");
+
+struct PendingCall {
+    target: Address,
+    data: Bytes,
+    reply: oneshot::Sender<Result<CallOutcome, BatchError>>,
+}
+
+/// Collects the calls made within `multicall.window` of each other, up to
+/// `multicall.max_calls`, and sends them as a single Multicall3 `aggregate3` call.
+///
+/// Every call is allowed to fail on its own, so one reverting name never affects the others.
+pub struct Multicall3 {
+    sender: mpsc::Sender<PendingCall>,
+}
+
+impl Multicall3 {
+    pub fn spawn(config: &MulticallConfig, provider: RoundRobin, metrics: Metrics) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<PendingCall>(config.max_calls * 8);
+
+        let (address, window, max_calls) = (
+            config.address,
+            Duration::from_millis(config.window),
+            config.max_calls,
+        );
+
+        // stops once the service, and with it the sender, is dropped
+        tokio::spawn(async move {
+            while let Some(first) = receiver.recv().await {
+                let mut batch = vec![first];
+
+                let deadline = tokio::time::sleep(window);
+                tokio::pin!(deadline);
+
+                while batch.len() < max_calls {
+                    tokio::select! {
+                        () = &mut deadline => break,
+                        call = receiver.recv() => match call {
+                            Some(call) => batch.push(call),
+                            None => break,
+                        },
+                    }
+                }
+
+                metrics
+                    .multicall_batch_size
+                    .observe(f64::from(u32::try_from(batch.len()).unwrap_or(u32::MAX)));
+
+                let provider = provider.clone();
+                let metrics = metrics.clone();
+
+                // keep collecting the next batch while this one is in flight
+                tokio::spawn(async move {
+                    aggregate(address, batch, &provider, &metrics).await;
+                });
+            }
+        });
+
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl CallBatcher for Multicall3 {
+    async fn call(&self, to: Address, data: Bytes) -> Result<CallOutcome, BatchError> {
+        let (reply, response) = oneshot::channel();
+
+        self.sender
+            .send(PendingCall {
+                target: to,
+                data,
+                reply,
+            })
+            .await
+            .map_err(|_| BatchError::Closed)?;
+
+        response.await.map_err(|_| BatchError::Closed)?
+    }
+}
+
+async fn aggregate(
+    address: Address,
+    batch: Vec<PendingCall>,
+    provider: &RoundRobin,
+    metrics: &Metrics,
+) {
+    let calls = batch
+        .iter()
+        .map(|call| {
+            Token::Tuple(vec![
+                Token::Address(call.target),
+                Token::Bool(true),
+                Token::Bytes(call.data.to_vec()),
+            ])
+        })
+        .collect();
+
+    let data = [
+        AGGREGATE3_SELECTOR.as_slice(),
+        &abi::encode(&[Token::Array(calls)]),
+    ]
+    .concat();
+
+    let transaction: TypedTransaction = TransactionRequest::new().to(address).data(data).into();
+
+    // the batched lookups take a provider slot only once their calls are in
+    let (provider, _permit) = provider.lease().await;
+
+    let outcomes = provider
+        .call(&transaction, None)
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|res| decode(&res, batch.len()));
+
+    match outcomes {
+        Ok(outcomes) => {
+            for (call, outcome) in batch.into_iter().zip(outcomes) {
+                let _ = call.reply.send(Ok(outcome));
+            }
+        }
+        Err(err) => {
+            warn!("multicall of {} calls failed: {err}", batch.len());
+            metrics.multicall_errors.inc();
+
+            for call in batch {
+                let _ = call.reply.send(Err(BatchError::Failed(err.clone())));
+            }
+        }
+    }
+}
+
+fn decode(res: &[u8], expected: usize) -> Result<Vec<CallOutcome>, String> {
+    let decoded = abi::decode(
+        &[ParamType::Array(Box::new(ParamType::Tuple(vec![
+            ParamType::Bool,
+            ParamType::Bytes,
+        ])))],
+        res,
+    )
+    .map_err(|err| err.to_string())?;
+
+    let Some(Token::Array(results)) = decoded.into_iter().next() else {
+        return Err("unexpected aggregate3 response".to_string());
+    };
+
+    if results.len() != expected {
+        return Err(format!(
+            "expected {expected} results, got {}",
+            results.len()
+        ));
+    }
+
+    results
+        .into_iter()
+        .map(|result| match result {
+            Token::Tuple(result) => match result.as_slice() {
+                [Token::Bool(true), Token::Bytes(data)] => {
+                    Ok(CallOutcome::Success(data.clone().into()))
+                }
+                [Token::Bool(false), Token::Bytes(data)] => {
+                    Ok(CallOutcome::Reverted(data.clone().into()))
+                }
+                _ => Err("unexpected aggregate3 result".to_string()),
+            },
+            _ => Err("unexpected aggregate3 result".to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use enstate_shared::batch::CallOutcome;
+    use ethers_core::abi::{self, Token};
+
+    use super::decode;
+
+    #[test]
+    fn test_decode() {
+        let res = abi::encode(&[Token::Array(vec![
+            Token::Tuple(vec![Token::Bool(true), Token::Bytes(vec![1, 2])]),
+            Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![0x55, 0x6f])]),
+        ])]);
+
+        let outcomes = decode(&res, 2).unwrap();
+
+        assert!(matches!(&outcomes[0], CallOutcome::Success(data) if data.as_ref() == [1, 2]));
+        assert!(
+            matches!(&outcomes[1], CallOutcome::Reverted(data) if data.as_ref() == [0x55, 0x6f])
+        );
+        assert!(decode(&res, 3).is_err());
+    }
+}
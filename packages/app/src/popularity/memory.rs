@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use enstate_shared::popularity::{PopularEntry, Popularity, PopularityError, PopularityKind};
+
+use super::{unix_now, Window};
+
+#[derive(Default)]
+struct Scores {
+    epoch: u64,
+    current: HashMap<String, f64>,
+    previous: HashMap<String, f64>,
+}
+
+impl Scores {
+    fn rotate(&mut self, epoch: u64) {
+        if epoch == self.epoch {
+            return;
+        }
+
+        self.previous = if epoch == self.epoch + 1 {
+            std::mem::take(&mut self.current)
+        } else {
+            self.current.clear();
+
+            HashMap::new()
+        };
+
+        self.epoch = epoch;
+    }
+}
+
+/// In-process popularity counters, per instance and lost on restart.
+pub struct Memory {
+    windows: Vec<Window>,
+    scores: Mutex<HashMap<(PopularityKind, String), Scores>>,
+    max_entries: usize,
+}
+
+impl Memory {
+    pub fn new(windows: Vec<Window>, max_entries: usize) -> Self {
+        Self {
+            windows,
+            scores: Mutex::new(HashMap::new()),
+            max_entries,
+        }
+    }
+}
+
+#[async_trait]
+impl Popularity for Memory {
+    async fn record_hit(&self, kind: PopularityKind, key: &str) -> Result<(), PopularityError> {
+        let now = unix_now();
+        let mut scores = self
+            .scores
+            .lock()
+            .expect("popularity lock should not be poisoned");
+
+        for window in &self.windows {
+            let scores = scores.entry((kind, window.name.clone())).or_default();
+
+            scores.rotate(window.epoch(now));
+
+            *scores.current.entry(key.to_string()).or_default() += window.weight(now);
+
+            // trim in batches, so the sort is amortized over many hits
+            if scores.current.len() > self.max_entries * 2 {
+                let mut entries = scores.current.drain().collect::<Vec<_>>();
+                entries.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+                entries.truncate(self.max_entries);
+
+                scores.current = entries.into_iter().collect();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn top(
+        &self,
+        kind: PopularityKind,
+        window: &str,
+        limit: usize,
+    ) -> Result<Vec<PopularEntry>, PopularityError> {
+        let window = self
+            .windows
+            .iter()
+            .find(|it| it.name == window)
+            .ok_or_else(|| PopularityError::UnknownWindow(window.to_string()))?;
+
+        let now = unix_now();
+        let mut scores = self
+            .scores
+            .lock()
+            .expect("popularity lock should not be poisoned");
+
+        let Some(scores) = scores.get_mut(&(kind, window.name.clone())) else {
+            return Ok(Vec::new());
+        };
+
+        scores.rotate(window.epoch(now));
+
+        let mut merged = scores.current.clone();
+        for (key, score) in &scores.previous {
+            *merged.entry(key.clone()).or_default() += score * Window::previous_epoch_weight();
+        }
+
+        let weight = window.weight(now);
+
+        let mut entries = merged
+            .into_iter()
+            .map(|(key, score)| PopularEntry {
+                key,
+                score: score / weight,
+            })
+            .collect::<Vec<_>>();
+
+        entries.sort_unstable_by(|a, b| b.score.total_cmp(&a.score));
+        entries.truncate(limit);
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use enstate_shared::popularity::{Popularity, PopularityKind};
+
+    use super::{Memory, Window};
+
+    #[tokio::test]
+    async fn test_top_orders_by_hits() {
+        let popularity = Memory::new(
+            vec![Window {
+                name: "day".to_string(),
+                half_life: 86400,
+            }],
+            100,
+        );
+
+        for name in [
+            "luc.eth",
+            "nick.eth",
+            "luc.eth",
+            "vitalik.eth",
+            "luc.eth",
+            "nick.eth",
+        ] {
+            popularity
+                .record_hit(PopularityKind::Name, name)
+                .await
+                .unwrap();
+        }
+
+        let top = popularity
+            .top(PopularityKind::Name, "day", 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            top.iter().map(|it| it.key.as_str()).collect::<Vec<_>>(),
+            ["luc.eth", "nick.eth"]
+        );
+        assert!((top[0].score - 3.0).abs() < 0.01);
+
+        assert!(popularity
+            .top(PopularityKind::Address, "day", 2)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(popularity
+            .top(PopularityKind::Name, "year", 2)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::async_trait;
+use enstate_shared::popularity::{PopularEntry, Popularity, PopularityError, PopularityKind};
+use prometheus::IntCounter;
+use redis::aio::ConnectionManager;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::cache::{Breaker, Reconnecting};
+use crate::config::{CacheConfig, PopularityConfig};
+use crate::database;
+use crate::telemetry::metrics::Metrics;
+
+pub use self::memory::Memory;
+pub use self::redis::Redis;
+
+mod memory;
+mod redis;
+
+/// Hits waiting to be recorded, further hits are dropped until the recorder catches up.
+const QUEUE_SIZE: usize = 4096;
+
+/// Half lives after which a window moves its landmark, keeping weights below 2^32.
+const EPOCH_HALF_LIVES: u32 = 32;
+
+/// A decay window, scores in it halve every `half_life` seconds.
+///
+/// Uses forward decay: a hit at `now` is recorded with weight
+/// `2^((now - landmark) / half_life)`, so older scores never need rewriting and
+/// dividing by the current weight yields the decayed hit count. The landmark moves
+/// every [`EPOCH_HALF_LIVES`] half lives, scores of the previous epoch are merged in
+/// at [`Window::previous_epoch_weight`].
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub name: String,
+    pub half_life: u64,
+}
+
+impl Window {
+    fn epoch_len(&self) -> u64 {
+        self.half_life * u64::from(EPOCH_HALF_LIVES)
+    }
+
+    fn epoch(&self, now: u64) -> u64 {
+        now / self.epoch_len()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn weight(&self, now: u64) -> f64 {
+        let since_landmark = now % self.epoch_len();
+
+        (since_landmark as f64 / self.half_life as f64).exp2()
+    }
+
+    fn previous_epoch_weight() -> f64 {
+        (-f64::from(EPOCH_HALF_LIVES)).exp2()
+    }
+}
+
+fn windows(config: &BTreeMap<String, u64>) -> Vec<Window> {
+    config
+        .iter()
+        .map(|(name, half_life)| Window {
+            name: name.clone(),
+            half_life: *half_life,
+        })
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now()
+        .timestamp()
+        .try_into()
+        .unwrap_or_default()
+}
+
+/// Records hits in the background, so lookups never wait for the tracker.
+pub struct Recorder {
+    hits: mpsc::Sender<(PopularityKind, String)>,
+    inner: Arc<dyn Popularity>,
+    dropped: IntCounter,
+}
+
+impl Recorder {
+    pub fn spawn(
+        inner: Arc<dyn Popularity>,
+        dropped: IntCounter,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let (hits, mut queue) = mpsc::channel::<(PopularityKind, String)>(QUEUE_SIZE);
+
+        let popularity = inner.clone();
+        tokio::spawn(async move {
+            loop {
+                let (kind, key) = tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    hit = queue.recv() => match hit {
+                        Some(hit) => hit,
+                        None => break,
+                    },
+                };
+
+                if let Err(err) = popularity.record_hit(kind, &key).await {
+                    warn!(key, "failed to record {} hit: {err}", kind.as_str());
+                }
+            }
+
+            info!("Popularity recorder shutdown");
+        });
+
+        Self {
+            hits,
+            inner,
+            dropped,
+        }
+    }
+}
+
+#[async_trait]
+impl Popularity for Recorder {
+    async fn record_hit(&self, kind: PopularityKind, key: &str) -> Result<(), PopularityError> {
+        if self.hits.try_send((kind, key.to_string())).is_err() {
+            self.dropped.inc();
+        }
+
+        Ok(())
+    }
+
+    async fn top(
+        &self,
+        kind: PopularityKind,
+        window: &str,
+        limit: usize,
+    ) -> Result<Vec<PopularEntry>, PopularityError> {
+        self.inner.top(kind, window, limit).await
+    }
+}
+
+pub async fn setup(
+    config: &PopularityConfig,
+    cache: &CacheConfig,
+    metrics: &Metrics,
+    shutdown: &CancellationToken,
+) -> Option<Box<dyn Popularity>> {
+    if !config.enabled {
+        return None;
+    }
+
+    let windows = windows(&config.windows);
+
+    let popularity: Arc<dyn Popularity> = match &cache.redis_url {
+        Some(redis_url) => {
+            info!("Tracking popularity in Redis");
+
+            let breaker = Breaker::new(
+                "popularity",
+                cache.breaker_threshold,
+                Duration::from_secs(cache.breaker_cooldown),
+                metrics.popularity_circuit_open.clone(),
+            );
+
+            Arc::new(Redis::new(
+                connect(redis_url, cache, shutdown).await,
+                breaker,
+                windows,
+                config.max_entries,
+            ))
+        }
+        None => Arc::new(Memory::new(windows, config.max_entries)),
+    };
+
+    Some(Box::new(Recorder::spawn(
+        popularity,
+        metrics.popularity_dropped_hits.clone(),
+        shutdown.clone(),
+    )))
+}
+
+async fn connect(
+    redis_url: &str,
+    cache: &CacheConfig,
+    shutdown: &CancellationToken,
+) -> Reconnecting<ConnectionManager> {
+    match database::setup(redis_url).await {
+        Ok(redis) => Reconnecting::connected(redis),
+        Err(err) => {
+            warn!("failed to connect to redis, not tracking popularity until it becomes reachable: {err}");
+
+            let redis_url = redis_url.to_string();
+
+            Reconnecting::spawn(
+                move || {
+                    let redis_url = redis_url.clone();
+
+                    async move { database::setup(&redis_url).await }
+                },
+                Duration::from_secs(cache.reconnect_interval),
+                shutdown.clone(),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::async_trait;
+    use enstate_shared::popularity::{PopularEntry, Popularity, PopularityError, PopularityKind};
+    use prometheus::IntCounter;
+    use tokio_util::sync::CancellationToken;
+
+    use super::{Memory, Recorder, Window, QUEUE_SIZE};
+
+    /// Never finishes recording a hit
+    struct Stalled;
+
+    #[async_trait]
+    impl Popularity for Stalled {
+        async fn record_hit(
+            &self,
+            _kind: PopularityKind,
+            _key: &str,
+        ) -> Result<(), PopularityError> {
+            std::future::pending().await
+        }
+
+        async fn top(
+            &self,
+            _kind: PopularityKind,
+            _window: &str,
+            _limit: usize,
+        ) -> Result<Vec<PopularEntry>, PopularityError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn dropped() -> IntCounter {
+        IntCounter::new("dropped", "dropped").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_records_in_background() {
+        let memory = Memory::new(
+            vec![Window {
+                name: "day".to_string(),
+                half_life: 86400,
+            }],
+            100,
+        );
+        let recorder = Recorder::spawn(Arc::new(memory), dropped(), CancellationToken::new());
+
+        for name in ["luc.eth", "nick.eth", "luc.eth"] {
+            recorder
+                .record_hit(PopularityKind::Name, name)
+                .await
+                .unwrap();
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let top = recorder.top(PopularityKind::Name, "day", 2).await.unwrap();
+
+        assert_eq!(
+            top.iter().map(|it| it.key.as_str()).collect::<Vec<_>>(),
+            ["luc.eth", "nick.eth"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drops_hits_when_behind() {
+        let dropped = dropped();
+        let recorder =
+            Recorder::spawn(Arc::new(Stalled), dropped.clone(), CancellationToken::new());
+
+        // a stalled tracker never holds up the lookup
+        tokio::time::timeout(Duration::from_secs(1), async {
+            for _ in 0..QUEUE_SIZE + 2 {
+                recorder
+                    .record_hit(PopularityKind::Name, "luc.eth")
+                    .await
+                    .unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(dropped.get() > 0);
+    }
+}
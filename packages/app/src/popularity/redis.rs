@@ -0,0 +1,146 @@
+use axum::async_trait;
+use enstate_shared::popularity::{PopularEntry, Popularity, PopularityError, PopularityKind};
+use redis::aio::ConnectionManager;
+use redis::RedisResult;
+
+use super::{unix_now, Window};
+use crate::cache::{Breaker, Reconnecting};
+
+/// Popularity counters shared between instances, one sorted set per kind, window and epoch.
+///
+/// Hits are dropped while Redis is unreachable or the breaker is open, popularity is best effort.
+pub struct Redis {
+    redis: Reconnecting<ConnectionManager>,
+    breaker: Breaker,
+    windows: Vec<Window>,
+    max_entries: usize,
+}
+
+impl Redis {
+    pub const fn new(
+        redis: Reconnecting<ConnectionManager>,
+        breaker: Breaker,
+        windows: Vec<Window>,
+        max_entries: usize,
+    ) -> Self {
+        Self {
+            redis,
+            breaker,
+            windows,
+            max_entries,
+        }
+    }
+
+    fn connection(&self) -> Option<ConnectionManager> {
+        self.redis.current().filter(|_| !self.breaker.is_open())
+    }
+
+    fn record<T>(&self, operation: &str, result: RedisResult<T>) -> Result<T, PopularityError> {
+        match result {
+            Ok(value) => {
+                self.breaker.record_success();
+
+                Ok(value)
+            }
+            Err(err) => {
+                self.breaker.record_failure(operation, &err);
+
+                Err(PopularityError::Other(err.to_string()))
+            }
+        }
+    }
+}
+
+fn scores_key(kind: PopularityKind, window: &Window, epoch: u64) -> String {
+    format!("popular:{}:{}:{epoch}", kind.as_str(), window.name)
+}
+
+#[async_trait]
+impl Popularity for Redis {
+    async fn record_hit(&self, kind: PopularityKind, member: &str) -> Result<(), PopularityError> {
+        let now = unix_now();
+        let mut pipe = redis::pipe();
+
+        for window in &self.windows {
+            let key = scores_key(kind, window, window.epoch(now));
+
+            // the previous epoch is still read until the next one starts
+            let expires = (window.epoch_len() * 2).try_into().unwrap_or(i64::MAX);
+
+            pipe.zincr(&key, member, window.weight(now))
+                .ignore()
+                .expire(&key, expires)
+                .ignore()
+                .zremrangebyrank(
+                    &key,
+                    0,
+                    -isize::try_from(self.max_entries).unwrap_or(isize::MAX) - 1,
+                )
+                .ignore();
+        }
+
+        let Some(mut redis) = self.connection() else {
+            return Ok(());
+        };
+
+        let result = pipe.query_async::<_, ()>(&mut redis).await;
+
+        self.record("record_hit", result)
+    }
+
+    async fn top(
+        &self,
+        kind: PopularityKind,
+        window: &str,
+        limit: usize,
+    ) -> Result<Vec<PopularEntry>, PopularityError> {
+        let window = self
+            .windows
+            .iter()
+            .find(|it| it.name == window)
+            .ok_or_else(|| PopularityError::UnknownWindow(window.to_string()))?;
+
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let now = unix_now();
+        let epoch = window.epoch(now);
+
+        let merged = format!("popular:{}:{}:top", kind.as_str(), window.name);
+        let sources = [
+            (scores_key(kind, window, epoch), 1.0),
+            (
+                scores_key(kind, window, epoch.saturating_sub(1)),
+                Window::previous_epoch_weight(),
+            ),
+        ];
+
+        let stop = isize::try_from(limit).unwrap_or(isize::MAX) - 1;
+
+        let Some(mut redis) = self.connection() else {
+            return Err(PopularityError::Other("redis unavailable".to_string()));
+        };
+
+        let result = redis::pipe()
+            .zunionstore_weights(&merged, &sources)
+            .ignore()
+            .expire(&merged, 60)
+            .ignore()
+            .zrevrange_withscores(&merged, 0, stop)
+            .query_async(&mut redis)
+            .await;
+
+        let (entries,): (Vec<(String, f64)>,) = self.record("top", result)?;
+
+        let weight = window.weight(now);
+
+        Ok(entries
+            .into_iter()
+            .map(|(key, score)| PopularEntry {
+                key,
+                score: score / weight,
+            })
+            .collect())
+    }
+}
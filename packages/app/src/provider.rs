@@ -1,17 +1,28 @@
 use std::sync::Arc;
 
+use axum::async_trait;
+use enstate_shared::limiter::Permit;
 use enstate_shared::utils::factory::Factory;
 use ethers::providers::{Http, Provider};
 use rand::seq::SliceRandom;
+use tokio::sync::Semaphore;
 use tracing::warn;
 
+/// Spreads lookups over the providers of a network, each with its own bound on the
+/// lookups using it at once.
+///
+/// Clones share the providers and their bounds, e.g. between a network and the
+/// backends routed to it.
 #[derive(Clone)]
 pub struct RoundRobin {
-    providers: Vec<Arc<Provider<Http>>>,
+    providers: Vec<(Arc<Provider<Http>>, Arc<Semaphore>)>,
 }
 
 impl RoundRobin {
-    pub fn new(rpc_urls: Vec<String>) -> Self {
+    /// `per_provider` bounds the lookups leasing each provider, unbounded when unset
+    pub fn new(rpc_urls: Vec<String>, per_provider: Option<usize>) -> Self {
+        let permits = per_provider.unwrap_or(Semaphore::MAX_PERMITS);
+
         Self {
             providers: rpc_urls
                 .into_iter()
@@ -23,16 +34,82 @@ impl RoundRobin {
 
                     provider.ok().map(Arc::new)
                 })
+                .map(|provider| (provider, Arc::new(Semaphore::new(permits))))
                 .collect(),
         }
     }
-}
 
-impl Factory<Arc<Provider<Http>>> for RoundRobin {
-    fn get_instance(&self) -> Arc<Provider<Http>> {
+    /// One of the providers with the most spare slots, picked at random
+    fn least_busy(&self) -> &(Arc<Provider<Http>>, Arc<Semaphore>) {
+        let most_available = self
+            .providers
+            .iter()
+            .map(|(_, semaphore)| semaphore.available_permits())
+            .max()
+            .expect("provider should exist");
+
         self.providers
+            .iter()
+            .filter(|(_, semaphore)| semaphore.available_permits() == most_available)
+            .collect::<Vec<_>>()
             .choose(&mut rand::thread_rng())
+            .copied()
             .expect("provider should exist")
+    }
+}
+
+#[async_trait]
+impl Factory<Arc<Provider<Http>>> for RoundRobin {
+    /// Picks one of the least busy providers without taking a slot on it
+    fn get_instance(&self) -> Arc<Provider<Http>> {
+        self.least_busy().0.clone()
+    }
+
+    /// Waits for a slot on one of the least busy providers
+    async fn lease(&self) -> (Arc<Provider<Http>>, Option<Permit>) {
+        let (provider, semaphore) = self.least_busy();
+
+        let permit = semaphore
             .clone()
+            .acquire_owned()
+            .await
+            .expect("provider semaphore should never be closed");
+
+        (provider.clone(), Some(Box::new(permit) as Permit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use enstate_shared::utils::factory::Factory;
+
+    use super::RoundRobin;
+
+    #[tokio::test]
+    async fn test_lease_per_provider() {
+        let provider = RoundRobin::new(
+            vec![
+                "http://localhost:8545".to_string(),
+                "http://localhost:8546".to_string(),
+            ],
+            Some(1),
+        );
+
+        let (first, first_permit) = provider.lease().await;
+        let (second, _second_permit) = provider.lease().await;
+
+        // the least busy provider is picked, one slot each
+        assert_ne!(first.url(), second.url());
+
+        // both are busy now
+        let third = tokio::time::timeout(Duration::from_millis(50), provider.lease()).await;
+        assert!(third.is_err());
+
+        drop(first_permit);
+
+        let (third, _third_permit) = provider.lease().await;
+        assert_eq!(third.url(), first.url());
     }
 }
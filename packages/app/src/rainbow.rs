@@ -0,0 +1,200 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+
+use enstate_shared::healer::LabelHealer;
+use enstate_shared::utils::name::labelhash;
+use ethers::types::H256;
+use flate2::read::GzDecoder;
+use thiserror::Error;
+
+const INDEX_MAGIC: &[u8; 8] = b"ENSRAINB";
+const GZIP_MAGIC: &[u8; 2] = &[0x1f, 0x8b];
+
+#[derive(Error, Debug)]
+pub enum RainbowTableError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("entry {0} is truncated")]
+    Truncated(usize),
+    #[error("label of entry {0} is not valid utf-8")]
+    InvalidLabel(usize),
+}
+
+/// Labels by labelhash, kept sorted by hash to heal with a binary search.
+///
+/// Read from either a sorted binary index (`ENSRAINB` followed by entries of a 32-byte
+/// labelhash, a length byte and the label) or a text file with one label per line,
+/// either optionally gzip compressed.
+pub struct RainbowTable {
+    hashes: Vec<[u8; 32]>,
+    /// Label `i` is `labels[offsets[i]..offsets[i + 1]]`
+    offsets: Vec<usize>,
+    labels: String,
+}
+
+impl RainbowTable {
+    pub fn open(path: &Path) -> Result<Self, RainbowTableError> {
+        Self::read(File::open(path)?)
+    }
+
+    fn read(reader: impl Read) -> Result<Self, RainbowTableError> {
+        let (magic, reader) = peek(reader, GZIP_MAGIC.len())?;
+
+        if magic == GZIP_MAGIC {
+            return Self::read_uncompressed(GzDecoder::new(reader));
+        }
+
+        Self::read_uncompressed(reader)
+    }
+
+    fn read_uncompressed(reader: impl Read) -> Result<Self, RainbowTableError> {
+        let (magic, mut reader) = peek(reader, INDEX_MAGIC.len())?;
+
+        let entries = if magic == INDEX_MAGIC {
+            let mut index = Vec::new();
+            reader.read_to_end(&mut index)?;
+
+            parse_index(&index[INDEX_MAGIC.len()..])?
+        } else {
+            let mut entries = Vec::new();
+
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                let label = line.trim();
+
+                if !label.is_empty() {
+                    entries.push((labelhash(label).0, label.to_string()));
+                }
+            }
+
+            entries
+        };
+
+        Ok(Self::from_entries(entries))
+    }
+
+    fn from_entries(mut entries: Vec<([u8; 32], String)>) -> Self {
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|a, b| a.0 == b.0);
+
+        let mut table = Self {
+            hashes: Vec::with_capacity(entries.len()),
+            offsets: Vec::with_capacity(entries.len() + 1),
+            labels: String::new(),
+        };
+
+        table.offsets.push(0);
+
+        for (hash, label) in entries {
+            table.hashes.push(hash);
+            table.labels.push_str(&label);
+            table.offsets.push(table.labels.len());
+        }
+
+        table.labels.shrink_to_fit();
+
+        table
+    }
+
+    pub fn label_count(&self) -> usize {
+        self.hashes.len()
+    }
+}
+
+impl LabelHealer for RainbowTable {
+    fn heal(&self, labelhash: &H256) -> Option<String> {
+        let index = self.hashes.binary_search(&labelhash.0).ok()?;
+
+        Some(self.labels[self.offsets[index]..self.offsets[index + 1]].to_string())
+    }
+}
+
+/// Reads the first `n` bytes, returning them with a reader that still starts at the beginning
+fn peek<R: Read>(mut reader: R, n: usize) -> io::Result<(Vec<u8>, impl Read)> {
+    let mut head = Vec::with_capacity(n);
+    (&mut reader).take(n as u64).read_to_end(&mut head)?;
+
+    Ok((head.clone(), Cursor::new(head).chain(reader)))
+}
+
+fn parse_index(mut rest: &[u8]) -> Result<Vec<([u8; 32], String)>, RainbowTableError> {
+    let mut entries = Vec::new();
+
+    while !rest.is_empty() {
+        let index = entries.len();
+
+        let (Some(hash), Some((&len, after))) =
+            (rest.get(..32), rest.get(32..).and_then(<[u8]>::split_first))
+        else {
+            return Err(RainbowTableError::Truncated(index));
+        };
+
+        let Some(label) = after.get(..usize::from(len)) else {
+            return Err(RainbowTableError::Truncated(index));
+        };
+
+        let label =
+            std::str::from_utf8(label).map_err(|_| RainbowTableError::InvalidLabel(index))?;
+
+        entries.push((
+            hash.try_into().expect("hash should be 32 bytes"),
+            label.to_string(),
+        ));
+
+        rest = &after[usize::from(len)..];
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use enstate_shared::healer::LabelHealer;
+    use enstate_shared::utils::name::labelhash;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::{RainbowTable, RainbowTableError, INDEX_MAGIC};
+
+    #[test]
+    fn test_text() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"vitalik\nluc\n\nluc\n").unwrap();
+
+        let table = RainbowTable::read(encoder.finish().unwrap().as_slice()).unwrap();
+
+        assert_eq!(table.label_count(), 2);
+        assert_eq!(table.heal(&labelhash("luc")).as_deref(), Some("luc"));
+        assert_eq!(
+            table.heal(&labelhash("vitalik")).as_deref(),
+            Some("vitalik")
+        );
+        assert_eq!(table.heal(&labelhash("eth")), None);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut index = INDEX_MAGIC.to_vec();
+
+        for label in ["eth", "luc"] {
+            index.extend(labelhash(label).as_bytes());
+            index.push(u8::try_from(label.len()).unwrap());
+            index.extend(label.as_bytes());
+        }
+
+        let table = RainbowTable::read(index.as_slice()).unwrap();
+
+        assert_eq!(table.heal(&labelhash("eth")).as_deref(), Some("eth"));
+        assert_eq!(table.heal(&labelhash("luc")).as_deref(), Some("luc"));
+
+        index.pop();
+
+        assert!(matches!(
+            RainbowTable::read(index.as_slice()),
+            Err(RainbowTableError::Truncated(1))
+        ));
+    }
+}
@@ -1,11 +1,10 @@
 use std::convert::Infallible;
-use std::sync::Arc;
 use std::time::Duration;
 
 use axum::response::sse::Event;
 use axum::response::{IntoResponse, Sse};
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, Query},
     http::StatusCode,
     Json,
 };
@@ -15,12 +14,15 @@ use ethers_core::types::Address;
 use futures::future::join_all;
 use serde::Deserialize;
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::warn;
 
+use crate::http_cache::CacheHeaders;
 use crate::models::bulk::{BulkResponse, ListResponse};
+use crate::models::error::ErrorCode;
 use crate::models::sse::SSEResponse;
 use crate::routes::{
-    http_simple_status_error, profile_http_error_mapper, validate_bulk_input, FreshQuery, Qs,
-    RouteError,
+    http_simple_status_error, invalid_address, profile_http_error_mapper, validate_bulk_input,
+    FreshQuery, Network, Qs, RouteError,
 };
 
 /// /a/{address}
@@ -46,19 +48,19 @@ use crate::routes::{
 pub async fn get(
     Path(address): Path<String>,
     Query(query): Query<FreshQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> Result<Json<Profile>, RouteError> {
+    network: Network,
+) -> Result<(CacheHeaders, Json<Profile>), RouteError> {
     get_bulk(
         Qs(AddressGetBulkQuery {
             fresh: query,
             addresses: vec![address],
         }),
-        State(state),
+        network,
     )
     .await
-    .map(|mut res| {
+    .map(|(_, mut res)| {
         Result::from(res.0.response.remove(0))
-            .map(Json)
+            .map(|profile| (CacheHeaders::of_profile(&profile), Json(profile)))
             .map_err(RouteError::from)
     })?
 }
@@ -95,29 +97,24 @@ pub struct AddressGetBulkQuery {
 )]
 pub async fn get_bulk(
     Qs(query): Qs<AddressGetBulkQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> Result<Json<ListResponse<BulkResponse<Profile>>>, RouteError> {
-    let addresses =
-        validate_bulk_input(&query.addresses, state.service.max_bulk_size)?;
+    network: Network,
+) -> Result<(CacheHeaders, Json<ListResponse<BulkResponse<Profile>>>), RouteError> {
+    let addresses = validate_bulk_input(&query.addresses, network.settings().max_bulk_size)?;
 
     let addresses = addresses
         .iter()
         .map(|address| address.parse::<Address>())
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| http_simple_status_error(StatusCode::BAD_REQUEST))?;
+        .map_err(|_| invalid_address())?;
 
     let profiles = addresses
         .iter()
-        .map(|address| {
-            state
-                .service
-                .resolve_profile(LookupInfo::Address(*address), query.fresh.fresh)
-        })
+        .map(|address| network.resolve_profile(LookupInfo::Address(*address), query.fresh.fresh))
         .collect::<Vec<_>>();
 
-    let joined = join_all(profiles).await.into();
+    let joined: ListResponse<BulkResponse<Profile>> = join_all(profiles).await.into();
 
-    Ok(Json(joined))
+    Ok((CacheHeaders::of_bulk(&joined.response), Json(joined)))
 }
 
 /// /sse/a
@@ -132,7 +129,7 @@ pub async fn get_bulk(
     path = "/sse/a",
     responses(
         (status = 200, description = "Successfully found address.", body = BulkResponse<ENSProfile>),
-        (status = BAD_REQUEST, description = "Invalid address.", body = ErrorResponse),
+        (status = BAD_REQUEST, description = "Too many addresses, invalid ones are reported in their own event.", body = ErrorResponse),
         (status = NOT_FOUND, description = "No name was associated with this address.", body = ErrorResponse),
         (status = UNPROCESSABLE_ENTITY, description = "Reverse record not owned by this address.", body = ErrorResponse),
     ),
@@ -142,19 +139,18 @@ pub async fn get_bulk(
 )]
 pub async fn get_bulk_sse(
     Qs(query): Qs<AddressGetBulkQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> impl IntoResponse {
-    let addresses =
-        validate_bulk_input(&query.addresses, state.service.max_bulk_size).unwrap();
+    network: Network,
+) -> Result<impl IntoResponse, RouteError> {
+    let addresses = validate_bulk_input(&query.addresses, network.settings().max_bulk_size)?;
 
     let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<Result<Event, Infallible>>();
 
     for address_input in addresses {
-        let state_clone = state.clone();
+        let service = network.0.clone();
         let event_tx_clone = event_tx.clone();
         tokio::spawn(async move {
             let dne_cache_key = format!("dne:{}", address_input);
-            let cached_not_exists = state_clone.service.cache.get(&dne_cache_key).await;
+            let cached_not_exists = service.cache.get(&dne_cache_key).await;
 
             if let Ok(_) = cached_not_exists {
                 let sse_response = SSEResponse {
@@ -173,18 +169,33 @@ pub async fn get_bulk_sse(
                 let address = address_input.parse::<Address>();
 
                 let Ok(address) = address else {
-                    break 'a Err(http_simple_status_error(StatusCode::BAD_REQUEST));
+                    break 'a Err(invalid_address());
                 };
 
-                state_clone
-                    .service
+                service
                     .resolve_profile(LookupInfo::Address(address), query.fresh.fresh)
                     .await
                     .map_err(profile_http_error_mapper)
             };
 
-            if profile.is_err() {
-                state_clone.service.cache.set(&dne_cache_key, "404", state_clone.service.cache_ttl.unwrap_or(600)).await;
+            // only remember addresses without a name, not lookups that failed for now
+            let not_found = matches!(
+                &profile,
+                Err(err) if matches!(err.code, ErrorCode::NameNotFound | ErrorCode::NoPrimaryName)
+            );
+
+            if not_found {
+                if let Err(err) = service
+                    .cache
+                    .set(
+                        &dne_cache_key,
+                        b"404",
+                        service.settings().cache_ttl.unwrap_or(600),
+                    )
+                    .await
+                {
+                    warn!(address = %address_input, "failed to cache missing name: {err}");
+                }
             }
 
             let sse_response = SSEResponse {
@@ -198,8 +209,8 @@ pub async fn get_bulk_sse(
         });
     }
 
-    Sse::new(UnboundedReceiverStream::new(event_rx))
-        .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(1)))
+    Ok(Sse::new(UnboundedReceiverStream::new(event_rx))
+        .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(1))))
 }
 
 /// /sse/a
@@ -211,15 +222,15 @@ pub async fn get_bulk_sse(
     path = "/sse/a",
     responses(
         (status = 200, description = "Successfully found address.", body = BulkResponse<ENSProfile>),
-        (status = BAD_REQUEST, description = "Invalid address.", body = ErrorResponse),
+        (status = BAD_REQUEST, description = "Too many addresses, invalid ones are reported in their own event.", body = ErrorResponse),
         (status = NOT_FOUND, description = "No name was associated with this address.", body = ErrorResponse),
         (status = UNPROCESSABLE_ENTITY, description = "Reverse record not owned by this address.", body = ErrorResponse),
     ),
     request_body = AddressGetBulkQuery,
 )]
 pub async fn post_bulk_sse(
-    State(state): State<Arc<crate::AppState>>,
+    network: Network,
     Json(query): Json<AddressGetBulkQuery>,
-) -> impl IntoResponse {
-    get_bulk_sse(Qs(query), State(state)).await
+) -> Result<impl IntoResponse, RouteError> {
+    get_bulk_sse(Qs(query), network).await
 }
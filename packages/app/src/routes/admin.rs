@@ -0,0 +1,387 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use enstate_shared::cache::envelope::{self, Header};
+use enstate_shared::cache::CacheError;
+use enstate_shared::core::lookup_data::LookupInfo;
+use enstate_shared::core::ENSService;
+use ethers_core::types::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::models::error::ErrorCode;
+use crate::routes::webhooks::setup_webhooks_router;
+use crate::routes::{http_coded_error, http_error, Network, RouteError};
+
+pub fn setup_admin_router(state: Arc<crate::AppState>) -> Router<Arc<crate::AppState>> {
+    let mut router = Router::new()
+        .route("/cache", delete(purge))
+        .route("/cache/n/:name", get(get_name).delete(delete_name))
+        .route("/cache/a/:address", get(get_address).delete(delete_address));
+
+    if state.webhooks.is_some() {
+        router = router.merge(setup_webhooks_router());
+    }
+
+    router.route_layer(middleware::from_fn_with_state(state, require_token))
+}
+
+/// Rejects requests without `Authorization: Bearer <admin.token>`.
+pub(crate) async fn require_token(
+    State(state): State<Arc<crate::AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, RouteError> {
+    let Some(token) = state.admin_token.as_deref() else {
+        return Err(http_error(StatusCode::NOT_FOUND, "Not found"));
+    };
+
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(provided) if constant_time_eq(provided.as_bytes(), token.as_bytes()) => {
+            Ok(next.run(req).await)
+        }
+        _ => Err(http_error(StatusCode::UNAUTHORIZED, "Invalid admin token")),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[derive(Serialize)]
+pub struct CacheEntryResponse {
+    key: String,
+    value: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    envelope: Option<EnvelopeResponse>,
+    /// Remaining time to live in seconds, `null` if the entry never expires
+    ttl: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct EnvelopeResponse {
+    schema_version: u16,
+    encoding: String,
+    created_at: u64,
+    expires_at: u64,
+}
+
+impl From<Header> for EnvelopeResponse {
+    fn from(header: Header) -> Self {
+        Self {
+            schema_version: header.schema_version,
+            encoding: format!("{:?}", header.encoding),
+            created_at: header.created_at,
+            expires_at: header.expires_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PurgeResponse {
+    purged: u64,
+}
+
+/// GET /admin/cache/n/{name}
+pub async fn get_name(
+    Path(name): Path<String>,
+    network: Network,
+) -> Result<Json<CacheEntryResponse>, RouteError> {
+    inspect(&network, name_key(&network, &name)?).await
+}
+
+/// DELETE /admin/cache/n/{name}
+pub async fn delete_name(
+    Path(name): Path<String>,
+    network: Network,
+) -> Result<StatusCode, RouteError> {
+    invalidate(&network, &name_key(&network, &name)?).await
+}
+
+/// GET /admin/cache/a/{address}
+pub async fn get_address(
+    Path(address): Path<String>,
+    network: Network,
+) -> Result<Json<CacheEntryResponse>, RouteError> {
+    inspect(&network, address_key(&address)?).await
+}
+
+/// DELETE /admin/cache/a/{address}
+pub async fn delete_address(
+    Path(address): Path<String>,
+    network: Network,
+) -> Result<StatusCode, RouteError> {
+    invalidate(&network, &address_key(&address)?).await
+}
+
+#[derive(Deserialize)]
+pub struct PurgeQuery {
+    prefix: Option<String>,
+    #[serde(default)]
+    all: bool,
+}
+
+/// DELETE /admin/cache?prefix={prefix}
+///
+/// Deletes every entry whose key starts with `prefix` (e.g. `n:` for all names).
+/// Purging the entire cache requires `?all=true` instead.
+pub async fn purge(
+    Query(query): Query<PurgeQuery>,
+    network: Network,
+) -> Result<Json<PurgeResponse>, RouteError> {
+    let prefix = match (query.prefix, query.all) {
+        (Some(prefix), false) if !prefix.is_empty() => prefix,
+        (None, true) => String::new(),
+        _ => {
+            return Err(http_error(
+                StatusCode::BAD_REQUEST,
+                "Either a non-empty prefix or all=true should be provided",
+            ))
+        }
+    };
+
+    let purged = network.cache.purge(&prefix).await.map_err(cache_error)?;
+
+    Ok(Json(PurgeResponse { purged }))
+}
+
+fn name_key(service: &ENSService, name: &str) -> Result<String, RouteError> {
+    match LookupInfo::guess(name) {
+        Ok(LookupInfo::Name(name)) => Ok(service.profile_key(&name)),
+        _ => Err(http_coded_error(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidName,
+            "Invalid name",
+        )),
+    }
+}
+
+fn address_key(address: &str) -> Result<String, RouteError> {
+    address
+        .parse::<Address>()
+        .map(|address| format!("a:{address:?}"))
+        .map_err(|_| {
+            http_coded_error(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidAddress,
+                "Invalid address",
+            )
+        })
+}
+
+async fn inspect(
+    service: &ENSService,
+    key: String,
+) -> Result<Json<CacheEntryResponse>, RouteError> {
+    let cache = &service.cache;
+
+    let value = cache.get(&key).await.map_err(cache_error)?;
+    let ttl = cache.ttl(&key).await.map_err(cache_error)?;
+
+    let (envelope, value) = match envelope::decode_header(&value) {
+        Ok((header, payload)) => {
+            let value = envelope::decode_payload(&header, payload)
+                .map_err(|err| http_error(StatusCode::UNPROCESSABLE_ENTITY, &err.to_string()))?;
+
+            (Some(EnvelopeResponse::from(header)), value)
+        }
+        // primary names and entries from before the envelope are stored as plain text
+        Err(_) => {
+            let value = String::from_utf8_lossy(&value).to_string();
+
+            (
+                None,
+                serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value)),
+            )
+        }
+    };
+
+    Ok(Json(CacheEntryResponse {
+        key,
+        value,
+        envelope,
+        ttl,
+    }))
+}
+
+async fn invalidate(service: &ENSService, key: &str) -> Result<StatusCode, RouteError> {
+    service.cache.delete(key).await.map_err(cache_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn cache_error(err: CacheError) -> RouteError {
+    match err {
+        CacheError::NotFound => http_error(StatusCode::NOT_FOUND, "Not cached"),
+        CacheError::Other(err) => http_error(StatusCode::BAD_GATEWAY, &err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Router;
+    use enstate_shared::cache::envelope;
+    use enstate_shared::core::profile::PROFILE_SCHEMA_VERSION;
+    use ethers_core::types::Address;
+    use reqwest::StatusCode;
+    use serde_json::Value;
+    use tokio::net::TcpListener;
+    use tokio_util::sync::CancellationToken;
+
+    use super::setup_admin_router;
+    use crate::changes::tests::profile;
+    use crate::config::Config;
+    use crate::state::AppState;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    /// Admin routes of a service with an in-memory cache, and their url
+    async fn serve() -> (Arc<AppState>, String) {
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [rpc]
+            urls = ["http://localhost:8545"]
+            universal_resolver = "0xce01f8eee7E479C928F8919abD53E553a36CeF67"
+
+            [admin]
+            token = "{TOKEN}"
+            "#
+        ))
+        .unwrap();
+
+        let state = Arc::new(AppState::new(&config, &CancellationToken::new()).await);
+
+        let router = Router::new()
+            .nest("/admin", setup_admin_router(state.clone()))
+            .with_state(state.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/admin", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        (state, url)
+    }
+
+    #[tokio::test]
+    async fn test_require_token() {
+        let (_, url) = serve().await;
+        let client = reqwest::Client::new();
+        let url = format!("{url}/cache/n/luc.eth");
+
+        let missing = client.get(&url).send().await.unwrap();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = client
+            .get(&url)
+            .bearer_auth("fedcba9876543210")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+
+        let prefix = client
+            .get(&url)
+            .bearer_auth(&TOKEN[..8])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(prefix.status(), StatusCode::UNAUTHORIZED);
+
+        // let through, the name just isn't cached
+        let correct = client.get(&url).bearer_auth(TOKEN).send().await.unwrap();
+        assert_eq!(correct.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_inspect() {
+        let (state, url) = serve().await;
+        let client = reqwest::Client::new();
+        let cache = &state.service.cache;
+
+        let encoded = envelope::encode(&profile(), PROFILE_SCHEMA_VERSION, false, 600).unwrap();
+        cache.set("n:luc.eth", &encoded, 600).await.unwrap();
+
+        let response = client
+            .get(format!("{url}/cache/n/luc.eth"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let entry: Value = response.json().await.unwrap();
+        assert_eq!(entry["key"], "n:luc.eth");
+        assert_eq!(entry["value"]["name"], "luc.eth");
+        assert_eq!(entry["envelope"]["schema_version"], PROFILE_SCHEMA_VERSION);
+        assert!(entry["ttl"].as_u64().is_some_and(|ttl| ttl <= 600));
+
+        // primary names are plain text
+        let address = Address::repeat_byte(1);
+        cache
+            .set(&format!("a:{address:?}"), b"luc.eth", 600)
+            .await
+            .unwrap();
+
+        let entry: Value = client
+            .get(format!("{url}/cache/a/{address:?}"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(entry["value"], "luc.eth");
+        assert!(entry.get("envelope").is_none());
+
+        let invalid = client
+            .get(format!("{url}/cache/a/luc.eth"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let (state, url) = serve().await;
+        let client = reqwest::Client::new();
+        let cache = &state.service.cache;
+
+        cache.set("n:luc.eth", b"profile", 600).await.unwrap();
+        cache.set("n:nick.eth", b"profile", 600).await.unwrap();
+
+        let response = client
+            .delete(format!("{url}/cache/n/luc.eth"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        assert!(cache.get("n:luc.eth").await.is_err());
+        assert!(cache.get("n:nick.eth").await.is_ok());
+
+        let gone = client
+            .get(format!("{url}/cache/n/luc.eth"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(gone.status(), StatusCode::NOT_FOUND);
+    }
+}
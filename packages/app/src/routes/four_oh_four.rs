@@ -1,15 +1,8 @@
-use crate::models::error::ErrorResponse;
 use axum::http::StatusCode;
-use axum::Json;
 
-use crate::routes::RouteError;
+use crate::models::error::ErrorCode;
+use crate::routes::{http_coded_error, RouteError};
 
 pub async fn handler() -> RouteError {
-    (
-        StatusCode::NOT_FOUND,
-        Json(ErrorResponse {
-            status: StatusCode::NOT_FOUND.as_u16(),
-            error: "Unknown route".to_string(),
-        }),
-    )
+    http_coded_error(StatusCode::NOT_FOUND, ErrorCode::NotFound, "Unknown route")
 }
@@ -1,6 +1,4 @@
-use std::sync::Arc;
-
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, Query};
 use axum::http::header::CONTENT_TYPE;
 use axum::http::StatusCode;
 use axum::response::{AppendHeaders, IntoResponse, Redirect};
@@ -8,7 +6,8 @@ use enstate_shared::core::error::ProfileError;
 use enstate_shared::core::lookup_data::LookupInfo;
 use enstate_shared::models::lookup::ENSLookup;
 
-use crate::routes::{FreshQuery, http_simple_status_error, profile_http_error_mapper, RouteError};
+use crate::http_cache::CacheHeaders;
+use crate::routes::{FreshQuery, http_simple_status_error, profile_http_error_mapper, Network, RouteError};
 
 /// Banner Endpoint
 /// 
@@ -37,28 +36,30 @@ use crate::routes::{FreshQuery, http_simple_status_error, profile_http_error_map
 pub async fn get(
     Path(name_or_address): Path<String>,
     Query(query): Query<FreshQuery>,
-    State(state): State<Arc<crate::AppState>>,
+    network: Network,
 ) -> Result<impl IntoResponse, RouteError> {
     let info = LookupInfo::guess(name_or_address)
-        .map_err(|_| profile_http_error_mapper(ProfileError::NotFound))?;
+        .map_err(|_| profile_http_error_mapper(ProfileError::InvalidName))?;
 
-    let header = state
-        .service
+    let header = network
         .resolve_record_simple(info, ENSLookup::Image("header".to_string()), query.fresh)
         .await
         .map_err(profile_http_error_mapper)?;
 
+    let cache_headers = CacheHeaders::of_record(network.settings().cache_ttl.unwrap_or(600));
+
     if let Some(processed) = enstate_shared::utils::data_url::process_data_url_image(&header) {
         let Ok(processed) = processed else {
             return Err(http_simple_status_error(StatusCode::UNSUPPORTED_MEDIA_TYPE).into());
         };
 
         return Ok((
+            cache_headers,
             AppendHeaders([(CONTENT_TYPE, processed.mimetype)]),
             processed.data,
         )
             .into_response());
     }
 
-    Ok(Redirect::to(header.as_str()).into_response())
+    Ok((cache_headers, Redirect::to(header.as_str())).into_response())
 }
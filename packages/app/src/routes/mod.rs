@@ -1,17 +1,27 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use axum::http::StatusCode;
 use axum::Json;
+use enstate_shared::core::address::AddressResolveError;
 use enstate_shared::core::error::ProfileError;
+use enstate_shared::core::resolvers::reverse::ReverseResolveError;
+use enstate_shared::core::ENSService;
+use enstate_shared::limiter::LimitError;
 use enstate_shared::utils::vec::dedup_ord;
 use ethers::prelude::ProviderError;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Deserializer};
+use serde_json::json;
 use thiserror::Error;
 
-use crate::models::error::ErrorResponse;
+use crate::models::error::{ErrorCode, ErrorResponse};
+use crate::state::AppState;
 
 pub mod address;
+pub mod admin;
 pub mod four_oh_four;
 pub mod header;
 pub mod image;
@@ -19,6 +29,8 @@ pub mod name;
 pub mod root;
 pub mod universal;
 pub mod v2;
+pub mod webhooks;
+pub mod ws;
 
 #[derive(Deserialize)]
 pub struct FreshQuery {
@@ -48,36 +60,64 @@ impl From<ErrorResponse> for RouteError {
 
 pub fn profile_http_error_mapper<T: AsRef<ProfileError>>(err: T) -> ErrorResponse {
     let err = err.as_ref();
-    let status = match err {
-        ProfileError::NotFound => StatusCode::NOT_FOUND,
-        ProfileError::CCIPError(_) => StatusCode::BAD_GATEWAY,
-        ProfileError::RPCError(ProviderError::EnsNotOwned(_)) => StatusCode::UNPROCESSABLE_ENTITY,
-        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    let (status, code) = match err {
+        ProfileError::NotFound => (StatusCode::NOT_FOUND, ErrorCode::NameNotFound),
+        ProfileError::InvalidName | ProfileError::DNSEncodeError(_) => {
+            (StatusCode::BAD_REQUEST, ErrorCode::InvalidName)
+        }
+        ProfileError::CCIPError(_) => (StatusCode::BAD_GATEWAY, ErrorCode::CcipGatewayError),
+        ProfileError::RPCError(ProviderError::EnsNotOwned(_))
+        | ProfileError::AddressResolveError(AddressResolveError::ReverseResolutionError(
+            ReverseResolveError::AddressMismatch,
+        )) => (StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ReverseMismatch),
+        ProfileError::AddressResolveError(
+            AddressResolveError::NotFound
+            | AddressResolveError::ReverseResolutionError(ReverseResolveError::MissingPrimaryName),
+        ) => (StatusCode::NOT_FOUND, ErrorCode::NoPrimaryName),
+        ProfileError::RPCError(_)
+        | ProfileError::AddressResolveError(
+            AddressResolveError::RPCError(_)
+            | AddressResolveError::ReverseResolutionError(ReverseResolveError::RPCError(_)),
+        ) => (StatusCode::BAD_GATEWAY, ErrorCode::RpcUnavailable),
+        ProfileError::Overloaded(LimitError::Overloaded { retry_after })
+        | ProfileError::AddressResolveError(AddressResolveError::Overloaded(
+            LimitError::Overloaded { retry_after },
+        )) => {
+            return ErrorResponse::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::Overloaded,
+                err.to_string(),
+            )
+            .with_details(json!({ "retry_after": retry_after }));
+        }
+        ProfileError::Timeout => (StatusCode::GATEWAY_TIMEOUT, ErrorCode::Timeout),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::InternalError),
     };
 
-    ErrorResponse {
-        status: status.as_u16(),
-        error: err.to_string(),
-    }
+    ErrorResponse::new(status, code, err.to_string())
 }
 
 pub fn http_simple_status_error(status: StatusCode) -> ErrorResponse {
-    ErrorResponse {
-        status: status.as_u16(),
-        error: status
-            .canonical_reason()
-            .unwrap_or("Unknown error")
-            .to_string(),
-    }
+    ErrorResponse::new(
+        status,
+        ErrorCode::from_status(status),
+        status.canonical_reason().unwrap_or("Unknown error"),
+    )
 }
 
 pub fn http_error(status: StatusCode, error: &str) -> RouteError {
-    (
-        status,
-        Json(ErrorResponse {
-            status: status.as_u16(),
-            error: error.to_string(),
-        }),
+    ErrorResponse::new(status, ErrorCode::from_status(status), error).into()
+}
+
+pub fn http_coded_error(status: StatusCode, code: ErrorCode, error: &str) -> RouteError {
+    ErrorResponse::new(status, code, error).into()
+}
+
+pub fn invalid_address() -> ErrorResponse {
+    ErrorResponse::new(
+        StatusCode::BAD_REQUEST,
+        ErrorCode::InvalidAddress,
+        "Invalid address",
     )
 }
 
@@ -89,7 +129,11 @@ pub enum ValidationError {
 
 impl From<ValidationError> for RouteError {
     fn from(value: ValidationError) -> Self {
-        http_error(StatusCode::BAD_REQUEST, &value.to_string())
+        http_coded_error(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidInput,
+            &value.to_string(),
+        )
     }
 }
 
@@ -133,3 +177,52 @@ where
         ))
     }
 }
+
+/// Network a request selected, with a `/{network}` path prefix or `?network=`
+#[derive(Clone)]
+pub struct SelectedNetwork(pub String);
+
+/// Service of the network the request selected, the default network's if it didn't select one
+#[derive(Clone)]
+pub struct Network(pub Arc<ENSService>);
+
+impl Network {
+    pub fn is_default(&self, state: &AppState) -> bool {
+        Arc::ptr_eq(&self.0, &state.service)
+    }
+}
+
+impl Deref for Network {
+    type Target = ENSService;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<AppState>> for Network {
+    type Rejection = RouteError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(SelectedNetwork(network)) = parts.extensions.get::<SelectedNetwork>() else {
+            return Ok(Self(state.service.clone()));
+        };
+
+        state
+            .networks
+            .get(network)
+            .cloned()
+            .map(Self)
+            .ok_or_else(|| {
+                http_coded_error(
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidInput,
+                    &format!("Unknown network {network}"),
+                )
+            })
+    }
+}
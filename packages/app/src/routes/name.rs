@@ -14,9 +14,12 @@ use futures::future::join_all;
 use serde::Deserialize;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
+use crate::http_cache::CacheHeaders;
 use crate::models::bulk::{BulkResponse, ListResponse};
 use crate::models::sse::SSEResponse;
-use crate::routes::{profile_http_error_mapper, validate_bulk_input, FreshQuery, Qs, RouteError};
+use crate::routes::{
+    profile_http_error_mapper, validate_bulk_input, FreshQuery, Network, Qs, RouteError,
+};
 
 /// /n/{name}
 ///
@@ -39,8 +42,9 @@ use crate::routes::{profile_http_error_mapper, validate_bulk_input, FreshQuery,
 pub async fn get(
     Path(name): Path<String>,
     Query(query): Query<FreshQuery>,
+    network: Network,
     State(state): State<Arc<crate::AppState>>,
-) -> Result<Json<Profile>, RouteError> {
+) -> Result<(CacheHeaders, Json<Profile>), RouteError> {
     let s = state.clone();
     s.metrics.name_lookup_total.inc();
     let start = Instant::now();
@@ -50,12 +54,12 @@ pub async fn get(
             fresh: query,
             names: vec![name],
         }),
-        State(state),
+        network,
     )
     .await
-    .map(|mut res| {
+    .map(|(_, mut res)| {
         Result::<_, _>::from(res.0.response.remove(0))
-            .map(Json)
+            .map(|profile| (CacheHeaders::of_profile(&profile), Json(profile)))
             .map_err(RouteError::from)
     })?;
 
@@ -96,32 +100,26 @@ pub struct NameGetBulkQuery {
 )]
 pub async fn get_bulk(
     Qs(query): Qs<NameGetBulkQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> Result<Json<ListResponse<BulkResponse<Profile>>>, RouteError> {
-    let names = validate_bulk_input(&query.names, state.service.max_bulk_size)?;
+    network: Network,
+) -> Result<(CacheHeaders, Json<ListResponse<BulkResponse<Profile>>>), RouteError> {
+    let names = validate_bulk_input(&query.names, network.settings().max_bulk_size)?;
 
     let profiles = names
         .into_iter()
-        .map(|name| {
-            state
-                .service
-                .resolve_profile(LookupInfo::Name(name), query.fresh.fresh)
-        })
+        .map(|name| network.resolve_profile(LookupInfo::Name(name), query.fresh.fresh))
         .collect::<Vec<_>>();
 
     let joined: ListResponse<BulkResponse<Profile>> = join_all(profiles).await.into();
 
-    // TODO: +1 on cache hit popularity discover
     for profile in &joined.response {
         if let BulkResponse::Ok(profile) = profile {
-            let _ = state.service.cache.cache_hit(&profile.name).await;
-            if let Some(discovery) = &state.service.discovery {
+            if let Some(discovery) = &network.discovery {
                 let _ = discovery.discover_name(profile).await;
             }
         }
     }
 
-    Ok(Json(joined))
+    Ok((CacheHeaders::of_bulk(&joined.response), Json(joined)))
 }
 
 /// /sse/n
@@ -136,6 +134,7 @@ pub async fn get_bulk(
     path = "/sse/n",
     responses(
         (status = 200, description = "Successfully found name.", body = ListButWithLength<BulkResponse<Profile>>),
+        (status = BAD_REQUEST, description = "Too many names.", body = ErrorResponse),
         (status = NOT_FOUND, description = "No name could be found.", body = ErrorResponse),
     ),
     params(
@@ -144,18 +143,17 @@ pub async fn get_bulk(
 )]
 pub async fn get_bulk_sse(
     Qs(query): Qs<NameGetBulkQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> impl IntoResponse {
-    let names = validate_bulk_input(&query.names, state.service.max_bulk_size).unwrap();
+    network: Network,
+) -> Result<impl IntoResponse, RouteError> {
+    let names = validate_bulk_input(&query.names, network.settings().max_bulk_size)?;
 
     let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<Result<Event, Infallible>>();
 
     for name in names {
-        let state_clone = state.clone();
+        let service = network.0.clone();
         let event_tx_clone = event_tx.clone();
         tokio::spawn(async move {
-            let profile = state_clone
-                .service
+            let profile = service
                 .resolve_profile(LookupInfo::Name(name.clone()), query.fresh.fresh)
                 .await
                 .map_err(profile_http_error_mapper);
@@ -171,8 +169,8 @@ pub async fn get_bulk_sse(
         });
     }
 
-    Sse::new(UnboundedReceiverStream::new(event_rx))
-        .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(1)))
+    Ok(Sse::new(UnboundedReceiverStream::new(event_rx))
+        .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(1))))
 }
 
 /// /sse/n
@@ -184,13 +182,14 @@ pub async fn get_bulk_sse(
     path = "/sse/n",
     responses(
         (status = 200, description = "Successfully found name.", body = ListButWithLength<BulkResponse<Profile>>),
+        (status = BAD_REQUEST, description = "Too many names.", body = ErrorResponse),
         (status = NOT_FOUND, description = "No name could be found.", body = ErrorResponse),
     ),
     request_body = NameGetBulkQuery,
 )]
 pub async fn post_bulk_sse(
-    State(state): State<Arc<crate::AppState>>,
+    network: Network,
     Json(query): Json<NameGetBulkQuery>,
-) -> impl IntoResponse {
-    get_bulk_sse(Qs(query), State(state)).await
+) -> Result<impl IntoResponse, RouteError> {
+    get_bulk_sse(Qs(query), network).await
 }
@@ -1,11 +1,10 @@
 use std::convert::Infallible;
-use std::sync::Arc;
 use std::time::Duration;
 
 use axum::response::sse::Event;
 use axum::response::{IntoResponse, Sse};
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, Query},
     Json,
 };
 use enstate_shared::core::error::ProfileError;
@@ -17,9 +16,12 @@ use tokio_stream::wrappers::UnboundedReceiverStream;
 use utoipa::openapi::schema;
 use utoipa::{IntoParams, ToSchema};
 
+use crate::http_cache::CacheHeaders;
 use crate::models::bulk::{BulkResponse, ListResponse};
 use crate::models::sse::SSEResponse;
-use crate::routes::{profile_http_error_mapper, validate_bulk_input, FreshQuery, Qs, RouteError};
+use crate::routes::{
+    profile_http_error_mapper, validate_bulk_input, FreshQuery, Network, Qs, RouteError,
+};
 
 /// /u/{name_or_address}
 ///
@@ -48,31 +50,32 @@ use crate::routes::{profile_http_error_mapper, validate_bulk_input, FreshQuery,
 pub async fn get(
     Path(name_or_address): Path<String>,
     Query(query): Query<FreshQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> Result<Json<Profile>, RouteError> {
+    network: Network,
+) -> Result<(CacheHeaders, Json<Profile>), RouteError> {
+    let service = network.0.clone();
+
     let response = get_bulk(
         Qs(UniversalGetBulkQuery {
             fresh: query,
             queries: vec![name_or_address],
         }),
-        State(state.clone()),
+        network,
     )
     .await;
 
     match response {
-        Ok(mut res) => {
+        Ok((_, mut res)) => {
             for profile in &res.response {
                 if let BulkResponse::Ok(profile) = profile {
                     let profile = profile.clone();
-                    let _ = state.service.cache.cache_hit(&profile.name).await;
-                    if let Some(discovery) = &state.service.discovery {
+                    if let Some(discovery) = &service.discovery {
                         let _ = discovery.discover_name(&profile).await;
                     }
                 }
             }
 
             Result::<_, _>::from(res.response.remove(0))
-            .map(Json)
+            .map(|profile| (CacheHeaders::of_profile(&profile), Json(profile)))
             .map_err(RouteError::from)
         }
         Err(e) => Err(e),
@@ -117,20 +120,20 @@ pub struct UniversalGetBulkQuery {
 )]
 pub async fn get_bulk(
     Qs(query): Qs<UniversalGetBulkQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> Result<Json<ListResponse<BulkResponse<Profile>>>, RouteError> {
-    let queries = validate_bulk_input(&query.queries, state.service.max_bulk_size)?;
+    network: Network,
+) -> Result<(CacheHeaders, Json<ListResponse<BulkResponse<Profile>>>), RouteError> {
+    let queries = validate_bulk_input(&query.queries, network.settings().max_bulk_size)?;
 
     let profiles = queries
         .iter()
         .map(|input| {
-            profile_from_lookup_guess(LookupInfo::guess(input), &state.service, query.fresh.fresh)
+            profile_from_lookup_guess(LookupInfo::guess(input), &network, query.fresh.fresh)
         })
         .collect::<Vec<_>>();
 
-    let joined = join_all(profiles).await.into();
+    let joined: ListResponse<BulkResponse<Profile>> = join_all(profiles).await.into();
 
-    Ok(Json(joined))
+    Ok((CacheHeaders::of_bulk(&joined.response), Json(joined)))
 }
 
 /// /sse/u
@@ -149,6 +152,7 @@ pub async fn get_bulk(
     path = "/sse/u",
     responses(
         (status = 200, description = "Successfully found name or address.", body = BulkResponse<ENSProfile>),
+        (status = BAD_REQUEST, description = "Too many names or addresses.", body = ErrorResponse),
         (status = NOT_FOUND, description = "No name or address could be found.", body = ErrorResponse),
         (status = UNPROCESSABLE_ENTITY, description = "Reverse record not owned by this address.", body = ErrorResponse),
     ),
@@ -158,23 +162,20 @@ pub async fn get_bulk(
 )]
 pub async fn get_bulk_sse(
     Qs(query): Qs<UniversalGetBulkQuery>,
-    State(state): State<Arc<crate::AppState>>,
-) -> impl IntoResponse {
-    let queries = validate_bulk_input(&query.queries, state.service.max_bulk_size).unwrap();
+    network: Network,
+) -> Result<impl IntoResponse, RouteError> {
+    let queries = validate_bulk_input(&query.queries, network.settings().max_bulk_size)?;
 
     let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<Result<Event, Infallible>>();
 
     for input in queries {
-        let state_clone = state.clone();
+        let service = network.0.clone();
         let event_tx_clone = event_tx.clone();
         tokio::spawn(async move {
-            let profile = profile_from_lookup_guess(
-                LookupInfo::guess(&input),
-                &state_clone.service,
-                query.fresh.fresh,
-            )
-            .await
-            .map_err(profile_http_error_mapper);
+            let profile =
+                profile_from_lookup_guess(LookupInfo::guess(&input), &service, query.fresh.fresh)
+                    .await
+                    .map_err(profile_http_error_mapper);
 
             let sse_response = SSEResponse {
                 query: input,
@@ -187,8 +188,8 @@ pub async fn get_bulk_sse(
         });
     }
 
-    Sse::new(UnboundedReceiverStream::new(event_rx))
-        .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(1)))
+    Ok(Sse::new(UnboundedReceiverStream::new(event_rx))
+        .keep_alive(axum::response::sse::KeepAlive::new().interval(Duration::from_secs(1))))
 }
 
 /// /sse/u
@@ -200,16 +201,17 @@ pub async fn get_bulk_sse(
     path = "/sse/u",
     responses(
         (status = 200, description = "Successfully found name or address.", body = BulkResponse<ENSProfile>),
+        (status = BAD_REQUEST, description = "Too many names or addresses.", body = ErrorResponse),
         (status = NOT_FOUND, description = "No name or address could be found.", body = ErrorResponse),
         (status = UNPROCESSABLE_ENTITY, description = "Reverse record not owned by this address.", body = ErrorResponse),
     ),
     request_body = UniversalGetBulkQuery,
 )]
 pub async fn post_bulk_sse(
-    State(state): State<Arc<crate::AppState>>,
+    network: Network,
     Json(query): Json<UniversalGetBulkQuery>,
-) -> impl IntoResponse {
-    get_bulk_sse(Qs(query), State(state)).await
+) -> Result<impl IntoResponse, RouteError> {
+    get_bulk_sse(Qs(query), network).await
 }
 
 // helper function for above
@@ -219,7 +221,7 @@ async fn profile_from_lookup_guess(
     fresh: bool,
 ) -> Result<Profile, ProfileError> {
     let Ok(lookup) = lookup else {
-        return Err(ProfileError::NotFound);
+        return Err(ProfileError::InvalidName);
     };
 
     service.resolve_profile(lookup, fresh).await
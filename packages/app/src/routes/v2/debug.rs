@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use axum::extract::Path;
+use axum::Json;
+use enstate_shared::core::error::{ProfileError, RecordError};
+use enstate_shared::core::lookup_data::LookupInfo;
+use enstate_shared::core::records::MAX_RECORDS_PER_CALL;
+use enstate_shared::core::resolvers::universal::resolve_calldata;
+use enstate_shared::models::lookup::ENSLookup;
+use enstate_shared::utils::dns::dns_encode;
+use enstate_shared::utils::eip55::EIP55Address;
+use enstate_shared::utils::name::namehash;
+use ethers::types::Bytes;
+use serde::Serialize;
+
+use crate::models::error::ErrorResponse;
+use crate::routes::{profile_http_error_mapper, Network, RouteError};
+use crate::telemetry::trace::{self, TraceEntry};
+
+#[derive(Serialize)]
+pub struct DebugResponse {
+    name: String,
+    /// DNS wire format of the name, as passed to the universal resolver
+    dns_encoded: Bytes,
+    namehash: String,
+    /// Unset when names are resolved by walking the registry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    universal_resolver: Option<EIP55Address>,
+    /// `resolve(bytes,bytes[])` calldata of every universal resolver call, the `addr` record
+    /// is always requested first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    calls: Vec<Bytes>,
+    rpc_url: Option<String>,
+    /// Backend the name is routed to, unset for the network's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<String>,
+    resolver: Option<EIP55Address>,
+    ccip_urls: Vec<String>,
+    records: Vec<RecordDebug>,
+    partial: bool,
+    /// The whole lookup failed, `records` only show what was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorResponse>,
+    duration_ms: f64,
+    trace: Vec<TraceEntry>,
+}
+
+#[derive(Serialize)]
+pub struct RecordDebug {
+    record: String,
+    /// Calldata sent to the resolver for this record
+    calldata: Bytes,
+    /// Whether the resolver call succeeded, unset when there was no result, e.g. on timeout
+    success: Option<bool>,
+    /// Returned data, or the revert data when it failed
+    raw: Option<Bytes>,
+    value: Option<String>,
+    error: Option<RecordError>,
+}
+
+/// /v2/debug/{name}
+///
+/// Resolves the profile records of a name from chain, bypassing the cache, and returns
+/// everything that was sent and received along the way.
+/// Requires `Authorization: Bearer <admin.token>`.
+pub async fn get(
+    Path(name): Path<String>,
+    network: Network,
+) -> Result<Json<DebugResponse>, RouteError> {
+    let Ok(LookupInfo::Name(name)) = LookupInfo::guess(name) else {
+        return Err(profile_http_error_mapper(ProfileError::InvalidName).into());
+    };
+
+    let dns_encoded = dns_encode(&name)
+        .map_err(|err| profile_http_error_mapper(ProfileError::DNSEncodeError(err)))?;
+    let node = namehash(&name);
+
+    let mut calldata = network.profile_calldata();
+    calldata.sort_by_key(ENSLookup::name);
+
+    let backend = network.routing.backend(&name);
+    let universal_resolver = backend.map_or(network.universal_resolver, |backend| {
+        backend.universal_resolver
+    });
+
+    let calls = match universal_resolver {
+        Some(_) => calldata
+            .chunks(MAX_RECORDS_PER_CALL)
+            .map(|chunk| resolve_calldata(&name, chunk))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(profile_http_error_mapper)?,
+        None => Vec::new(),
+    };
+
+    let start = Instant::now();
+
+    let (resolved, trace) =
+        trace::collect(network.resolve_records(LookupInfo::Name(name.clone()), &calldata, true))
+            .await;
+
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let (resolved, error) = match resolved {
+        Ok(resolved) => (Some(resolved), None),
+        Err(err) => (None, Some(profile_http_error_mapper(err))),
+    };
+
+    let raw: HashMap<_, _> = resolved
+        .as_ref()
+        .map(|resolved| resolved.raw.iter().map(|(k, v)| (k, v)).collect())
+        .unwrap_or_default();
+
+    let records = calldata
+        .iter()
+        .map(|lookup| {
+            let result = raw.get(lookup);
+
+            RecordDebug {
+                record: lookup.name(),
+                calldata: lookup.calldata(&node).into(),
+                success: result.map(|result| result.success),
+                raw: result.map(|result| result.data.clone().into()),
+                value: resolved
+                    .as_ref()
+                    .and_then(|resolved| resolved.records.get(lookup).cloned()),
+                error: resolved
+                    .as_ref()
+                    .and_then(|resolved| resolved.invalid.get(lookup))
+                    .map(RecordError::from),
+            }
+        })
+        .collect();
+
+    Ok(Json(DebugResponse {
+        name,
+        dns_encoded: dns_encoded.into(),
+        namehash: format!("{node:?}"),
+        universal_resolver: universal_resolver.map(EIP55Address),
+        calls,
+        rpc_url: resolved.as_ref().map(|resolved| resolved.rpc_url.clone()),
+        backend: backend.map(|backend| backend.name.clone()),
+        resolver: resolved
+            .as_ref()
+            .map(|resolved| EIP55Address(resolved.resolver)),
+        ccip_urls: resolved
+            .as_ref()
+            .map(|resolved| resolved.ccip_urls.clone())
+            .unwrap_or_default(),
+        records,
+        partial: resolved.as_ref().is_some_and(|resolved| resolved.partial),
+        error,
+        duration_ms,
+        trace,
+    }))
+}
@@ -0,0 +1,61 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::Json;
+use enstate_shared::utils::name::decode_label;
+use ethers::types::H256;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::routes::{http_error, Network, RouteError};
+
+#[derive(Serialize, ToSchema)]
+pub struct HealResponse {
+    #[schema(value_type = String, example = "0x4f5b812789fc606be1b3b16908db13fc7a9adf7ca72641f84d75b47069d3d7f0")]
+    labelhash: H256,
+    #[schema(example = "eth")]
+    label: String,
+}
+
+/// /v2/heal/{labelhash}
+///
+/// Here is an example of a valid request that heals the labelhash of `eth`:
+/// ```url
+/// /v2/heal/0x4f5b812789fc606be1b3b16908db13fc7a9adf7ca72641f84d75b47069d3d7f0
+/// ```
+#[utoipa::path(
+    get,
+    tag = "Names",
+    path = "/v2/heal/{labelhash}",
+    responses(
+        (status = 200, description = "Label of the labelhash.", body = HealResponse),
+        (status = BAD_REQUEST, description = "Invalid labelhash.", body = ErrorResponse),
+        (status = NOT_FOUND, description = "The label is unknown, or no rainbow table is configured.", body = ErrorResponse),
+    ),
+    params(
+        ("labelhash" = String, Path, description = "Labelhash to heal, as hex or as an encoded label (`[<labelhash>]`)."),
+    )
+)]
+pub async fn get(
+    Path(labelhash): Path<String>,
+    network: Network,
+) -> Result<Json<HealResponse>, RouteError> {
+    if network.healer.is_none() {
+        return Err(http_error(
+            StatusCode::NOT_FOUND,
+            "Label healing is not configured",
+        ));
+    }
+
+    let labelhash = match decode_label(&labelhash) {
+        Some(labelhash) => labelhash,
+        None => labelhash
+            .parse::<H256>()
+            .map_err(|_| http_error(StatusCode::BAD_REQUEST, "Invalid labelhash"))?,
+    };
+
+    let label = network
+        .heal_label(&labelhash)
+        .ok_or_else(|| http_error(StatusCode::NOT_FOUND, "Unknown labelhash"))?;
+
+    Ok(Json(HealResponse { labelhash, label }))
+}
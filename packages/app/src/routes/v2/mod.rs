@@ -4,7 +4,7 @@ use std::time::Duration;
 
 use axum::response::sse::Event;
 use axum::response::{IntoResponse, Sse};
-use axum::{routing::get, Router};
+use axum::{middleware, routing::get, Router};
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
@@ -25,10 +25,27 @@ use crate::routes::{
     RouteError,
 };
 
+pub mod debug;
+pub mod heal;
+pub mod name;
+pub mod popular;
+pub mod resolver;
 
 pub fn setup_v2_router(state: Arc<crate::AppState>) -> Router<Arc<crate::AppState>> {
     Router::new()
-        .route("/discover/search", get(discovery_search)).with_state(state)
+        .route("/discover/search", get(discovery_search))
+        .route("/popular", get(popular::get))
+        .route("/resolver/:name", get(resolver::get))
+        .route("/name/:name/info", get(name::get_info))
+        .route("/heal/:labelhash", get(heal::get))
+        .route(
+            "/debug/:name",
+            get(debug::get).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                crate::routes::admin::require_token,
+            )),
+        )
+        .with_state(state)
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,81 @@
+use axum::extract::{Path, Query};
+use axum::Json;
+use enstate_shared::core::error::ProfileError;
+use enstate_shared::core::resolver::ResolverInfo;
+use enstate_shared::utils::name::NameInfo;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::routes::{profile_http_error_mapper, Network, RouteError};
+
+#[derive(Deserialize, IntoParams)]
+pub struct NameInfoQuery {
+    /// Also look up the resolver of the name, see `/v2/resolver/{name}`
+    #[serde(default)]
+    resolve: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NameInfoResponse {
+    #[serde(flatten)]
+    info: NameInfo,
+    /// The name with its encoded labels healed from the rainbow table, when any could be
+    #[serde(skip_serializing_if = "Option::is_none")]
+    healed: Option<String>,
+    /// Only with `resolve=true`, unset when neither the name nor any of its parents has a resolver
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolver: Option<ResolverInfo>,
+}
+
+/// /v2/name/{name}/info
+///
+/// Here is an example of a valid request that looks up the namehash, labelhashes and DNS encoding of a name:
+/// ```url
+/// /v2/name/luc.eth/info
+/// ```
+#[utoipa::path(
+    get,
+    tag = "Names",
+    path = "/v2/name/{name}/info",
+    responses(
+        (status = 200, description = "Normalized name, its hashes and encodings, and why it's invalid if it is.", body = NameInfoResponse),
+        (status = BAD_REQUEST, description = "Invalid name with `resolve=true`.", body = ErrorResponse),
+    ),
+    params(
+        ("name" = String, Path, description = "Name to describe."),
+        NameInfoQuery,
+    )
+)]
+pub async fn get_info(
+    Path(name): Path<String>,
+    Query(query): Query<NameInfoQuery>,
+    network: Network,
+) -> Result<Json<NameInfoResponse>, RouteError> {
+    let info = NameInfo::of(&name);
+
+    let healed = Some(network.heal_name(&info.name)).filter(|healed| *healed != info.name);
+
+    if !query.resolve {
+        return Ok(Json(NameInfoResponse {
+            info,
+            healed,
+            resolver: None,
+        }));
+    }
+
+    if !info.valid {
+        return Err(profile_http_error_mapper(ProfileError::InvalidName).into());
+    }
+
+    let resolver = match network.resolver_info(info.name.clone()).await {
+        Ok(resolver) => Some(resolver),
+        Err(ProfileError::NotFound) => None,
+        Err(err) => return Err(profile_http_error_mapper(err).into()),
+    };
+
+    Ok(Json(NameInfoResponse {
+        info,
+        healed,
+        resolver,
+    }))
+}
@@ -0,0 +1,98 @@
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::Json;
+use enstate_shared::popularity::{PopularityError, PopularityKind};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::routes::{http_error, Network, RouteError};
+
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+pub struct PopularQuery {
+    /// Decay window, one of the configured `popularity.windows` (`hour`, `day` and `week` by default)
+    #[serde(default = "default_window")]
+    window: String,
+    /// Number of names to return, at most 100
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_window() -> String {
+    "day".to_string()
+}
+
+const fn default_limit() -> usize {
+    10
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PopularResponse {
+    window: String,
+    names: Vec<PopularName>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PopularName {
+    name: String,
+    /// Number of lookups, with older lookups counting less the further they're out of the window
+    score: f64,
+}
+
+/// /v2/popular
+///
+/// Here is an example of a valid request that looks up the most popular names of the last day:
+/// ```url
+/// /v2/popular?window=day&limit=10
+/// ```
+#[utoipa::path(
+    get,
+    tag = "Popularity",
+    path = "/v2/popular",
+    responses(
+        (status = 200, description = "Most looked up names, most popular first.", body = PopularResponse),
+        (status = BAD_REQUEST, description = "Unknown window or invalid limit.", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Popularity tracking is disabled, or the network isn't the default one.", body = ErrorResponse),
+    ),
+    params(PopularQuery)
+)]
+pub async fn get(
+    Query(query): Query<PopularQuery>,
+    network: Network,
+) -> Result<Json<PopularResponse>, RouteError> {
+    let Some(popularity) = &network.popularity else {
+        return Err(http_error(
+            StatusCode::NOT_FOUND,
+            "Popularity tracking is disabled",
+        ));
+    };
+
+    if query.limit == 0 || query.limit > MAX_LIMIT {
+        return Err(http_error(
+            StatusCode::BAD_REQUEST,
+            &format!("limit should be between 1 and {MAX_LIMIT}"),
+        ));
+    }
+
+    let entries = popularity
+        .top(PopularityKind::Name, &query.window, query.limit)
+        .await
+        .map_err(|err| match err {
+            PopularityError::UnknownWindow(_) => {
+                http_error(StatusCode::BAD_REQUEST, &err.to_string())
+            }
+            PopularityError::Other(_) => http_error(StatusCode::BAD_GATEWAY, &err.to_string()),
+        })?;
+
+    Ok(Json(PopularResponse {
+        window: query.window,
+        names: entries
+            .into_iter()
+            .map(|entry| PopularName {
+                name: entry.key,
+                score: entry.score,
+            })
+            .collect(),
+    }))
+}
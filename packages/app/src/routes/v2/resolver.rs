@@ -0,0 +1,41 @@
+use axum::extract::Path;
+use axum::Json;
+use enstate_shared::core::error::ProfileError;
+use enstate_shared::core::lookup_data::LookupInfo;
+use enstate_shared::core::resolver::ResolverInfo;
+
+use crate::routes::{profile_http_error_mapper, Network, RouteError};
+
+/// /v2/resolver/{name}
+///
+/// Here is an example of a valid request that looks up the resolver of a name:
+/// ```url
+/// /v2/resolver/luc.eth
+/// ```
+#[utoipa::path(
+    get,
+    tag = "Resolver",
+    path = "/v2/resolver/{name}",
+    responses(
+        (status = 200, description = "Resolver of the name and the interfaces it supports.", body = ResolverInfo),
+        (status = BAD_REQUEST, description = "Invalid name.", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Neither the name nor any of its parents has a resolver.", body = ErrorResponse),
+    ),
+    params(
+        ("name" = String, Path, description = "Name to look up the resolver of."),
+    )
+)]
+pub async fn get(
+    Path(name): Path<String>,
+    network: Network,
+) -> Result<Json<ResolverInfo>, RouteError> {
+    let Ok(LookupInfo::Name(name)) = LookupInfo::guess(name) else {
+        return Err(profile_http_error_mapper(ProfileError::InvalidName).into());
+    };
+
+    network
+        .resolver_info(name)
+        .await
+        .map(Json)
+        .map_err(|err| profile_http_error_mapper(err).into())
+}
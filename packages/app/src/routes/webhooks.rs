@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use enstate_shared::core::lookup_data::LookupInfo;
+use enstate_shared::utils::eip55::EIP55Address;
+use enstate_shared::utils::vec::dedup_ord;
+use ethers_core::types::Address;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::routes::{http_error, RouteError};
+use crate::webhooks::{DeliveryState, Subscription, WebhookError, Webhooks};
+
+pub fn setup_webhooks_router() -> Router<Arc<crate::AppState>> {
+    Router::new()
+        .route("/webhooks", get(list).post(create))
+        .route("/webhooks/:id", get(get_one).delete(delete_one))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateWebhookRequest {
+    url: Url,
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    addresses: Vec<String>,
+    records: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookResponse {
+    id: String,
+    url: Url,
+    names: Vec<String>,
+    addresses: Vec<EIP55Address>,
+    records: Option<Vec<String>>,
+    created_at: i64,
+    /// Only returned once, when the subscription is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delivery: Option<DeliveryState>,
+}
+
+impl From<Subscription> for WebhookResponse {
+    fn from(subscription: Subscription) -> Self {
+        Self {
+            id: subscription.id,
+            url: subscription.url,
+            names: subscription.names,
+            addresses: subscription
+                .addresses
+                .into_iter()
+                .map(EIP55Address)
+                .collect(),
+            records: subscription.records,
+            created_at: subscription.created_at,
+            secret: None,
+            delivery: None,
+        }
+    }
+}
+
+/// GET /admin/webhooks
+pub async fn list(
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<Json<Vec<WebhookResponse>>, RouteError> {
+    let webhooks = webhooks(&state)?;
+
+    webhooks
+        .refresh(&state.watchlist)
+        .await
+        .map_err(webhook_error)?;
+
+    Ok(Json(
+        webhooks
+            .subscriptions()
+            .iter()
+            .cloned()
+            .map(WebhookResponse::from)
+            .collect(),
+    ))
+}
+
+/// POST /admin/webhooks
+///
+/// The response contains the signing secret, it can't be retrieved afterwards.
+pub async fn create(
+    State(state): State<Arc<crate::AppState>>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>), RouteError> {
+    let webhooks = webhooks(&state)?;
+
+    if !matches!(request.url.scheme(), "http" | "https") {
+        return Err(http_error(
+            StatusCode::BAD_REQUEST,
+            "url should be a http(s) url",
+        ));
+    }
+
+    if request.names.is_empty() && request.addresses.is_empty() {
+        return Err(http_error(
+            StatusCode::BAD_REQUEST,
+            "At least one name or address should be provided",
+        ));
+    }
+
+    let names = request
+        .names
+        .iter()
+        .map(|name| match LookupInfo::guess(name) {
+            Ok(LookupInfo::Name(name)) => Ok(name),
+            _ => Err(http_error(
+                StatusCode::BAD_REQUEST,
+                &format!("Invalid name: {name}"),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let addresses = request
+        .addresses
+        .iter()
+        .map(|address| {
+            address.parse::<Address>().map_err(|_| {
+                http_error(
+                    StatusCode::BAD_REQUEST,
+                    &format!("Invalid address: {address}"),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let subscription = Subscription::new(
+        request.url,
+        dedup_ord(&names),
+        dedup_ord(&addresses),
+        request.records,
+    );
+
+    let subscription = webhooks
+        .create(&state.watchlist, subscription)
+        .await
+        .map_err(webhook_error)?;
+
+    let secret = subscription.secret.clone();
+
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookResponse {
+            secret: Some(secret),
+            ..subscription.into()
+        }),
+    ))
+}
+
+/// GET /admin/webhooks/{id}
+pub async fn get_one(
+    Path(id): Path<String>,
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<Json<WebhookResponse>, RouteError> {
+    let webhooks = webhooks(&state)?;
+
+    webhooks
+        .refresh(&state.watchlist)
+        .await
+        .map_err(webhook_error)?;
+
+    let subscription = webhooks
+        .subscriptions()
+        .iter()
+        .find(|subscription| subscription.id == id)
+        .cloned()
+        .ok_or_else(|| http_error(StatusCode::NOT_FOUND, "Webhook not found"))?;
+
+    let delivery = webhooks.delivery_state(&id).await.map_err(webhook_error)?;
+
+    Ok(Json(WebhookResponse {
+        delivery: Some(delivery),
+        ..subscription.into()
+    }))
+}
+
+/// DELETE /admin/webhooks/{id}
+pub async fn delete_one(
+    Path(id): Path<String>,
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<StatusCode, RouteError> {
+    let deleted = webhooks(&state)?
+        .delete(&state.watchlist, &id)
+        .await
+        .map_err(webhook_error)?;
+
+    if !deleted {
+        return Err(http_error(StatusCode::NOT_FOUND, "Webhook not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn webhooks(state: &crate::AppState) -> Result<&Webhooks, RouteError> {
+    state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| http_error(StatusCode::NOT_FOUND, "Webhooks are disabled"))
+}
+
+fn webhook_error(err: WebhookError) -> RouteError {
+    let status = match err {
+        WebhookError::TooManySubscriptions(_) => StatusCode::CONFLICT,
+        WebhookError::Store(_) => StatusCode::BAD_GATEWAY,
+        WebhookError::Corrupt(_) | WebhookError::Unusable(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    http_error(status, &err.to_string())
+}
@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Response;
+use enstate_shared::core::lookup_data::LookupInfo;
+use enstate_shared::core::Profile;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::changes::{address_key, name_key, ChangeDetector, FieldChange, ProfileChange};
+use crate::models::bulk::BulkResponse;
+use crate::models::error::ErrorCode;
+use crate::routes::{http_coded_error, profile_http_error_mapper, Network, RouteError};
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    /// Names or addresses to receive the current profile and future updates for
+    Subscribe {
+        queries: Vec<String>,
+    },
+    Unsubscribe {
+        queries: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage<'a> {
+    /// Current profile of a query, sent once after subscribing
+    Profile {
+        query: String,
+        response: BulkResponse<Profile>,
+    },
+    Update {
+        query: &'a str,
+        changes: &'a BTreeMap<String, FieldChange>,
+        profile: &'a Profile,
+    },
+    Subscribed {
+        queries: Vec<&'a str>,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// /ws
+///
+/// Subscribe to names and addresses with `{"type": "subscribe", "queries": ["luc.eth"]}`.
+/// The current profile of every new query is sent right away, followed by an `update`
+/// message whenever enstate notices a change to it: as soon as the log follower sees
+/// the name's records change, or without the follower within `websocket.refresh_interval`
+/// seconds. Changes are only detected on the default network.
+pub async fn get(
+    ws: WebSocketUpgrade,
+    network: Network,
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<Response, RouteError> {
+    if !network.is_default(&state) {
+        return Err(http_coded_error(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidInput,
+            "Live updates are only available on the default network",
+        ));
+    }
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        state.metrics.websocket_connections.inc();
+
+        Connection::new(state.clone()).run(socket).await;
+
+        state.metrics.websocket_connections.dec();
+    }))
+}
+
+/// Queries a connection is subscribed to, up to `max` of them.
+struct Subscriptions {
+    max: usize,
+    /// Query -> watchlist key
+    keys: HashMap<String, String>,
+}
+
+impl Subscriptions {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Adds the queries not subscribed to yet until the limit is reached, returns
+    /// their lookups along with errors for the ones that were rejected
+    fn subscribe(&mut self, queries: Vec<String>) -> (Vec<(String, LookupInfo)>, Vec<String>) {
+        let mut added = Vec::new();
+        let mut errors = Vec::new();
+
+        for query in queries {
+            if self.keys.contains_key(&query) {
+                continue;
+            }
+
+            if self.keys.len() >= self.max {
+                errors.push(format!("Subscription limit of {} reached", self.max));
+
+                break;
+            }
+
+            let lookup = match LookupInfo::guess(&query) {
+                Ok(lookup) => lookup,
+                Err(err) => {
+                    errors.push(format!("{query}: {err}"));
+
+                    continue;
+                }
+            };
+
+            let key = match &lookup {
+                LookupInfo::Name(name) => name_key(name),
+                LookupInfo::Address(address) => address_key(address),
+            };
+
+            self.keys.insert(query.clone(), key);
+            added.push((query, lookup));
+        }
+
+        (added, errors)
+    }
+
+    fn unsubscribe(&mut self, queries: &[String]) {
+        for query in queries {
+            self.keys.remove(query);
+        }
+    }
+}
+
+struct Connection {
+    state: Arc<crate::AppState>,
+    /// Watchlist owner of this connection
+    owner: String,
+    subscriptions: Subscriptions,
+}
+
+impl Connection {
+    fn new(state: Arc<crate::AppState>) -> Self {
+        Self {
+            owner: format!("ws:{}", NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed)),
+            subscriptions: Subscriptions::new(state.websocket.max_subscriptions),
+            state,
+        }
+    }
+
+    async fn run(mut self, mut socket: WebSocket) {
+        let mut changes = self.state.changes.subscribe();
+
+        loop {
+            let messages = tokio::select! {
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => self.handle(&text).await,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+                change = changes.recv() => match change {
+                    Ok(change) => self.updates(&change),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("websocket connection lagging behind, skipped {skipped} changes");
+
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+
+            if Self::send(&mut socket, messages).await.is_err() {
+                break;
+            }
+        }
+
+        self.state.watchlist.remove(&self.owner);
+    }
+
+    async fn send(socket: &mut WebSocket, messages: Vec<String>) -> Result<(), axum::Error> {
+        for message in messages {
+            socket.send(Message::Text(message)).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, text: &str) -> Vec<String> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(err) => {
+                return vec![encode(&ServerMessage::Error {
+                    error: format!("Invalid message: {err}"),
+                })]
+            }
+        };
+
+        match message {
+            ClientMessage::Subscribe { queries } => self.subscribe(queries).await,
+            ClientMessage::Unsubscribe { queries } => {
+                self.subscriptions.unsubscribe(&queries);
+
+                self.sync_watchlist();
+
+                Vec::new()
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, queries: Vec<String>) -> Vec<String> {
+        let (added, errors) = self.subscriptions.subscribe(queries);
+
+        let mut messages = errors
+            .into_iter()
+            .map(|error| encode(&ServerMessage::Error { error }))
+            .collect::<Vec<_>>();
+
+        // watch before resolving, so changes found by this very resolution aren't missed
+        self.sync_watchlist();
+
+        if !added.is_empty() {
+            messages.push(encode(&ServerMessage::Subscribed {
+                queries: added.iter().map(|(query, _)| query.as_str()).collect(),
+            }));
+        }
+
+        let service = &self.state.service;
+
+        let (queries, lookups): (Vec<_>, Vec<_>) = added.into_iter().unzip();
+
+        let profiles = join_all(
+            lookups
+                .into_iter()
+                .map(|lookup| service.resolve_profile(lookup, false)),
+        )
+        .await;
+
+        for (query, profile) in queries.into_iter().zip(profiles) {
+            if let Ok(profile) = &profile {
+                ChangeDetector::baseline(&self.state, profile).await;
+            }
+
+            messages.push(encode(&ServerMessage::Profile {
+                query,
+                response: profile.map_err(profile_http_error_mapper).into(),
+            }));
+        }
+
+        messages
+    }
+
+    fn updates(&self, change: &ProfileChange) -> Vec<String> {
+        let keys = [
+            Some(name_key(&change.name)),
+            change
+                .address
+                .as_ref()
+                .map(|address| address_key(&address.0)),
+        ];
+
+        self.subscriptions
+            .keys
+            .iter()
+            .filter(|(_, key)| keys.iter().flatten().any(|it| it == *key))
+            .map(|(query, _)| {
+                encode(&ServerMessage::Update {
+                    query,
+                    changes: &change.changes,
+                    profile: &change.profile,
+                })
+            })
+            .collect()
+    }
+
+    fn sync_watchlist(&self) {
+        self.state.watchlist.set(
+            &self.owner,
+            self.subscriptions
+                .keys
+                .values()
+                .cloned()
+                .collect::<HashSet<_>>(),
+        );
+    }
+}
+
+fn encode(message: &ServerMessage) -> String {
+    serde_json::to_string(message).expect("server messages should serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientMessage, Subscriptions};
+
+    /// Applies a client message, returns the newly subscribed queries and the errors
+    fn handle(subscriptions: &mut Subscriptions, text: &str) -> (Vec<String>, Vec<String>) {
+        match serde_json::from_str::<ClientMessage>(text).unwrap() {
+            ClientMessage::Subscribe { queries } => {
+                let (added, errors) = subscriptions.subscribe(queries);
+
+                (added.into_iter().map(|(query, _)| query).collect(), errors)
+            }
+            ClientMessage::Unsubscribe { queries } => {
+                subscriptions.unsubscribe(&queries);
+
+                (Vec::new(), Vec::new())
+            }
+        }
+    }
+
+    #[test]
+    fn test_subscription_limit() {
+        let mut subscriptions = Subscriptions::new(2);
+
+        let (added, errors) = handle(
+            &mut subscriptions,
+            r#"{"type": "subscribe", "queries": ["luc.eth", "nick.eth", "v3x.eth"]}"#,
+        );
+
+        assert_eq!(added, ["luc.eth", "nick.eth"]);
+        assert_eq!(errors, ["Subscription limit of 2 reached"]);
+
+        // queries already subscribed to don't count against the limit again
+        let (added, errors) = handle(
+            &mut subscriptions,
+            r#"{"type": "subscribe", "queries": ["luc.eth"]}"#,
+        );
+
+        assert!(added.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_unsubscribe_frees_slot() {
+        let mut subscriptions = Subscriptions::new(1);
+
+        handle(
+            &mut subscriptions,
+            r#"{"type": "subscribe", "queries": ["luc.eth"]}"#,
+        );
+
+        let (_, errors) = handle(
+            &mut subscriptions,
+            r#"{"type": "subscribe", "queries": ["nick.eth"]}"#,
+        );
+        assert_eq!(errors.len(), 1);
+
+        handle(
+            &mut subscriptions,
+            r#"{"type": "unsubscribe", "queries": ["luc.eth"]}"#,
+        );
+
+        let (added, errors) = handle(
+            &mut subscriptions,
+            r#"{"type": "subscribe", "queries": ["nick.eth"]}"#,
+        );
+        assert_eq!(added, ["nick.eth"]);
+        assert!(errors.is_empty());
+        assert_eq!(subscriptions.keys["nick.eth"], "n:nick.eth");
+    }
+
+    #[test]
+    fn test_invalid_query_takes_no_slot() {
+        let mut subscriptions = Subscriptions::new(1);
+
+        let (added, errors) = handle(
+            &mut subscriptions,
+            r#"{"type": "subscribe", "queries": ["not a name", "luc.eth"]}"#,
+        );
+
+        assert_eq!(added, ["luc.eth"]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("not a name: "));
+    }
+}
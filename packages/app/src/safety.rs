@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use enstate_shared::popularity::PopularityKind;
+use enstate_shared::safety::{skeleton, ProtectedNames};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::SafetyConfig;
+use crate::state::AppState;
+
+/// The most popular names by skeleton, for safety reports to flag their lookalikes.
+///
+/// Shared between the service and the task refreshing it from the popularity tracker.
+#[derive(Clone, Default)]
+pub struct PopularNames(Arc<RwLock<HashMap<String, String>>>);
+
+impl PopularNames {
+    pub fn spawn(
+        state: Arc<AppState>,
+        config: SafetyConfig,
+        shutdown: CancellationToken,
+    ) -> Option<JoinHandle<()>> {
+        let names = state.popular_names.clone()?;
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(config.refresh_interval));
+
+            loop {
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    _ = interval.tick() => names.refresh(&state, &config).await,
+                }
+            }
+
+            info!("Popular names refresher shutdown");
+        }))
+    }
+
+    async fn refresh(&self, state: &AppState, config: &SafetyConfig) {
+        let Some(popularity) = state.service.popularity.as_ref() else {
+            return;
+        };
+
+        let top = match popularity
+            .top(PopularityKind::Name, &config.window, config.top_n)
+            .await
+        {
+            Ok(top) => top,
+            Err(err) => {
+                warn!("failed to get popular names: {err}");
+
+                return;
+            }
+        };
+
+        let mut names = HashMap::with_capacity(top.len());
+
+        // most popular first, a lookalike that got popular shouldn't protect itself
+        for entry in top {
+            names.entry(skeleton(&entry.key)).or_insert(entry.key);
+        }
+
+        *self
+            .0
+            .write()
+            .expect("popular names lock should not be poisoned") = names;
+    }
+}
+
+impl ProtectedNames for PopularNames {
+    fn with_skeleton(&self, skeleton: &str) -> Option<String> {
+        self.0
+            .read()
+            .expect("popular names lock should not be poisoned")
+            .get(skeleton)
+            .cloned()
+    }
+}
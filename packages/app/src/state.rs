@@ -1,121 +1,408 @@
-use std::env;
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use enstate_shared::cache::{CacheLayer, PassthroughCacheLayer};
-use enstate_shared::core::ENSService;
+use enstate_shared::batch::CallBatcher;
+use enstate_shared::cache::CacheLayer;
+use enstate_shared::core::routing::{Backend, Routing};
+use enstate_shared::core::{ENSService, Profile, Settings};
 use enstate_shared::discovery::Discovery;
-use enstate_shared::models::{
-    multicoin::cointype::{coins::CoinType, Coins},
-    records::Records,
-};
+use enstate_shared::healer::LabelHealer;
+use enstate_shared::limiter::Limiter;
+use enstate_shared::models::multicoin::cointype::coins::CoinType;
+use enstate_shared::observer::Observer;
+use enstate_shared::safety::ProtectedNames;
 use ethers_core::types::H160;
-use tracing::{info, warn};
-use url::Url;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
+use crate::cache::{self, Namespaced};
+use crate::changes::{ProfileChange, ResolvedFeed, Watchlist};
+use crate::config::{
+    ConcurrencyConfig, Config, Resolution, RpcConfig, WarmerConfig, WebSocketConfig,
+};
 use crate::discovery::engine::DiscoveryEngine;
 use crate::http::RateLimiter;
+use crate::limiter::RpcLimiter;
+use crate::multicall::Multicall3;
+use crate::popularity;
 use crate::provider::RoundRobin;
+use crate::rainbow::RainbowTable;
+use crate::safety::PopularNames;
 use crate::telemetry::metrics::Metrics;
-use crate::{cache, database};
+use crate::webhooks::Webhooks;
 
 #[allow(clippy::module_name_repetitions)]
 pub struct AppState {
-    pub service: ENSService,
+    /// Service of the default network
+    pub service: Arc<ENSService>,
+    /// Name of the default network
+    pub network: String,
+    /// Services of every network by name, the default one included
+    pub networks: BTreeMap<String, Arc<ENSService>>,
     pub metrics: Metrics,
     pub rate_limiter: RateLimiter,
+    pub admin_token: Option<String>,
+    pub watchlist: Watchlist,
+    /// Every profile resolved from chain
+    pub resolved: broadcast::Sender<Profile>,
+    /// Differences between consecutive resolutions of watched profiles
+    pub changes: broadcast::Sender<Arc<ProfileChange>>,
+    pub webhooks: Option<Webhooks>,
+    pub websocket: WebSocketConfig,
+    pub concurrency: ConcurrencyConfig,
+    /// Protected names of the safety report, refreshed from the popularity tracker
+    pub popular_names: Option<PopularNames>,
+    /// Reloadable, read by the cache warmer on every run
+    warmer: RwLock<Arc<WarmerConfig>>,
 }
 
 impl AppState {
-    pub async fn new() -> Self {
-        let profile_records = env::var("PROFILE_RECORDS").map_or_else(
-            |_| Records::default().records,
-            |s| s.split(',').map(ToString::to_string).collect(),
-        );
+    pub async fn new(config: &Config, shutdown: &CancellationToken) -> Self {
+        let metrics = Metrics::new();
 
-        let multicoin_chains: Vec<CoinType> = env::var("MULTICOIN_CHAINS").map_or_else(
-            |_| Coins::default().coins,
-            |s| {
-                let numbers = s
-                    .split(',')
-                    .filter_map(|it| it.parse::<u64>().ok())
-                    .collect::<Vec<_>>();
+        // networks share the backend, each in its own namespace
+        let cache: Arc<dyn CacheLayer> =
+            Arc::from(cache::setup(&config.cache, &metrics, shutdown).await);
 
-                numbers.iter().map(|num| CoinType::from(*num)).collect()
-            },
-        );
+        let popularity =
+            popularity::setup(&config.popularity, &config.cache, &metrics, shutdown).await;
 
-        let raw_rpc_urls: String = env::var("RPC_URL").expect("RPC_URL should've been set");
-        let rpc_urls = raw_rpc_urls
-            .split(',')
-            .map(ToString::to_string)
-            .collect::<Vec<_>>();
+        let (resolved, _) = broadcast::channel(1024);
+        let (changes, _) = broadcast::channel(1024);
 
-        info!("Connecting to Redis...");
+        let discovery = config.discovery.meilisearch_url.as_ref().map(|url| {
+            let engine =
+                DiscoveryEngine::new(url.as_str(), config.discovery.meilisearch_key.as_deref());
 
-        let cache = database::setup().await.map_or_else(
-            |_| {
-                warn!("failed to connect to redis, using no cache");
+            Box::new(engine) as Box<dyn Discovery>
+        });
 
-                Box::new(PassthroughCacheLayer {}) as Box<dyn CacheLayer>
-            },
-            |redis| {
-                info!("Connected to Redis");
+        let healer = config.healing.rainbow_table.as_deref().and_then(|path| {
+            match RainbowTable::open(path) {
+                Ok(table) => {
+                    info!(
+                        "Healing labels from {} ({} labels)",
+                        path.display(),
+                        table.label_count()
+                    );
 
-                Box::new(cache::Redis::new(redis)) as Box<dyn CacheLayer>
-            },
-        );
+                    Some(Arc::new(table))
+                }
+                Err(err) => {
+                    warn!(
+                        "failed to load rainbow table {}: {err}, not healing labels",
+                        path.display()
+                    );
 
-        let provider = RoundRobin::new(rpc_urls);
+                    None
+                }
+            }
+        });
 
-        let opensea_api_key =
-            env::var("OPENSEA_API_KEY").expect("OPENSEA_API_KEY should've been set");
+        let popular_names =
+            (config.safety.enabled && config.safety.top_n > 0 && popularity.is_some())
+                .then(PopularNames::default);
 
-        let ipfs_gateway = env::var("IPFS_GATEWAY").map_or_else(
-            |_| "https://ipfs.io/ipfs/".to_string(),
-            |ipfs_gateway| Url::parse(&ipfs_gateway).unwrap().to_string(),
-        );
+        // a single limiter bounds the lookups of every network, the providers of each network
+        // are bounded on their own, also when names are routed to them
+        let limiter = config
+            .concurrency
+            .enabled
+            .then(|| RpcLimiter::new(&config.concurrency, metrics.clone()));
+
+        let providers: BTreeMap<&str, RoundRobin> = config
+            .networks
+            .iter()
+            .chain([(&config.network, &config.rpc)])
+            .map(|(name, rpc)| (name.as_str(), provider(config, rpc)))
+            .collect();
+
+        let network =
+            |name: &str, rpc: &RpcConfig, namespace: String, observer: Box<dyn Observer>| {
+                let cache = Box::new(Namespaced::new(cache.clone(), namespace));
+                let mut service = build_service(
+                    config,
+                    rpc,
+                    providers[name].clone(),
+                    cache,
+                    &metrics,
+                    observer,
+                );
+
+                service.limiter = limiter
+                    .clone()
+                    .map(|limiter| Box::new(limiter) as Box<dyn Limiter>);
+
+                service.healer = healer
+                    .clone()
+                    .map(|table| Box::new(table) as Box<dyn LabelHealer>);
+                service.protected_names = popular_names
+                    .clone()
+                    .map(|names| Box::new(names) as Box<dyn ProtectedNames>);
+
+                service
+            };
+
+        // lookups on other networks are counted, but aren't fed to the change detector,
+        // discovery or popularity, which only know names of the default network
+        let mut networks: BTreeMap<String, Arc<ENSService>> = config
+            .networks
+            .iter()
+            .map(|(name, rpc)| {
+                let service = network(name, rpc, format!("{name}:"), Box::new(metrics.clone()));
 
-        let arweave_gateway = env::var("AR_GATEWAY").map_or_else(
-            |_| "https://arweave.net/".to_string(),
-            |arweave_gateway| Url::parse(&arweave_gateway).unwrap().to_string(),
+                (name.clone(), Arc::new(service))
+            })
+            .collect();
+
+        let mut service = network(
+            &config.network,
+            &config.rpc,
+            String::new(),
+            Box::new(vec![
+                Box::new(metrics.clone()) as Box<dyn Observer>,
+                Box::new(ResolvedFeed(resolved.clone())),
+            ]),
         );
 
-        let universal_resolver = env::var("UNIVERSAL_RESOLVER")
-            .expect("UNIVERSAL_RESOLVER should've been set")
-            .parse::<H160>()
-            .expect("UNIVERSAL_RESOLVER should be a valid address");
+        service.discovery = discovery;
+        service.popularity = popularity;
+        service.cache_node_index = config.follower.enabled;
+        service.routing = routing(config, &providers);
 
-        let max_bulk_size =
-            env::var("MAX_BULK_SIZE").map_or(10, |bulk_size| bulk_size.parse().unwrap());
+        let service = Arc::new(service);
 
-        let cache_ttl =
-            env::var("PROFILE_CACHE_TTL").map_or(Some(600), |cache_ttl| cache_ttl.parse().ok());
+        networks.insert(config.network.clone(), service.clone());
 
-        let meilisearch_url = env::var("MEILI_ENDPOINT").ok();
-        let meilisearch_key = env::var("MEILI_KEY").ok();
+        let webhooks = if config.webhooks.enabled {
+            match Webhooks::open(config.webhooks.clone(), config.cache.redis_url.as_deref()).await {
+                Ok(webhooks) => Some(webhooks),
+                Err(err) => {
+                    error!("webhooks disabled: {err}");
 
-        let discovery = meilisearch_url.map(|url| {
-            let engine = DiscoveryEngine::new(&url, meilisearch_key.as_deref());
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
-            Box::new(engine) as Box<dyn Discovery>
-        });
+        info!(
+            "Serving networks: {} (default: {})",
+            networks.keys().cloned().collect::<Vec<_>>().join(", "),
+            config.network
+        );
 
         Self {
-            rate_limiter: RateLimiter::new(),
-            service: ENSService {
-                discovery,
-                cache,
-                rpc: Box::new(provider),
-                opensea_api_key,
-                ipfs_gateway,
-                arweave_gateway,
-                max_bulk_size,
-                cache_ttl,
-                profile_records: Arc::from(profile_records),
-                profile_chains: Arc::from(multicoin_chains),
-                universal_resolver,
-            },
-            metrics: Metrics::new(),
+            rate_limiter: RateLimiter::new(&config.rate_limit),
+            admin_token: config.admin.token.clone(),
+            watchlist: Watchlist::default(),
+            websocket: config.websocket.clone(),
+            concurrency: config.concurrency.clone(),
+            webhooks,
+            service,
+            network: config.network.clone(),
+            networks,
+            metrics,
+            resolved,
+            changes,
+            popular_names,
+            warmer: RwLock::new(Arc::new(config.warmer.clone())),
+        }
+    }
+
+    /// Applies the hot-reloadable subset of the configuration: rate limits, the settings of
+    /// every network (see [`Settings`]) and the warmer's selection of names.
+    ///
+    /// Everything else, e.g. backends, providers, contracts and which tasks run, takes a restart.
+    pub fn reload(&self, config: &Config) {
+        self.rate_limiter.reload(&config.rate_limit);
+
+        for (name, service) in &self.networks {
+            let rpc = if *name == config.network {
+                Some(&config.rpc)
+            } else {
+                config.networks.get(name)
+            };
+
+            match rpc {
+                Some(rpc) => service.reload(settings(config, rpc)),
+                None => warn!(
+                    network = %name,
+                    "network is no longer configured, keeping its settings"
+                ),
+            }
         }
+
+        *self
+            .warmer
+            .write()
+            .expect("warmer config lock should not be poisoned") = Arc::new(config.warmer.clone());
+
+        info!("Configuration reloaded");
+    }
+
+    /// Warmer configuration of the current run, `enabled` and `interval` apply at startup only
+    pub fn warmer(&self) -> Arc<WarmerConfig> {
+        self.warmer
+            .read()
+            .expect("warmer config lock should not be poisoned")
+            .clone()
+    }
+}
+
+/// Service of a single network, without the plugins that are shared between networks
+fn build_service(
+    config: &Config,
+    rpc: &RpcConfig,
+    provider: RoundRobin,
+    cache: Box<dyn CacheLayer>,
+    metrics: &Metrics,
+    observer: Box<dyn Observer>,
+) -> ENSService {
+    let universal_resolver = universal_resolver(rpc);
+
+    // only universal resolver calls are batched
+    let batcher = (config.multicall.enabled && universal_resolver.is_some()).then(|| {
+        Box::new(Multicall3::spawn(
+            &config.multicall,
+            provider.clone(),
+            metrics.clone(),
+        )) as Box<dyn CallBatcher>
+    });
+
+    ENSService {
+        batcher,
+        limiter: None,
+        discovery: None,
+        healer: None,
+        observer: Some(observer),
+        popularity: None,
+        protected_names: None,
+        safety: config.safety.enabled,
+        cache,
+        rpc: Box::new(provider),
+        opensea_api_key: config.opensea.api_key.clone().unwrap_or_default(),
+        ipfs_gateway: config.gateways.ipfs.to_string(),
+        arweave_gateway: config.gateways.arweave.to_string(),
+        cache_compression: config.cache.compression,
+        cache_node_index: false,
+        universal_resolver,
+        registry: rpc.registry,
+        reverse_registrar: rpc.reverse_registrar,
+        routing: Routing::default(),
+        settings: RwLock::new(Arc::new(settings(config, rpc))),
+    }
+}
+
+/// Settings of a network's service, swapped in on reload
+fn settings(config: &Config, rpc: &RpcConfig) -> Settings {
+    Settings {
+        profile_records: Arc::from(config.profile.records.clone()),
+        profile_chains: config
+            .profile
+            .chains
+            .iter()
+            .map(|num| CoinType::from(*num))
+            .collect(),
+        lenient_records: config.profile.lenient_records,
+        lookup_timeout: rpc.lookup_timeout.map(Duration::from_millis),
+        call_timeout: rpc.call_timeout.map(Duration::from_millis),
+        max_bulk_size: config.max_bulk_size,
+        cache_ttl: Some(config.cache.profile_ttl),
+        partial_cache_ttl: config.cache.partial_ttl,
+    }
+}
+
+/// Providers of a network, bounded per provider when the concurrency limit is enabled
+fn provider(config: &Config, rpc: &RpcConfig) -> RoundRobin {
+    RoundRobin::new(
+        rpc.urls.iter().map(ToString::to_string).collect(),
+        config
+            .concurrency
+            .enabled
+            .then_some(config.concurrency.per_provider),
+    )
+}
+
+/// Backends of the default network's routed suffixes, sharing the providers of their network
+fn routing(config: &Config, providers: &BTreeMap<&str, RoundRobin>) -> Routing {
+    Routing::new(config.routes.iter().map(|(suffix, network)| {
+        let rpc = &config.networks[network];
+
+        let backend = Backend {
+            name: network.clone(),
+            rpc: Box::new(providers[network.as_str()].clone()),
+            universal_resolver: universal_resolver(rpc),
+            registry: rpc.registry,
+            reverse_registrar: rpc.reverse_registrar,
+            namespace: format!("route:{network}:"),
+        };
+
+        (suffix.clone(), backend)
+    }))
+}
+
+fn universal_resolver(rpc: &RpcConfig) -> Option<H160> {
+    match rpc.resolution {
+        Resolution::Universal => Some(
+            rpc.universal_resolver
+                .expect("universal_resolver should've been validated"),
+        ),
+        Resolution::Registry => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::AppState;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn test_reload() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [rpc]
+            urls = ["http://localhost:8545"]
+            universal_resolver = "0xce01f8eee7E479C928F8919abD53E553a36CeF67"
+
+            [networks.sepolia]
+            urls = ["http://localhost:8546"]
+            universal_resolver = "0xc8Af999e38273D658BE1b921b88A9Ddf005769cC"
+            "#,
+        )
+        .unwrap();
+
+        let state = AppState::new(&config, &CancellationToken::new()).await;
+
+        config.max_bulk_size = 50;
+        config.profile.records = vec!["com.github".to_string()];
+        config.cache.profile_ttl = 60;
+        config.rpc.call_timeout = Some(2000);
+        config.networks.get_mut("sepolia").unwrap().lookup_timeout = Some(1000);
+        config.warmer.rpc_budget = 5;
+
+        state.reload(&config);
+
+        let settings = state.service.settings();
+        assert_eq!(settings.max_bulk_size, 50);
+        assert_eq!(&*settings.profile_records, ["com.github".to_string()]);
+        assert_eq!(settings.cache_ttl, Some(60));
+        assert_eq!(settings.call_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(settings.lookup_timeout, None);
+
+        // every network takes its own rpc settings
+        let settings = state.networks["sepolia"].settings();
+        assert_eq!(settings.max_bulk_size, 50);
+        assert_eq!(settings.lookup_timeout, Some(Duration::from_secs(1)));
+        assert_eq!(settings.call_timeout, None);
+
+        assert_eq!(state.warmer().rpc_budget, 5);
     }
 }
@@ -1,7 +1,11 @@
 use std::sync::Arc;
 
 use axum::{extract::State, response::IntoResponse};
-use prometheus::{Counter, CounterVec, Encoder, Histogram, Registry, TextEncoder};
+use enstate_shared::cache::envelope::EnvelopeError;
+use enstate_shared::observer::Observer;
+use prometheus::{
+    Counter, CounterVec, Encoder, Histogram, IntCounter, IntGauge, Registry, TextEncoder,
+};
 
 #[derive(Clone)]
 pub struct Metrics {
@@ -11,6 +15,31 @@ pub struct Metrics {
     pub name_lookup_latency: Histogram,
 
     pub rate_limit_infringements: CounterVec,
+
+    pub cache_errors: CounterVec,
+    pub cache_circuit_open: IntGauge,
+    pub cache_decode_failures: CounterVec,
+    pub cache_migrations: IntCounter,
+
+    pub popularity_circuit_open: IntGauge,
+    pub popularity_dropped_hits: IntCounter,
+
+    pub warmer_profiles: CounterVec,
+
+    pub follower_invalidations: IntCounter,
+    pub follower_block: IntGauge,
+
+    pub webhook_deliveries: CounterVec,
+
+    pub websocket_connections: IntGauge,
+
+    pub multicall_batch_size: Histogram,
+    pub multicall_errors: IntCounter,
+
+    pub rpc_in_flight: IntGauge,
+    pub rpc_queue_depth: IntGauge,
+    pub rpc_queue_seconds: Histogram,
+    pub rpc_shed: IntCounter,
 }
 
 impl Metrics {
@@ -46,6 +75,151 @@ impl Metrics {
             .register(Box::new(rate_limit_infringements.clone()))
             .unwrap();
 
+        let cache_errors_opts =
+            prometheus::Opts::new("cache_errors", "Total number of failed cache operations");
+        let cache_errors = CounterVec::new(cache_errors_opts, &["operation"]).unwrap();
+        registry.register(Box::new(cache_errors.clone())).unwrap();
+
+        let cache_circuit_open = IntGauge::new(
+            "cache_circuit_open",
+            "Whether the cache is currently bypassed after repeated failures",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(cache_circuit_open.clone()))
+            .unwrap();
+
+        let cache_decode_failures_opts = prometheus::Opts::new(
+            "cache_decode_failures",
+            "Total number of cached entries that could not be decoded",
+        );
+        let cache_decode_failures =
+            CounterVec::new(cache_decode_failures_opts, &["reason"]).unwrap();
+        registry
+            .register(Box::new(cache_decode_failures.clone()))
+            .unwrap();
+
+        let cache_migrations = IntCounter::new(
+            "cache_migrations",
+            "Total number of legacy cached entries rewritten in the current encoding",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(cache_migrations.clone()))
+            .unwrap();
+
+        let popularity_circuit_open = IntGauge::new(
+            "popularity_circuit_open",
+            "Whether popularity tracking is currently paused after repeated Redis failures",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(popularity_circuit_open.clone()))
+            .unwrap();
+
+        let popularity_dropped_hits = IntCounter::new(
+            "popularity_dropped_hits",
+            "Total number of lookups not counted towards popularity because the queue was full",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(popularity_dropped_hits.clone()))
+            .unwrap();
+
+        let warmer_profiles_opts = prometheus::Opts::new(
+            "warmer_profiles",
+            "Total number of profiles handled by the cache warmer, by outcome",
+        );
+        let warmer_profiles = CounterVec::new(warmer_profiles_opts, &["outcome"]).unwrap();
+        registry
+            .register(Box::new(warmer_profiles.clone()))
+            .unwrap();
+
+        let follower_invalidations = IntCounter::new(
+            "follower_invalidations",
+            "Total number of cache entries invalidated by on-chain events",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(follower_invalidations.clone()))
+            .unwrap();
+
+        let follower_block =
+            IntGauge::new("follower_block", "Last block processed by the log follower").unwrap();
+        registry
+            .register(Box::new(follower_block.clone()))
+            .unwrap();
+
+        let webhook_deliveries_opts = prometheus::Opts::new(
+            "webhook_deliveries",
+            "Total number of webhook delivery attempts, by outcome",
+        );
+        let webhook_deliveries = CounterVec::new(webhook_deliveries_opts, &["outcome"]).unwrap();
+        registry
+            .register(Box::new(webhook_deliveries.clone()))
+            .unwrap();
+
+        let websocket_connections = IntGauge::new(
+            "websocket_connections",
+            "Number of currently open /ws connections",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(websocket_connections.clone()))
+            .unwrap();
+
+        let multicall_batch_size_opts = prometheus::HistogramOpts::new(
+            "multicall_batch_size",
+            "Number of universal resolver calls per Multicall3 batch",
+        )
+        .buckets(vec![1.0, 2.0, 5.0, 10.0, 20.0, 50.0]);
+        let multicall_batch_size = Histogram::with_opts(multicall_batch_size_opts).unwrap();
+        registry
+            .register(Box::new(multicall_batch_size.clone()))
+            .unwrap();
+
+        let multicall_errors = IntCounter::new(
+            "multicall_errors",
+            "Total number of failed Multicall3 batches, their calls are retried on their own",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(multicall_errors.clone()))
+            .unwrap();
+
+        let rpc_in_flight = IntGauge::new(
+            "rpc_in_flight",
+            "Number of lookups currently resolving from chain",
+        )
+        .unwrap();
+        registry.register(Box::new(rpc_in_flight.clone())).unwrap();
+
+        let rpc_queue_depth = IntGauge::new(
+            "rpc_queue_depth",
+            "Number of lookups waiting for a concurrency slot",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(rpc_queue_depth.clone()))
+            .unwrap();
+
+        let rpc_queue_seconds_opts = prometheus::HistogramOpts::new(
+            "rpc_queue_seconds",
+            "Time lookups spent waiting for a concurrency slot",
+        )
+        .buckets(vec![0.0, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]);
+        let rpc_queue_seconds = Histogram::with_opts(rpc_queue_seconds_opts).unwrap();
+        registry
+            .register(Box::new(rpc_queue_seconds.clone()))
+            .unwrap();
+
+        let rpc_shed = IntCounter::new(
+            "rpc_shed",
+            "Total number of lookups rejected because the queue was full",
+        )
+        .unwrap();
+        registry.register(Box::new(rpc_shed.clone())).unwrap();
+
         // let rate_limit_infringements = Counter::with_opts(rate_limit_infringements_opts).unwrap();
         // registry
         //     .register(Box::new(rate_limit_infringements.clone()))
@@ -56,10 +230,39 @@ impl Metrics {
             name_lookup_total,
             name_lookup_latency,
             rate_limit_infringements,
+            cache_errors,
+            cache_circuit_open,
+            cache_decode_failures,
+            cache_migrations,
+            popularity_circuit_open,
+            popularity_dropped_hits,
+            warmer_profiles,
+            follower_invalidations,
+            follower_block,
+            webhook_deliveries,
+            websocket_connections,
+            multicall_batch_size,
+            multicall_errors,
+            rpc_in_flight,
+            rpc_queue_depth,
+            rpc_queue_seconds,
+            rpc_shed,
         }
     }
 }
 
+impl Observer for Metrics {
+    fn cache_migrated(&self, _key: &str) {
+        self.cache_migrations.inc();
+    }
+
+    fn cache_decode_failed(&self, _key: &str, error: &EnvelopeError) {
+        self.cache_decode_failures
+            .with_label_values(&[error.reason()])
+            .inc();
+    }
+}
+
 pub async fn handle(State(state): State<Arc<crate::AppState>>) -> impl IntoResponse {
     let encoder = TextEncoder::new();
     let metric_families = state.metrics.registry.gather();
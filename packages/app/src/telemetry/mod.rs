@@ -8,6 +8,7 @@ use tracing_subscriber::{EnvFilter, FmtSubscriber};
 use tracing_subscriber::layer::SubscriberExt;
 
 pub mod metrics;
+pub mod trace;
 
 pub fn setup() {
     let filter = EnvFilter::new(format!(
@@ -22,7 +23,9 @@ pub fn setup() {
         .with_max_level(Level::DEBUG)
         .with_env_filter(filter)
         // completes the builder.
-        .finish();
+        .finish()
+        // records the lookups of /v2/debug
+        .with(trace::TraceCollector);
 
     let otlp_url = env::var("OTLP_ENDPOINT");
 
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Instrument, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+const ROOT_SPAN: &str = "debug_trace";
+
+// spans and events beyond this are dropped, a single lookup stays far below it
+const MAX_ENTRIES: usize = 5000;
+
+lazy_static! {
+    static ref TRACES: DashMap<u64, Trace> = DashMap::new();
+}
+
+static NEXT_TRACE: AtomicU64 = AtomicU64::new(1);
+
+struct Trace {
+    started: Instant,
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    fn elapsed_ms(&self) -> f64 {
+        self.started.elapsed().as_secs_f64() * 1000.0
+    }
+
+    fn push(&mut self, entry: TraceEntry) -> Option<usize> {
+        if self.entries.len() >= MAX_ENTRIES {
+            return None;
+        }
+
+        self.entries.push(entry);
+
+        Some(self.entries.len() - 1)
+    }
+}
+
+/// A span or event recorded while collecting a trace, times are relative to its start
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceEntry {
+    Span {
+        id: usize,
+        parent: Option<usize>,
+        name: &'static str,
+        target: &'static str,
+        fields: BTreeMap<&'static str, String>,
+        start_ms: f64,
+        duration_ms: Option<f64>,
+    },
+    Event {
+        span: usize,
+        level: String,
+        target: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        fields: BTreeMap<&'static str, String>,
+        at_ms: f64,
+    },
+}
+
+/// Stored in the extensions of every span that is part of a collected trace
+struct Traced {
+    trace: u64,
+    index: usize,
+}
+
+/// Records the spans and events under [`collect`], every other span is ignored.
+pub struct TraceCollector;
+
+impl<S> Layer<S> for TraceCollector
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        let (trace, parent) = if attrs.metadata().name() == ROOT_SPAN {
+            let Some(trace) = fields.trace_id else {
+                return;
+            };
+
+            (trace, None)
+        } else {
+            let Some(parent) = span.parent() else {
+                return;
+            };
+
+            let extensions = parent.extensions();
+            let Some(traced) = extensions.get::<Traced>() else {
+                return;
+            };
+
+            (traced.trace, Some(traced.index))
+        };
+
+        let Some(mut collected) = TRACES.get_mut(&trace) else {
+            return;
+        };
+
+        let start_ms = collected.elapsed_ms();
+        let index = collected.entries.len();
+
+        let Some(index) = collected.push(TraceEntry::Span {
+            id: index,
+            parent,
+            name: attrs.metadata().name(),
+            target: attrs.metadata().target(),
+            fields: fields.values,
+            start_ms,
+            duration_ms: None,
+        }) else {
+            return;
+        };
+
+        drop(collected);
+
+        span.extensions_mut().insert(Traced { trace, index });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let extensions = span.extensions();
+        let Some(traced) = extensions.get::<Traced>() else {
+            return;
+        };
+
+        let Some(mut collected) = TRACES.get_mut(&traced.trace) else {
+            return;
+        };
+
+        let mut recorded = Fields::default();
+        values.record(&mut recorded);
+
+        if let Some(TraceEntry::Span { fields, .. }) = collected.entries.get_mut(traced.index) {
+            fields.extend(recorded.values);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+
+        let extensions = span.extensions();
+        let Some(traced) = extensions.get::<Traced>() else {
+            return;
+        };
+
+        let Some(mut collected) = TRACES.get_mut(&traced.trace) else {
+            return;
+        };
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let at_ms = collected.elapsed_ms();
+
+        collected.push(TraceEntry::Event {
+            span: traced.index,
+            level: event.metadata().level().to_string(),
+            target: event.metadata().target(),
+            message: fields.message,
+            fields: fields.values,
+            at_ms,
+        });
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        let extensions = span.extensions();
+        let Some(traced) = extensions.get::<Traced>() else {
+            return;
+        };
+
+        let Some(mut collected) = TRACES.get_mut(&traced.trace) else {
+            return;
+        };
+
+        let now = collected.elapsed_ms();
+
+        if let Some(TraceEntry::Span {
+            start_ms,
+            duration_ms,
+            ..
+        }) = collected.entries.get_mut(traced.index)
+        {
+            *duration_ms = Some(now - *start_ms);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    trace_id: Option<u64>,
+    message: Option<String>,
+    values: BTreeMap<&'static str, String>,
+}
+
+impl Visit for Fields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "trace_id" {
+            self.trace_id = Some(value);
+        }
+
+        self.values.insert(field.name(), value.to_string());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.values.insert(field.name(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        } else {
+            self.values.insert(field.name(), format!("{value:?}"));
+        }
+    }
+}
+
+/// Drops the trace when the future is, e.g. because the client went away
+struct TraceGuard(u64);
+
+impl Drop for TraceGuard {
+    fn drop(&mut self) {
+        TRACES.remove(&self.0);
+    }
+}
+
+/// Runs `future` while recording every span and event it emits, in the order they happened.
+///
+/// Only spans and events enabled by the global filter are recorded, and nothing at all
+/// without [`TraceCollector`] installed.
+pub async fn collect<F: Future>(future: F) -> (F::Output, Vec<TraceEntry>) {
+    let trace = NEXT_TRACE.fetch_add(1, Ordering::Relaxed);
+
+    TRACES.insert(
+        trace,
+        Trace {
+            started: Instant::now(),
+            entries: Vec::new(),
+        },
+    );
+
+    let guard = TraceGuard(trace);
+
+    let output = future
+        .instrument(tracing::info_span!(ROOT_SPAN, trace_id = trace))
+        .await;
+
+    let entries = TRACES
+        .remove(&guard.0)
+        .map(|(_, trace)| trace.entries)
+        .unwrap_or_default();
+
+    (output, entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use super::{collect, TraceCollector, TraceEntry, TRACES};
+
+    #[tokio::test]
+    async fn test_collect() {
+        let _guard = tracing::subscriber::set_default(Registry::default().with(TraceCollector));
+
+        info!("outside of the trace");
+
+        let (output, entries) = collect(async {
+            let _span = tracing::info_span!("lookup", name = "luc.eth").entered();
+            info!(record = "avatar", "resolved");
+
+            1
+        })
+        .await;
+
+        assert_eq!(output, 1);
+        assert_eq!(entries.len(), 3);
+        assert!(TRACES.is_empty());
+
+        assert!(matches!(
+            &entries[1],
+            TraceEntry::Span { name: "lookup", parent: Some(0), fields, duration_ms: Some(_), .. }
+                if fields["name"] == "luc.eth"
+        ));
+        assert!(matches!(
+            &entries[2],
+            TraceEntry::Event { span: 1, message: Some(message), fields, .. }
+                if message == "resolved" && fields["record"] == "avatar"
+        ));
+    }
+}
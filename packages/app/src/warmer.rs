@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use enstate_shared::cache::CacheError;
+use enstate_shared::core::error::ProfileError;
+use enstate_shared::core::lookup_data::LookupInfo;
+use enstate_shared::popularity::PopularityKind;
+use enstate_shared::utils::vec::dedup_ord;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::WarmerConfig;
+use crate::state::AppState;
+
+/// Re-resolves popular and configured names shortly before their cached
+/// profile expires, so users don't pay the resolution latency.
+///
+/// Every run reads the current [`WarmerConfig`], so reloads apply from the next run.
+/// Names that aren't found are retried after exponentially more runs, up to
+/// [`MAX_BACKOFF_RUNS`], rather than spending the budget on them every run.
+pub struct Warmer {
+    state: Arc<AppState>,
+    runs: u64,
+    not_found: HashMap<String, Backoff>,
+}
+
+const MAX_BACKOFF_RUNS: u64 = 64;
+
+struct Backoff {
+    misses: u32,
+    /// First run the name is tried again on
+    retry_on: u64,
+}
+
+#[derive(Default)]
+struct RunStats {
+    warmed: u32,
+    not_found: u32,
+    failed: u32,
+    skipped: u32,
+}
+
+impl Warmer {
+    pub fn spawn(state: Arc<AppState>, shutdown: CancellationToken) -> Option<JoinHandle<()>> {
+        let config = state.warmer();
+
+        if !config.enabled {
+            return None;
+        }
+
+        let mut warmer = Self::new(state);
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+
+            loop {
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    _ = interval.tick() => warmer.run(&shutdown).await,
+                }
+            }
+
+            info!("Cache warmer shutdown");
+        }))
+    }
+
+    fn new(state: Arc<AppState>) -> Self {
+        Self {
+            state,
+            runs: 0,
+            not_found: HashMap::new(),
+        }
+    }
+
+    async fn run(&mut self, shutdown: &CancellationToken) {
+        let config = self.state.warmer();
+
+        self.runs += 1;
+
+        let mut stats = RunStats::default();
+
+        let candidates = self.candidates(&config).await;
+
+        // forget names that are no longer candidates, e.g. popular ones that dropped out
+        let listed = candidates.iter().collect::<HashSet<_>>();
+        self.not_found.retain(|name, _| listed.contains(name));
+
+        for name in self.due(&config, candidates, &mut stats).await {
+            let result = tokio::select! {
+                () = shutdown.cancelled() => break,
+                result = self.state.service.resolve_name_profile(name.clone(), true) => result,
+            };
+
+            match result {
+                Ok(_) => {
+                    self.not_found.remove(&name);
+
+                    stats.warmed += 1;
+                }
+                Err(ProfileError::NotFound) => {
+                    self.back_off(name);
+
+                    stats.not_found += 1;
+                }
+                Err(err) => {
+                    warn!(name, "failed to warm profile: {err}");
+
+                    stats.failed += 1;
+                }
+            }
+        }
+
+        let metric = &self.state.metrics.warmer_profiles;
+        metric
+            .with_label_values(&["warmed"])
+            .inc_by(f64::from(stats.warmed));
+        metric
+            .with_label_values(&["not_found"])
+            .inc_by(f64::from(stats.not_found));
+        metric
+            .with_label_values(&["failed"])
+            .inc_by(f64::from(stats.failed));
+        metric
+            .with_label_values(&["skipped"])
+            .inc_by(f64::from(stats.skipped));
+
+        info!(
+            "Cache warmer run: {} warmed, {} not found, {} failed, {} skipped",
+            stats.warmed, stats.not_found, stats.failed, stats.skipped
+        );
+    }
+
+    /// Candidates to re-resolve this run, at most `rpc_budget` of them, the others are skipped
+    async fn due(
+        &self,
+        config: &WarmerConfig,
+        candidates: Vec<String>,
+        stats: &mut RunStats,
+    ) -> Vec<String> {
+        let mut due = Vec::new();
+
+        for name in candidates {
+            let backing_off = self
+                .not_found
+                .get(&name)
+                .is_some_and(|backoff| backoff.retry_on > self.runs);
+
+            if backing_off
+                || due.len() >= config.rpc_budget
+                || !self.needs_refresh(config, &name).await
+            {
+                stats.skipped += 1;
+                continue;
+            }
+
+            due.push(name);
+        }
+
+        due
+    }
+
+    fn back_off(&mut self, name: String) {
+        let backoff = self.not_found.entry(name).or_insert(Backoff {
+            misses: 0,
+            retry_on: 0,
+        });
+
+        backoff.misses += 1;
+        backoff.retry_on = self.runs + (1 << backoff.misses.min(6)).min(MAX_BACKOFF_RUNS);
+    }
+
+    /// Configured names first, so they're never pushed out of the budget by popular ones
+    async fn candidates(&self, config: &WarmerConfig) -> Vec<String> {
+        let mut names = config.names.clone();
+
+        if let Some(path) = &config.names_file {
+            match tokio::fs::read_to_string(path).await {
+                Ok(contents) => names.extend(
+                    contents
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(ToString::to_string),
+                ),
+                Err(err) => warn!("failed to read {}: {err}", path.display()),
+            }
+        }
+
+        let popularity = self.state.service.popularity.as_ref();
+
+        if let Some(popularity) = popularity.filter(|_| config.top_n > 0) {
+            match popularity
+                .top(PopularityKind::Name, &config.window, config.top_n)
+                .await
+            {
+                Ok(top) => names.extend(top.into_iter().map(|entry| entry.key)),
+                Err(err) => warn!("failed to get popular names: {err}"),
+            }
+        }
+
+        let names = names
+            .iter()
+            .filter_map(|name| match LookupInfo::guess(name) {
+                Ok(LookupInfo::Name(name)) => Some(name),
+                _ => {
+                    warn!(name, "skipping invalid name");
+
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        dedup_ord(&names)
+    }
+
+    async fn needs_refresh(&self, config: &WarmerConfig, name: &str) -> bool {
+        let service = &self.state.service;
+
+        match service.cache.ttl(&service.profile_key(name)).await {
+            Ok(Some(remaining)) => remaining <= config.refresh_before,
+            Ok(None) => false,
+            Err(CacheError::NotFound) => true,
+            // a failing cache wouldn't keep the result anyway
+            Err(CacheError::Other(_)) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::{RunStats, Warmer};
+    use crate::config::Config;
+    use crate::state::AppState;
+
+    #[tokio::test]
+    async fn test_due() {
+        let config: Config = toml::from_str(
+            r#"
+            [rpc]
+            urls = ["http://localhost:8545"]
+            universal_resolver = "0xce01f8eee7E479C928F8919abD53E553a36CeF67"
+
+            [warmer]
+            refresh_before = 60
+            rpc_budget = 2
+            "#,
+        )
+        .unwrap();
+
+        let state = Arc::new(AppState::new(&config, &CancellationToken::new()).await);
+        let cache = &state.service.cache;
+
+        cache.set("n:fresh.eth", b"profile", 600).await.unwrap();
+        cache.set("n:expiring.eth", b"profile", 30).await.unwrap();
+
+        let config = state.warmer();
+        let mut warmer = Warmer::new(state.clone());
+        let candidates = [
+            "fresh.eth",
+            "expiring.eth",
+            "uncached.eth",
+            "over-budget.eth",
+        ]
+        .map(ToString::to_string)
+        .to_vec();
+
+        // profiles expiring within refresh_before or not cached, up to rpc_budget
+        let mut stats = RunStats::default();
+        let due = warmer.due(&config, candidates.clone(), &mut stats).await;
+
+        assert_eq!(due, ["expiring.eth", "uncached.eth"]);
+        assert_eq!(stats.skipped, 2);
+
+        // a name that wasn't found is skipped for a run, leaving its budget to others
+        warmer.runs = 1;
+        warmer.back_off("uncached.eth".to_string());
+        warmer.runs = 2;
+
+        let due = warmer
+            .due(&config, candidates.clone(), &mut RunStats::default())
+            .await;
+        assert_eq!(due, ["expiring.eth", "over-budget.eth"]);
+
+        warmer.runs = 3;
+
+        let due = warmer
+            .due(&config, candidates, &mut RunStats::default())
+            .await;
+        assert_eq!(due, ["expiring.eth", "uncached.eth"]);
+
+        // and for exponentially more runs every time it's still not found
+        warmer.back_off("uncached.eth".to_string());
+        assert_eq!(warmer.not_found["uncached.eth"].retry_on, 7);
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use enstate_shared::core::Profile;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::changes::{FieldChange, ProfileChange};
+use crate::state::AppState;
+use crate::webhooks::{random_hex, Subscription, Webhooks};
+
+#[derive(Serialize)]
+struct Event<'a> {
+    id: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    created_at: i64,
+    name: &'a str,
+    address: Option<String>,
+    changes: BTreeMap<String, FieldChange>,
+    profile: &'a Profile,
+}
+
+/// Matches profile changes against the webhook subscriptions and delivers
+/// them as signed `POST` requests, retrying failed deliveries with backoff.
+pub struct Dispatcher {
+    state: Arc<AppState>,
+    client: reqwest::Client,
+    shutdown: CancellationToken,
+}
+
+impl Dispatcher {
+    pub fn spawn(state: Arc<AppState>, shutdown: CancellationToken) -> Option<JoinHandle<()>> {
+        let webhooks = state.webhooks.as_ref()?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(webhooks.config.timeout))
+            .user_agent(concat!("enstate/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .ok()?;
+
+        let dispatcher = Arc::new(Self {
+            state: state.clone(),
+            client,
+            shutdown: shutdown.clone(),
+        });
+
+        let mut changes = state.changes.subscribe();
+        let mut refresh =
+            tokio::time::interval(Duration::from_secs(webhooks.config.refresh_interval));
+
+        Some(tokio::spawn(async move {
+            let webhooks = dispatcher.webhooks();
+
+            loop {
+                tokio::select! {
+                    () = shutdown.cancelled() => break,
+                    _ = refresh.tick() => {
+                        // picks up subscriptions created on other instances
+                        if let Err(err) = webhooks
+                            .refresh(&dispatcher.state.watchlist)
+                            .await
+                        {
+                            warn!("failed to refresh webhook subscriptions: {err}");
+                        }
+                    }
+                    change = changes.recv() => match change {
+                        Ok(change) => dispatcher.dispatch(&change),
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("webhook dispatcher lagging behind, skipped {skipped} changes");
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                }
+            }
+
+            info!("Webhook dispatcher shutdown");
+        }))
+    }
+
+    fn webhooks(&self) -> &Webhooks {
+        self.state
+            .webhooks
+            .as_ref()
+            .expect("dispatcher should only run with webhooks enabled")
+    }
+
+    fn dispatch(self: &Arc<Self>, change: &ProfileChange) {
+        for subscription in self.webhooks().subscriptions().iter() {
+            let Some(changes) = subscription.filter(change) else {
+                continue;
+            };
+
+            let event = Event {
+                id: random_hex::<16>(),
+                kind: "profile.changed",
+                created_at: chrono::Utc::now().timestamp(),
+                name: &change.name,
+                address: change.address.as_ref().map(ToString::to_string),
+                changes: changes.into_iter().collect(),
+                profile: &change.profile,
+            };
+
+            let body = match serde_json::to_string(&event) {
+                Ok(body) => body,
+                Err(err) => {
+                    warn!("failed to encode webhook event: {err}");
+
+                    continue;
+                }
+            };
+
+            let dispatcher = self.clone();
+            let subscription = subscription.clone();
+            let event_id = event.id;
+
+            tokio::spawn(async move {
+                dispatcher.deliver(&subscription, &event_id, body).await;
+            });
+        }
+    }
+
+    async fn deliver(&self, subscription: &Subscription, event_id: &str, body: String) {
+        let config = &self.webhooks().config;
+        let metrics = &self.state.metrics.webhook_deliveries;
+
+        let mut attempt = 1;
+
+        let (status, error) = loop {
+            let (status, error) = match self.attempt(subscription, event_id, &body).await {
+                Ok(response) if response.status().is_success() => {
+                    (Some(response.status().as_u16()), None)
+                }
+                Ok(response) => (
+                    Some(response.status().as_u16()),
+                    Some(format!("unexpected status {}", response.status())),
+                ),
+                Err(err) => (None, Some(err.to_string())),
+            };
+
+            if error.is_none() {
+                metrics.with_label_values(&["delivered"]).inc();
+
+                break (status, None);
+            }
+
+            if attempt >= config.max_attempts || self.shutdown.is_cancelled() {
+                metrics.with_label_values(&["failed"]).inc();
+
+                break (status, error);
+            }
+
+            metrics.with_label_values(&["retried"]).inc();
+
+            let delay = backoff(config.backoff_base, config.backoff_max, attempt);
+
+            tokio::select! {
+                () = self.shutdown.cancelled() => {
+                    metrics.with_label_values(&["failed"]).inc();
+
+                    break (status, error);
+                }
+                () = tokio::time::sleep(delay) => {}
+            }
+
+            attempt += 1;
+        };
+
+        if let Some(error) = &error {
+            warn!(
+                id = subscription.id,
+                event_id, "webhook delivery failed after {attempt} attempts: {error}"
+            );
+        }
+
+        if let Err(err) = self
+            .webhooks()
+            .record_delivery(&subscription.id, status, error)
+            .await
+        {
+            warn!(
+                id = subscription.id,
+                "failed to store webhook delivery state: {err}"
+            );
+        }
+    }
+
+    async fn attempt(
+        &self,
+        subscription: &Subscription,
+        event_id: &str,
+        body: &str,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+
+        self.client
+            .post(subscription.url.clone())
+            .header("Content-Type", "application/json")
+            .header("X-Enstate-Event-Id", event_id)
+            .header("X-Enstate-Timestamp", &timestamp)
+            .header(
+                "X-Enstate-Signature",
+                format!("sha256={}", sign(&subscription.secret, &timestamp, body)),
+            )
+            .body(body.to_string())
+            .send()
+            .await
+    }
+}
+
+/// `HMAC-SHA256(secret, "{timestamp}.{body}")`, hex encoded.
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("hmac should accept keys of any length");
+
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn backoff(base: u64, max: u64, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)));
+
+    Duration::from_secs(delay.min(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{backoff, sign};
+
+    #[test]
+    fn test_sign() {
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", "1700000000", "{}"),
+            "b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(2, 300, 1), Duration::from_secs(2));
+        assert_eq!(backoff(2, 300, 3), Duration::from_secs(8));
+        assert_eq!(backoff(2, 300, 20), Duration::from_secs(300));
+    }
+}
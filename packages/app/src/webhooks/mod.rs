@@ -0,0 +1,464 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use enstate_shared::cache::{CacheError, CacheLayer};
+use ethers_core::types::Address;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::cache::{Disk, Redis};
+use crate::changes::{address_key, name_key, FieldChange, ProfileChange, Watchlist};
+use crate::config::{WebhookStore, WebhooksConfig};
+use crate::database;
+
+pub mod dispatcher;
+
+/// Prefix of every key in the store, a redis store shares its database with the cache
+pub const KEY_PREFIX: &str = "webhooks:";
+
+const SUBSCRIPTIONS_KEY: &str = "webhooks:subscriptions";
+const WATCHLIST_OWNER: &str = "webhooks";
+
+/// Subscriptions and delivery state never expire on their own.
+const STORE_TTL: u32 = 10 * 365 * 24 * 60 * 60;
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Subscription limit of {0} reached")]
+    TooManySubscriptions(usize),
+
+    #[error("Store error: {0}")]
+    Store(#[from] CacheError),
+
+    #[error("Corrupt subscription store: {0}")]
+    Corrupt(#[from] serde_json::Error),
+
+    #[error("Unusable subscription store: {0}")]
+    Unusable(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub url: Url,
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub addresses: Vec<Address>,
+    /// Only deliver changes to these fields, e.g. `avatar`, `com.twitter` or `records.com.twitter`
+    pub records: Option<Vec<String>>,
+    /// HMAC-SHA256 key for `X-Enstate-Signature`
+    pub secret: String,
+    pub created_at: i64,
+}
+
+impl Subscription {
+    pub fn new(
+        url: Url,
+        names: Vec<String>,
+        addresses: Vec<Address>,
+        records: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            id: random_hex::<16>(),
+            url,
+            names,
+            addresses,
+            records,
+            secret: random_hex::<32>(),
+            created_at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.names
+            .iter()
+            .map(|name| name_key(name))
+            .chain(self.addresses.iter().map(address_key))
+    }
+
+    /// The part of `change` this subscription is interested in, `None` if nothing is left.
+    pub fn filter(&self, change: &ProfileChange) -> Option<Vec<(String, FieldChange)>> {
+        let watched = self.names.contains(&change.name)
+            || change
+                .address
+                .as_ref()
+                .is_some_and(|address| self.addresses.contains(&address.0));
+
+        if !watched {
+            return None;
+        }
+
+        let changes = change
+            .changes
+            .iter()
+            .filter(|(key, _)| {
+                self.records.as_ref().map_or(true, |records| {
+                    records.iter().any(|record| {
+                        record == *key
+                            || ["records.", "chains."]
+                                .iter()
+                                .any(|prefix| key.strip_prefix(prefix) == Some(record.as_str()))
+                    })
+                })
+            })
+            .map(|(key, change)| (key.clone(), change.clone()))
+            .collect::<Vec<_>>();
+
+        (!changes.is_empty()).then_some(changes)
+    }
+}
+
+/// Outcome of the deliveries to a subscription, stored under `webhooks:state:{id}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeliveryState {
+    pub delivered: u64,
+    pub failed: u64,
+    pub last_attempt_at: Option<i64>,
+    /// HTTP status of the last attempt, `None` if no response was received
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+}
+
+/// Webhook subscriptions, persisted in a store of their own which is never evicted or
+/// purged along with the cache.
+pub struct Webhooks {
+    pub config: WebhooksConfig,
+    store: Box<dyn CacheLayer>,
+    subscriptions: RwLock<Arc<Vec<Subscription>>>,
+    // serializes read-modify-write cycles of the stored subscriptions and delivery states on this instance
+    write_lock: Mutex<()>,
+}
+
+impl Webhooks {
+    pub fn new(config: WebhooksConfig, store: Box<dyn CacheLayer>) -> Self {
+        Self {
+            config,
+            store,
+            subscriptions: RwLock::default(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Opens the configured store, refusing a redis that may evict subscriptions.
+    pub async fn open(
+        config: WebhooksConfig,
+        redis_url: Option<&str>,
+    ) -> Result<Self, WebhookError> {
+        let store = config.store.unwrap_or(if redis_url.is_some() {
+            WebhookStore::Redis
+        } else {
+            WebhookStore::Disk
+        });
+
+        let store: Box<dyn CacheLayer> = match store {
+            WebhookStore::Redis => {
+                let redis_url = redis_url.expect("redis_url should've been validated");
+
+                let mut redis = database::setup(redis_url)
+                    .await
+                    .map_err(|err| WebhookError::Unusable(err.to_string()))?;
+
+                let policy: Result<(String, String), _> = redis::cmd("CONFIG")
+                    .arg("GET")
+                    .arg("maxmemory-policy")
+                    .query_async(&mut redis)
+                    .await;
+
+                match policy {
+                    Ok((_, policy)) if policy == "noeviction" => {}
+                    Ok((_, policy)) => {
+                        return Err(WebhookError::Unusable(format!(
+                            "redis maxmemory-policy is {policy}, it should be noeviction or webhooks.store disk"
+                        )))
+                    }
+                    // managed redis often doesn't allow CONFIG
+                    Err(err) => warn!(
+                        "could not check the redis maxmemory-policy, subscriptions are lost if it evicts: {err}"
+                    ),
+                }
+
+                info!("Storing webhook subscriptions in redis");
+
+                Box::new(Redis::new(redis))
+            }
+            WebhookStore::Disk => {
+                // compaction never runs on this database, so nothing is evicted
+                let disk = Disk::open(&config.store_path, u64::MAX)
+                    .map_err(|err| WebhookError::Unusable(err.to_string()))?;
+
+                info!(
+                    "Storing webhook subscriptions at {}",
+                    config.store_path.display()
+                );
+
+                Box::new(disk)
+            }
+        };
+
+        Ok(Self::new(config, store))
+    }
+
+    pub fn subscriptions(&self) -> Arc<Vec<Subscription>> {
+        self.subscriptions
+            .read()
+            .expect("subscriptions lock should not be poisoned")
+            .clone()
+    }
+
+    /// Reloads the subscriptions from the store and watches their names and addresses.
+    pub async fn refresh(&self, watchlist: &Watchlist) -> Result<(), WebhookError> {
+        let subscriptions = load(self.store.as_ref()).await?;
+
+        self.apply(subscriptions, watchlist);
+
+        Ok(())
+    }
+
+    pub async fn create(
+        &self,
+        watchlist: &Watchlist,
+        subscription: Subscription,
+    ) -> Result<Subscription, WebhookError> {
+        let _guard = self.write_lock.lock().await;
+        let store = self.store.as_ref();
+
+        let mut subscriptions = load(store).await?;
+
+        if subscriptions.len() >= self.config.max_subscriptions {
+            return Err(WebhookError::TooManySubscriptions(
+                self.config.max_subscriptions,
+            ));
+        }
+
+        subscriptions.push(subscription.clone());
+        save(store, &subscriptions).await?;
+
+        self.apply(subscriptions, watchlist);
+
+        Ok(subscription)
+    }
+
+    /// Returns whether a subscription with `id` existed.
+    pub async fn delete(&self, watchlist: &Watchlist, id: &str) -> Result<bool, WebhookError> {
+        let _guard = self.write_lock.lock().await;
+        let store = self.store.as_ref();
+
+        let mut subscriptions = load(store).await?;
+        let before = subscriptions.len();
+
+        subscriptions.retain(|subscription| subscription.id != id);
+
+        if subscriptions.len() == before {
+            return Ok(false);
+        }
+
+        save(store, &subscriptions).await?;
+
+        if let Err(err) = store.delete(&state_key(id)).await {
+            warn!(id, "failed to delete webhook delivery state: {err}");
+        }
+
+        self.apply(subscriptions, watchlist);
+
+        Ok(true)
+    }
+
+    pub async fn delivery_state(&self, id: &str) -> Result<DeliveryState, WebhookError> {
+        match self.store.get(&state_key(id)).await {
+            Ok(value) => Ok(serde_json::from_slice(&value)?),
+            Err(CacheError::NotFound) => Ok(DeliveryState::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Records a finished delivery, `status` and `error` describe its last attempt.
+    pub async fn record_delivery(
+        &self,
+        id: &str,
+        status: Option<u16>,
+        error: Option<String>,
+    ) -> Result<(), WebhookError> {
+        // concurrent deliveries to the same subscription would otherwise lose counts
+        let _guard = self.write_lock.lock().await;
+
+        let mut state = self.delivery_state(id).await?;
+
+        if error.is_none() {
+            state.delivered += 1;
+        } else {
+            state.failed += 1;
+        }
+
+        state.last_attempt_at = Some(chrono::Utc::now().timestamp());
+        state.last_status = status;
+        state.last_error = error;
+
+        self.store
+            .set(&state_key(id), &serde_json::to_vec(&state)?, STORE_TTL)
+            .await?;
+
+        Ok(())
+    }
+
+    fn apply(&self, subscriptions: Vec<Subscription>, watchlist: &Watchlist) {
+        watchlist.set(
+            WATCHLIST_OWNER,
+            subscriptions
+                .iter()
+                .flat_map(Subscription::keys)
+                .collect::<HashSet<_>>(),
+        );
+
+        *self
+            .subscriptions
+            .write()
+            .expect("subscriptions lock should not be poisoned") = Arc::new(subscriptions);
+    }
+}
+
+async fn load(store: &dyn CacheLayer) -> Result<Vec<Subscription>, WebhookError> {
+    match store.get(SUBSCRIPTIONS_KEY).await {
+        Ok(value) => Ok(serde_json::from_slice(&value)?),
+        Err(CacheError::NotFound) => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn save(store: &dyn CacheLayer, subscriptions: &[Subscription]) -> Result<(), WebhookError> {
+    store
+        .set(
+            SUBSCRIPTIONS_KEY,
+            &serde_json::to_vec(subscriptions)?,
+            STORE_TTL,
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn state_key(id: &str) -> String {
+    format!("{KEY_PREFIX}state:{id}")
+}
+
+fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::num::NonZeroUsize;
+
+    use futures::future::join_all;
+
+    use super::{Subscription, Webhooks};
+    use crate::cache::Memory;
+    use crate::changes::tests::profile;
+    use crate::changes::{FieldChange, ProfileChange, Watchlist};
+    use crate::config::{WebhookStore, WebhooksConfig};
+
+    fn change() -> ProfileChange {
+        let field = || FieldChange {
+            old: None,
+            new: Some("lucemans".to_string()),
+        };
+
+        ProfileChange {
+            name: "luc.eth".to_string(),
+            address: None,
+            changes: BTreeMap::from([
+                ("avatar".to_string(), field()),
+                ("records.com.twitter".to_string(), field()),
+            ]),
+            profile: profile(),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let url = "https://example.com/hook".parse().unwrap();
+
+        let all = Subscription::new(url, vec!["luc.eth".to_string()], Vec::new(), None);
+        assert_eq!(all.filter(&change()).unwrap().len(), 2);
+
+        let twitter = Subscription {
+            records: Some(vec!["com.twitter".to_string()]),
+            ..all.clone()
+        };
+        assert_eq!(
+            twitter.filter(&change()).unwrap()[0].0,
+            "records.com.twitter"
+        );
+
+        let header = Subscription {
+            records: Some(vec!["header".to_string()]),
+            ..all.clone()
+        };
+        assert!(header.filter(&change()).is_none());
+
+        let other = Subscription {
+            names: vec!["vitalik.eth".to_string()],
+            ..all
+        };
+        assert!(other.filter(&change()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_record_delivery_counts_concurrent_deliveries() {
+        let webhooks = Webhooks::new(
+            WebhooksConfig::default(),
+            Box::new(Memory::new(NonZeroUsize::new(16).unwrap())),
+        );
+
+        join_all((0..20).map(|i| {
+            let error = (i % 4 == 0).then(|| "unexpected status 500".to_string());
+
+            webhooks.record_delivery("a", Some(200), error)
+        }))
+        .await;
+
+        let state = webhooks.delivery_state("a").await.unwrap();
+
+        assert_eq!(state.delivered, 15);
+        assert_eq!(state.failed, 5);
+    }
+
+    #[tokio::test]
+    async fn test_disk_store_persists_subscriptions() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = WebhooksConfig {
+            store: Some(WebhookStore::Disk),
+            store_path: dir.path().join("webhooks.redb"),
+            ..WebhooksConfig::default()
+        };
+
+        let url = "https://example.com/hook".parse().unwrap();
+        let subscription = Subscription::new(url, vec!["luc.eth".to_string()], Vec::new(), None);
+
+        {
+            let webhooks = Webhooks::open(config.clone(), None).await.unwrap();
+
+            webhooks
+                .create(&Watchlist::default(), subscription.clone())
+                .await
+                .unwrap();
+        }
+
+        let webhooks = Webhooks::open(config, None).await.unwrap();
+        let watchlist = Watchlist::default();
+
+        webhooks.refresh(&watchlist).await.unwrap();
+
+        assert_eq!(webhooks.subscriptions()[0].id, subscription.id);
+        assert!(watchlist.contains("n:luc.eth"));
+    }
+}
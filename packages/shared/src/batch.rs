@@ -0,0 +1,27 @@
+use async_trait::async_trait;
+use ethers_core::types::{Address, Bytes};
+use thiserror::Error;
+
+/// Result of a single call within a batch
+#[derive(Debug, Clone)]
+pub enum CallOutcome {
+    Success(Bytes),
+    /// The call reverted with this revert data, e.g. an `OffchainLookup` error
+    Reverted(Bytes),
+}
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("Batcher is not running")]
+    Closed,
+
+    #[error("Batch failed: {0}")]
+    Failed(String),
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait CallBatcher: Send + Sync {
+    /// `eth_call`s `data` on `to`, possibly aggregated with concurrent calls into a single request
+    async fn call(&self, to: Address, data: Bytes) -> Result<CallOutcome, BatchError>;
+}
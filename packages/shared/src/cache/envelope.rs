@@ -0,0 +1,257 @@
+//! Versioned binary envelope for cached values.
+//!
+//! Layout, integers are big endian:
+//!
+//! | bytes | field                                        |
+//! |-------|----------------------------------------------|
+//! | 2     | magic, `ES`                                  |
+//! | 1     | envelope format version                      |
+//! | 1     | [`Encoding`] of the payload                  |
+//! | 2     | schema version of the payload type           |
+//! | 8     | created at, unix seconds                     |
+//! | 8     | expires at, unix seconds                     |
+//! | ..    | payload                                      |
+
+use std::io::Read;
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+const MAGIC: &[u8; 2] = b"ES";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Cbor,
+    /// CBOR, compressed with raw deflate
+    CborDeflate,
+}
+
+impl Encoding {
+    const fn to_byte(self) -> u8 {
+        match self {
+            Self::Cbor => 0,
+            Self::CborDeflate => 1,
+        }
+    }
+
+    const fn from_byte(byte: u8) -> Result<Self, EnvelopeError> {
+        match byte {
+            0 => Ok(Self::Cbor),
+            1 => Ok(Self::CborDeflate),
+            _ => Err(EnvelopeError::UnknownEncoding(byte)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub schema_version: u16,
+    pub encoding: Encoding,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum EnvelopeError {
+    #[error("Not an envelope")]
+    NotAnEnvelope,
+
+    #[error("Unsupported envelope format version {0}")]
+    UnsupportedFormat(u8),
+
+    #[error("Unknown encoding {0}")]
+    UnknownEncoding(u8),
+
+    #[error("Schema version {found}, expected {expected}")]
+    SchemaMismatch { found: u16, expected: u16 },
+
+    #[error("Encode error: {0}")]
+    Encode(String),
+
+    #[error("Decode error: {0}")]
+    Decode(String),
+}
+
+impl EnvelopeError {
+    /// Short, stable identifier, e.g. for metric labels
+    pub const fn reason(&self) -> &'static str {
+        match self {
+            Self::NotAnEnvelope => "not_an_envelope",
+            Self::UnsupportedFormat(_) => "unsupported_format",
+            Self::UnknownEncoding(_) => "unknown_encoding",
+            Self::SchemaMismatch { .. } => "schema_mismatch",
+            Self::Encode(_) => "encode",
+            Self::Decode(_) => "decode",
+        }
+    }
+}
+
+pub fn encode<T: Serialize>(
+    value: &T,
+    schema_version: u16,
+    compress: bool,
+    ttl: u32,
+) -> Result<Vec<u8>, EnvelopeError> {
+    let encoding = if compress {
+        Encoding::CborDeflate
+    } else {
+        Encoding::Cbor
+    };
+
+    let created_at = unix_now();
+    let expires_at = created_at + u64::from(ttl);
+
+    let mut encoded = Vec::with_capacity(HEADER_LEN + 256);
+    encoded.extend_from_slice(MAGIC);
+    encoded.push(FORMAT_VERSION);
+    encoded.push(encoding.to_byte());
+    encoded.extend_from_slice(&schema_version.to_be_bytes());
+    encoded.extend_from_slice(&created_at.to_be_bytes());
+    encoded.extend_from_slice(&expires_at.to_be_bytes());
+
+    match encoding {
+        Encoding::Cbor => ciborium::into_writer(value, &mut encoded)
+            .map_err(|err| EnvelopeError::Encode(err.to_string()))?,
+        Encoding::CborDeflate => {
+            let mut encoder = DeflateEncoder::new(encoded, Compression::default());
+
+            ciborium::into_writer(value, &mut encoder)
+                .map_err(|err| EnvelopeError::Encode(err.to_string()))?;
+
+            encoded = encoder
+                .finish()
+                .map_err(|err| EnvelopeError::Encode(err.to_string()))?;
+        }
+    }
+
+    Ok(encoded)
+}
+
+/// Decodes an envelope whose payload was written with `schema_version`.
+pub fn decode<T: DeserializeOwned>(
+    bytes: &[u8],
+    schema_version: u16,
+) -> Result<(Header, T), EnvelopeError> {
+    let (header, payload) = decode_header(bytes)?;
+
+    if header.schema_version != schema_version {
+        return Err(EnvelopeError::SchemaMismatch {
+            found: header.schema_version,
+            expected: schema_version,
+        });
+    }
+
+    let value = decode_payload(&header, payload)?;
+
+    Ok((header, value))
+}
+
+/// Splits an envelope into its header and still encoded payload.
+pub fn decode_header(bytes: &[u8]) -> Result<(Header, &[u8]), EnvelopeError> {
+    if bytes.len() < HEADER_LEN || &bytes[..2] != MAGIC {
+        return Err(EnvelopeError::NotAnEnvelope);
+    }
+
+    if bytes[2] != FORMAT_VERSION {
+        return Err(EnvelopeError::UnsupportedFormat(bytes[2]));
+    }
+
+    let u64_at = |offset: usize| {
+        u64::from_be_bytes(
+            bytes[offset..offset + 8]
+                .try_into()
+                .expect("slice should be 8 bytes"),
+        )
+    };
+
+    let header = Header {
+        encoding: Encoding::from_byte(bytes[3])?,
+        schema_version: u16::from_be_bytes([bytes[4], bytes[5]]),
+        created_at: u64_at(6),
+        expires_at: u64_at(14),
+    };
+
+    Ok((header, &bytes[HEADER_LEN..]))
+}
+
+pub fn decode_payload<T: DeserializeOwned>(
+    header: &Header,
+    payload: &[u8],
+) -> Result<T, EnvelopeError> {
+    match header.encoding {
+        Encoding::Cbor => ciborium::from_reader(payload),
+        Encoding::CborDeflate => {
+            let mut decompressed = Vec::new();
+
+            DeflateDecoder::new(payload)
+                .read_to_end(&mut decompressed)
+                .map_err(|err| EnvelopeError::Decode(err.to_string()))?;
+
+            ciborium::from_reader(decompressed.as_slice())
+        }
+    }
+    .map_err(|err| EnvelopeError::Decode(err.to_string()))
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now()
+        .timestamp()
+        .try_into()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn sample() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("name".to_string(), "luc.eth".to_string()),
+            ("com.twitter".to_string(), "lucemans".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for compress in [false, true] {
+            let encoded = encode(&sample(), 3, compress, 600).unwrap();
+
+            let (header, decoded) = decode::<BTreeMap<String, String>>(&encoded, 3).unwrap();
+
+            assert_eq!(decoded, sample());
+            assert_eq!(header.schema_version, 3);
+            assert_eq!(header.expires_at - header.created_at, 600);
+        }
+    }
+
+    #[test]
+    fn test_schema_mismatch() {
+        let encoded = encode(&sample(), 1, false, 600).unwrap();
+
+        assert!(matches!(
+            decode::<BTreeMap<String, String>>(&encoded, 2),
+            Err(EnvelopeError::SchemaMismatch {
+                found: 1,
+                expected: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_legacy_json_is_not_an_envelope() {
+        let legacy = serde_json::to_vec(&sample()).unwrap();
+
+        assert!(matches!(
+            decode::<BTreeMap<String, String>>(&legacy, 1),
+            Err(EnvelopeError::NotAnEnvelope)
+        ));
+    }
+}
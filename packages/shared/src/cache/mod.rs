@@ -1,18 +1,36 @@
 use std::fmt::Debug;
 
 use async_trait::async_trait;
+use ethers_core::types::H256;
+use thiserror::Error;
 
-#[derive(Debug)]
+pub mod envelope;
+
+#[derive(Error, Debug)]
 pub enum CacheError {
+    #[error("Not found")]
+    NotFound,
+
+    #[error("{0}")]
     Other(String),
 }
 
+/// Key of the entry holding which cache key (`n:` or `a:`) belongs to `node`,
+/// so on-chain changes to a node can be mapped back to cached entries.
+pub fn node_key(node: &H256) -> String {
+    format!("h:{node:x}")
+}
+
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait CacheLayer: Send + Sync {
-    async fn get(&self, key: &str) -> Result<String, CacheError>;
-    async fn set(&self, key: &str, value: &str, expires: u32) -> Result<(), CacheError>;
-    async fn cache_hit(&self, key: &str) -> Result<(), CacheError>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CacheError>;
+    async fn set(&self, key: &str, value: &[u8], expires: u32) -> Result<(), CacheError>;
+    async fn delete(&self, key: &str) -> Result<(), CacheError>;
+    /// Remaining time to live in seconds, `None` if the entry never expires
+    async fn ttl(&self, key: &str) -> Result<Option<u32>, CacheError>;
+    /// Deletes every entry whose key starts with `prefix`, returning the amount deleted
+    async fn purge(&self, prefix: &str) -> Result<u64, CacheError>;
 }
 
 pub struct PassthroughCacheLayer {}
@@ -20,15 +38,23 @@ pub struct PassthroughCacheLayer {}
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl CacheLayer for PassthroughCacheLayer {
-    async fn get(&self, _key: &str) -> Result<String, CacheError> {
-        Err(CacheError::Other("".to_string()))
+    async fn get(&self, _key: &str) -> Result<Vec<u8>, CacheError> {
+        Err(CacheError::NotFound)
     }
 
-    async fn set(&self, _key: &str, _value: &str, _expires: u32) -> Result<(), CacheError> {
+    async fn set(&self, _key: &str, _value: &[u8], _expires: u32) -> Result<(), CacheError> {
         Ok(())
     }
 
-    async fn cache_hit(&self, _key: &str) -> Result<(), CacheError> {
+    async fn delete(&self, _key: &str) -> Result<(), CacheError> {
         Ok(())
     }
+
+    async fn ttl(&self, _key: &str) -> Result<Option<u32>, CacheError> {
+        Err(CacheError::NotFound)
+    }
+
+    async fn purge(&self, _prefix: &str) -> Result<u64, CacheError> {
+        Ok(0)
+    }
 }
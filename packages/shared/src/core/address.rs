@@ -1,67 +1,90 @@
 use ethers::middleware::MiddlewareBuilder;
-use ethers::providers::ProviderError;
+use ethers::providers::{namehash, reverse_address, ProviderError};
 use ethers_ccip_read::CCIPReadMiddleware;
 use ethers_core::types::Address;
 use thiserror::Error;
-use tracing::instrument;
+use tracing::{instrument, warn};
 
-use crate::core::resolvers::reverse::{resolve_reverse, ReverseResolveError};
-use crate::core::ENSService;
+use crate::core::resolvers::reverse::{reverse_name, verify_primary_name, ReverseResolveError};
+use crate::core::{CCIPProvider, ENSService};
+use crate::limiter::LimitError;
+use crate::popularity::PopularityKind;
 
 #[derive(Error, Debug)]
 pub enum AddressResolveError {
     #[error("Primary name not found")]
     NotFound,
 
-    #[error("Cache operation failed: {0}")]
-    CacheFail(&'static str),
-
     #[error("RPC error: {0}")]
     RPCError(#[from] ProviderError),
 
     #[error("Reverse resolution error: {0}")]
     ReverseResolutionError(#[from] ReverseResolveError),
+
+    #[error("{0}")]
+    Overloaded(#[from] LimitError),
 }
 
 impl ENSService {
-    #[instrument(skip(self))]
     pub async fn primary_from_address(
         &self,
         address: &Address,
         fresh: bool,
     ) -> Result<String, AddressResolveError> {
-        let cache_key = format!("a:{address:?}");
+        self.record_hit(PopularityKind::Address, &format!("{address:?}"))
+            .await;
+
+        self.primary_name(address, fresh).await
+    }
 
-        let rpc = self.rpc.get_instance();
-        let rpc = rpc.wrap_into(CCIPReadMiddleware::new);
+    /// Primary name of an address without counting the lookup towards its
+    /// popularity, e.g. for background refreshes.
+    #[instrument(skip(self))]
+    pub async fn primary_name(
+        &self,
+        address: &Address,
+        fresh: bool,
+    ) -> Result<String, AddressResolveError> {
+        let cache_key = format!("a:{address:?}");
 
         // TODO: improve
         let cached_name = if fresh {
             None
         } else {
-            self.cache.get(&cache_key).await.ok()
+            // primary names are small enough to be cached as plain utf-8
+            self.cache
+                .get(&cache_key)
+                .await
+                .ok()
+                .and_then(|name| String::from_utf8(name).ok())
         };
 
         // Get value from the cache otherwise compute
         let name = if let Some(name) = cached_name {
             name
         } else {
-            let result = resolve_reverse(&rpc, address, &self.universal_resolver)
-                .await
-                .or_else(|error| {
-                    match error {
-                        // address doesn't resolve, cache ""
-                        ReverseResolveError::MissingPrimaryName => Ok("".to_string()),
-                        // yield error up, don't cache
-                        _ => Err(error),
-                    }
-                })?;
+            let lease = self.lease(self.rpc.as_ref()).await?;
+            let rpc = lease.provider.clone().wrap_into(CCIPReadMiddleware::new);
+
+            let result = self.resolve_reverse(&rpc, address).await.or_else(|error| {
+                match error {
+                    // address doesn't resolve, cache ""
+                    ReverseResolveError::MissingPrimaryName => Ok("".to_string()),
+                    // yield error up, don't cache
+                    _ => Err(error),
+                }
+            })?;
+
+            let ttl = self.settings().cache_ttl.unwrap_or(600);
 
             // Cache the value, and expire it after 10 minutes
-            self.cache
-                .set(&cache_key, &result, self.cache_ttl.unwrap_or(600))
-                .await
-                .map_err(|_| AddressResolveError::CacheFail("set"))?;
+            if let Err(err) = self.cache.set(&cache_key, result.as_bytes(), ttl).await {
+                warn!(address = ?address, "failed to cache primary name: {err}");
+            } else {
+                let reverse_node = namehash(&reverse_address(*address));
+
+                self.index_node("", reverse_node, &cache_key, ttl).await;
+            }
 
             result
         };
@@ -70,6 +93,137 @@ impl ENSService {
             return Err(AddressResolveError::NotFound);
         }
 
+        Ok(self.heal_name(&name))
+    }
+
+    /// Reverse record of the address on this chain, or else on the first routed backend
+    /// that has one, verified on the backend of the name it points at, see
+    /// [`crate::core::routing::Routing`]
+    async fn resolve_reverse(
+        &self,
+        rpc: &CCIPProvider,
+        address: &Address,
+    ) -> Result<String, ReverseResolveError> {
+        let name = match reverse_name(
+            rpc.inner(),
+            address,
+            &self.registry,
+            self.reverse_registrar.as_ref(),
+        )
+        .await
+        {
+            Err(ReverseResolveError::MissingPrimaryName) => {
+                self.backend_reverse_name(address).await?
+            }
+            result => result?,
+        };
+
+        let route = self.route(&name);
+
+        // the lookup already holds its slot, only the backend's provider is waited for
+        let backend_rpc;
+        let _backend_permit;
+        let rpc = match route.backend {
+            Some(_) => {
+                let (provider, permit) = route.rpc.lease().await;
+
+                backend_rpc = provider.wrap_into(CCIPReadMiddleware::new);
+                _backend_permit = permit;
+
+                &backend_rpc
+            }
+            None => rpc,
+        };
+
+        verify_primary_name(
+            rpc,
+            &name,
+            address,
+            route.registry,
+            route.universal_resolver,
+        )
+        .await?;
+
         Ok(name)
     }
+
+    /// Reverse record of the address on the first backend that has one, with the
+    /// backend's own registry and reverse registrar
+    async fn backend_reverse_name(&self, address: &Address) -> Result<String, ReverseResolveError> {
+        for backend in self.routing.backends() {
+            // the lookup already holds its slot, only the backend's provider is waited for
+            let (provider, _permit) = backend.rpc.lease().await;
+
+            match reverse_name(
+                &provider,
+                address,
+                &backend.registry,
+                backend.reverse_registrar.as_ref(),
+            )
+            .await
+            {
+                Err(ReverseResolveError::MissingPrimaryName) => continue,
+                result => return result,
+            }
+        }
+
+        Err(ReverseResolveError::MissingPrimaryName)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::providers::{Http, Provider};
+    use ethers_core::abi::{self, Token};
+    use ethers_core::types::{Address, H160, H256};
+
+    use crate::core::routing::{Backend, Routing};
+    use crate::core::testing::{rpc, service};
+    use crate::utils::factory::SimpleFactory;
+
+    const BACKEND_REGISTRY: H160 = H160::repeat_byte(4);
+    const BACKEND_REVERSE_REGISTRAR: H160 = H160::repeat_byte(5);
+    const BACKEND_RESOLVER: H160 = H160::repeat_byte(6);
+    const REVERSE_NODE: H256 = H256::repeat_byte(7);
+
+    #[tokio::test]
+    async fn test_backend_reverse_name() {
+        // the backend's reverse registrar knows the reverse node, whose resolver has the name
+        let backend_url = rpc(|to, data| match to {
+            BACKEND_REVERSE_REGISTRAR => Some(abi::encode(&[Token::FixedBytes(
+                REVERSE_NODE.as_bytes().to_vec(),
+            )])),
+            BACKEND_REGISTRY if data.ends_with(REVERSE_NODE.as_bytes()) => {
+                Some(abi::encode(&[Token::Address(BACKEND_RESOLVER)]))
+            }
+            BACKEND_REGISTRY => Some(abi::encode(&[Token::Address(H160::zero())])),
+            BACKEND_RESOLVER => Some(abi::encode(&[Token::String("luc.gno".to_string())])),
+            _ => None,
+        })
+        .await;
+
+        let provider = Provider::<Http>::try_from(backend_url.as_str()).unwrap();
+
+        let mut service = service("http://localhost:8545");
+        service.routing = Routing::new([(
+            "gno".to_string(),
+            Backend {
+                name: "gnosis".to_string(),
+                rpc: Box::new(SimpleFactory::from(Arc::new(provider))),
+                universal_resolver: None,
+                registry: BACKEND_REGISTRY,
+                reverse_registrar: Some(BACKEND_REVERSE_REGISTRAR),
+                namespace: "gnosis:".to_string(),
+            },
+        )]);
+
+        let address = Address::repeat_byte(8);
+
+        assert_eq!(
+            service.backend_reverse_name(&address).await.unwrap(),
+            "luc.gno"
+        );
+    }
 }
@@ -1,9 +1,12 @@
 use crate::core::address::AddressResolveError;
+use crate::limiter::LimitError;
 use ethers::prelude::{Provider, ProviderError};
 use ethers::providers::Http;
 use ethers_ccip_read::CCIPReadMiddlewareError;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum ProfileError {
@@ -19,12 +22,21 @@ pub enum ProfileError {
     #[error("CCIP error: {0}")]
     CCIPError(#[from] CCIPReadMiddlewareError<Arc<Provider<Http>>>),
 
+    #[error("Invalid name")]
+    InvalidName,
+
     #[error("DNS encode error: {0}")]
     DNSEncodeError(String),
 
     #[error("Implementation error: {0}")]
     ImplementationError(String),
 
+    #[error("{0}")]
+    Overloaded(#[from] LimitError),
+
+    #[error("Timed out")]
+    Timeout,
+
     #[error("Other: {0}")]
     Other(String),
 }
@@ -34,3 +46,35 @@ impl AsRef<ProfileError> for ProfileError {
         self
     }
 }
+
+/// Why a record is missing from a profile, as listed in `Profile.error_codes`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RecordError {
+    pub code: RecordErrorCode,
+    #[schema(example = "CCIP resolution error (500): Internal Server Error")]
+    pub message: String,
+    /// HTTP status returned by the CCIP gateway
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RecordErrorCode {
+    /// The resolver reverted, e.g. with `Error(string)`
+    ResolverReverted,
+    /// The universal resolver reported a `ResolverError`
+    ResolverError,
+    /// The offchain (CCIP-Read) gateway failed, see `status`
+    CcipGatewayError,
+    /// The record's value couldn't be decoded
+    DecodeFailed,
+    /// The record's value is in a format enstate doesn't support
+    Unsupported,
+    /// Metadata the record points to, e.g. of an NFT avatar, couldn't be fetched
+    MetadataFailed,
+    /// The lookup's deadline or the call timeout passed before the record arrived
+    Timeout,
+    /// The RPC failed to answer the record's call
+    RpcUnavailable,
+}
@@ -1,6 +1,8 @@
 use ethers::prelude::Address;
 use thiserror::Error;
 
+use crate::utils::name::decode_label;
+
 #[derive(Debug)]
 pub enum LookupInfo {
     Name(String),
@@ -25,6 +27,14 @@ impl LookupInfo {
             return Err(NameParseError::InvalidNameFormat);
         }
 
+        // encoded labels (`[<labelhash>]`) go straight into the namehash, so they should be valid
+        if name_or_address
+            .split('.')
+            .any(|label| label.starts_with('[') && decode_label(label).is_none())
+        {
+            return Err(NameParseError::InvalidNameFormat);
+        }
+
         Ok(LookupInfo::Name(name_or_address.to_lowercase()))
     }
 }
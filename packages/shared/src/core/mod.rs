@@ -1,25 +1,54 @@
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use ethers::prelude::Http;
 use ethers::providers::Provider;
 use ethers_ccip_read::CCIPReadMiddleware;
-use ethers_core::types::H160;
+use ethers_core::types::{H160, H256};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
+use crate::core::error::RecordError;
+use crate::limiter::{LimitError, Permit};
 use crate::models::multicoin::cointype::coins::CoinType;
+use crate::popularity::PopularityKind;
+use crate::safety::SafetyReport;
 use crate::utils::eip55::EIP55Address;
 use crate::utils::factory::Factory;
+use crate::utils::name::{decode_label, labelhash};
 
 pub mod address;
 pub mod error;
 pub mod lookup_data;
 pub mod profile;
 pub mod records;
+pub mod resolver;
 pub mod resolvers;
+pub mod routing;
+#[cfg(test)]
+mod testing;
 
 pub type CCIPProvider = CCIPReadMiddleware<Arc<Provider<Http>>>;
 
+/// Provider of a lookup, holding its slots until dropped
+pub(crate) struct Lease {
+    pub provider: Arc<Provider<Http>>,
+    _permits: [Option<Permit>; 2],
+}
+
+impl Lease {
+    /// Waits for a provider of `rpc` with a spare slot, for a lookup holding `permit`
+    pub(crate) async fn of(rpc: &dyn Factory<Arc<Provider<Http>>>, permit: Option<Permit>) -> Self {
+        let (provider, provider_permit) = rpc.lease().await;
+
+        Self {
+            provider,
+            _permits: [permit, provider_permit],
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
     // Name
@@ -48,20 +77,167 @@ pub struct Profile {
     pub resolver: EIP55Address,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub ccip_urls: Vec<String>,
+    // Backend the name was resolved through, unset for the default one
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub backend: Option<String>,
     // Errors encountered while fetching & decoding
     pub errors: BTreeMap<String, String>,
+    // The same errors with a machine-readable code
+    #[serde(default)]
+    pub error_codes: BTreeMap<String, RecordError>,
+    // Some records timed out or their call failed and are missing, their errors say which
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub partial: bool,
+    // Whether the name can be mistaken for another, not cached
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub safety: Option<SafetyReport>,
+    // Unix timestamp (seconds) the cached profile expires at, unknown for legacy entries
+    #[serde(skip)]
+    pub expires_at: Option<u64>,
+}
+
+/// Settings of an [`ENSService`] that can change between lookups
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub profile_records: Arc<[String]>,
+    pub profile_chains: Arc<[CoinType]>,
+    /// Leave records of reverting resolvers and failing gateways unset, rather than reporting them
+    pub lenient_records: bool,
+    /// Time a lookup may spend resolving before it's returned with the records that arrived
+    pub lookup_timeout: Option<Duration>,
+    /// Time a single resolver call may take, as a whole: the `eth_call` and every offchain
+    /// (CCIP-Read) gateway request it makes
+    pub call_timeout: Option<Duration>,
+    pub max_bulk_size: usize,
+    pub cache_ttl: Option<u32>,
+    /// TTL of partial profiles, so they're completed soon
+    pub partial_cache_ttl: u32,
 }
 
 pub struct ENSService {
     pub cache: Box<dyn crate::cache::CacheLayer>,
+    /// Aggregates universal resolver calls of concurrent lookups
+    pub batcher: Option<Box<dyn crate::batch::CallBatcher>>,
+    /// Bounds the number of lookups resolving from chain at once
+    pub limiter: Option<Box<dyn crate::limiter::Limiter>>,
     pub discovery: Option<Box<dyn crate::discovery::Discovery>>,
+    /// Heals encoded labels (`[<labelhash>]`) of names
+    pub healer: Option<Box<dyn crate::healer::LabelHealer>>,
+    pub observer: Option<Box<dyn crate::observer::Observer>>,
+    pub popularity: Option<Box<dyn crate::popularity::Popularity>>,
+    /// Names lookalikes are reported for in the safety report
+    pub protected_names: Option<Box<dyn crate::safety::ProtectedNames>>,
     pub rpc: Box<dyn Factory<Arc<Provider<Http>>>>,
     pub opensea_api_key: String,
     pub ipfs_gateway: String,
     pub arweave_gateway: String,
-    pub profile_records: Arc<[String]>,
-    pub profile_chains: Arc<[CoinType]>,
-    pub universal_resolver: H160,
-    pub max_bulk_size: usize,
-    pub cache_ttl: Option<u32>,
+    /// Add a [`crate::safety::SafetyReport`] to profiles
+    pub safety: bool,
+    /// Without a universal resolver names are resolved by walking the registry,
+    /// see [`crate::core::resolvers::resolve`]
+    pub universal_resolver: Option<H160>,
+    /// ENS registry resolvers are looked up in, see [`crate::core::resolvers::registry::ENS_REGISTRY`]
+    pub registry: H160,
+    /// Reverse registrar asked for the reverse node of addresses, see
+    /// [`crate::core::resolvers::reverse::reverse_node`]
+    pub reverse_registrar: Option<H160>,
+    /// Backends the names under some suffixes are resolved on instead
+    pub routing: crate::core::routing::Routing,
+    /// Settings that can be reloaded while the service runs, see [`ENSService::reload`]
+    pub settings: RwLock<Arc<Settings>>,
+    /// Deflate cached profiles, trading a little CPU for memory
+    pub cache_compression: bool,
+    /// Also cache which entry belongs to which node, see [`crate::cache::node_key`]
+    pub cache_node_index: bool,
+}
+
+impl ENSService {
+    /// Settings of the lookups starting now, a lookup keeps the ones it started with
+    pub fn settings(&self) -> Arc<Settings> {
+        self.settings
+            .read()
+            .expect("settings lock should not be poisoned")
+            .clone()
+    }
+
+    /// Replaces the settings of the lookups starting from now
+    pub fn reload(&self, settings: Settings) {
+        *self
+            .settings
+            .write()
+            .expect("settings lock should not be poisoned") = Arc::new(settings);
+    }
+
+    pub(crate) fn observe(&self, event: impl FnOnce(&dyn crate::observer::Observer)) {
+        if let Some(observer) = &self.observer {
+            event(observer.as_ref());
+        }
+    }
+
+    pub fn heal_label(&self, hash: &H256) -> Option<String> {
+        self.healer
+            .as_ref()?
+            .heal(hash)
+            // a label from a corrupt table would silently change the name
+            .filter(|label| !label.contains('.') && labelhash(label) == *hash)
+    }
+
+    /// Replaces the encoded labels of a name with their label where known,
+    /// the healed name has the same namehash
+    pub fn heal_name(&self, name: &str) -> String {
+        if self.healer.is_none() || !name.contains('[') {
+            return name.to_string();
+        }
+
+        name.split('.')
+            .map(|label| {
+                decode_label(label)
+                    .and_then(|hash| self.heal_label(&hash))
+                    .unwrap_or_else(|| label.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Waits for a slot to resolve from chain
+    pub(crate) async fn acquire(&self) -> Result<Option<Permit>, LimitError> {
+        match &self.limiter {
+            Some(limiter) => limiter.acquire().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Waits for a slot to resolve from chain, then for a provider of `rpc` with a spare one
+    pub(crate) async fn lease(
+        &self,
+        rpc: &dyn Factory<Arc<Provider<Http>>>,
+    ) -> Result<Lease, LimitError> {
+        let permit = self.acquire().await?;
+
+        Ok(Lease::of(rpc, permit).await)
+    }
+
+    /// Indexes `cache_key` under the node, in the cache `namespace` of the backend it's resolved on
+    pub(crate) async fn index_node(&self, namespace: &str, node: H256, cache_key: &str, ttl: u32) {
+        if !self.cache_node_index {
+            return;
+        }
+
+        let index_key = format!("{namespace}{}", crate::cache::node_key(&node));
+
+        if let Err(err) = self.cache.set(&index_key, cache_key.as_bytes(), ttl).await {
+            warn!(cache_key, "failed to index node: {err}");
+        }
+    }
+
+    pub(crate) async fn record_hit(&self, kind: PopularityKind, key: &str) {
+        let Some(popularity) = &self.popularity else {
+            return;
+        };
+
+        // popularity is best effort, it should never fail a lookup
+        if let Err(err) = popularity.record_hit(kind, key).await {
+            warn!(key, "failed to record {} hit: {err}", kind.as_str());
+        }
+    }
 }
@@ -1,16 +1,48 @@
 use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 
-use ethers::prelude::{Middleware, MiddlewareBuilder};
-use ethers_ccip_read::CCIPReadMiddleware;
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 
-use crate::cache::CacheError;
-use crate::core::error::ProfileError;
+use crate::cache::envelope::{self, EnvelopeError};
+use crate::core::address::AddressResolveError;
+use crate::core::error::{ProfileError, RecordError};
 use crate::core::lookup_data::LookupInfo;
-use crate::core::{ENSService, Profile};
+use crate::core::{ENSService, Profile, Settings};
 use crate::models::lookup::ENSLookup;
+use crate::popularity::PopularityKind;
+use crate::safety::SafetyReport;
 use crate::utils::eip55::EIP55Address;
+use crate::utils::name::namehash;
+
+impl Settings {
+    /// Records resolved for a [`Profile`]
+    pub fn profile_calldata(&self) -> Vec<ENSLookup> {
+        // Preset Hardcoded Lookups
+        let mut calldata: HashSet<ENSLookup> = HashSet::from([
+            ENSLookup::Addr,
+            ENSLookup::StaticImage("avatar"),
+            ENSLookup::StaticImage("header"),
+            ENSLookup::StaticText("display"),
+            ENSLookup::ContentHash,
+        ]);
+
+        calldata.extend(self.profile_records.iter().cloned().map(ENSLookup::Text));
+        calldata.extend(
+            self.profile_chains
+                .iter()
+                .cloned()
+                .map(ENSLookup::Multicoin),
+        );
+
+        Vec::from_iter(calldata)
+    }
+}
+
+/// Version of the cached [`Profile`] encoding.
+///
+/// Bump whenever `Profile` changes in a way older cached entries can't be decoded into,
+/// entries with any other version are skipped and re-resolved.
+pub const PROFILE_SCHEMA_VERSION: u16 = 3;
 
 impl ENSService {
     #[instrument(skip(self))]
@@ -24,20 +56,38 @@ impl ENSService {
             LookupInfo::Address(address) => self
                 .primary_from_address(&address, fresh)
                 .await
-                .map_err(|_| ProfileError::NotFound)?,
+                .map_err(|err| match err {
+                    AddressResolveError::Overloaded(err) => ProfileError::Overloaded(err),
+                    err => ProfileError::AddressResolveError(err),
+                })?,
         };
 
-        let cache_key = format!("n:{name}");
+        self.record_hit(PopularityKind::Name, &name).await;
 
-        let rpc = self.rpc.get_instance();
+        self.resolve_name_profile(name, fresh).await
+    }
+
+    /// Records resolved for a [`Profile`]
+    pub fn profile_calldata(&self) -> Vec<ENSLookup> {
+        self.settings().profile_calldata()
+    }
 
-        let rpc = rpc.wrap_into(CCIPReadMiddleware::new);
+    /// Resolves the profile of a normalized name without counting the lookup
+    /// towards its popularity, e.g. for background refreshes.
+    #[instrument(skip(self))]
+    pub async fn resolve_name_profile(
+        &self,
+        name: String,
+        fresh: bool,
+    ) -> Result<Profile, ProfileError> {
+        // same namehash, but readable and cached once
+        let name = self.heal_name(&name);
+        let cache_key = self.profile_key(&name);
 
         info!(
             name = name,
             cache_key = cache_key,
             fresh = fresh,
-            rpc_url = rpc.inner().url().to_string(),
             "Looking up profile for {name}..."
         );
 
@@ -48,18 +98,12 @@ impl ENSService {
                     return Err(ProfileError::NotFound);
                 }
 
-                let entry_result: Result<Profile, _> = serde_json::from_str(value.as_str());
-                if let Ok(entry) = entry_result {
-                    // TODO: +1 on cache hit popularity
-                    return Ok(entry);
+                if let Some(entry) = self.decode_cached_profile(&cache_key, &value).await {
+                    return Ok(self.with_safety(entry));
                 }
-                // TODO: Else, warn about unparsable data in cache
             }
         }
 
-        // Preset Hardcoded Lookups
-        let mut calldata: HashSet<ENSLookup> = HashSet::new();
-
         let (addr_key, avatar_key, header_key, display_key, contenthash_key) = (
             ENSLookup::Addr,
             ENSLookup::StaticImage("avatar"),
@@ -68,26 +112,12 @@ impl ENSService {
             ENSLookup::ContentHash,
         );
 
-        calldata.extend([
-            addr_key.clone(),
-            avatar_key.clone(),
-            header_key.clone(),
-            display_key.clone(),
-            contenthash_key.clone(),
-        ]);
-
-        calldata.extend(self.profile_records.iter().cloned().map(ENSLookup::Text));
-        calldata.extend(
-            self.profile_chains
-                .iter()
-                .cloned()
-                .map(ENSLookup::Multicoin),
-        );
+        let settings = self.settings();
 
         let resolved = self
             .resolve_records(
                 LookupInfo::Name(name.to_string()),
-                &Vec::from_iter(calldata),
+                &settings.profile_calldata(),
                 fresh,
             )
             .await?;
@@ -112,7 +142,7 @@ impl ENSService {
 
         let contenthash = resolved.records.get(&contenthash_key).cloned();
 
-        let records: BTreeMap<String, String> = self
+        let records: BTreeMap<String, String> = settings
             .profile_records
             .iter()
             .filter_map(|record| {
@@ -123,7 +153,7 @@ impl ENSService {
             })
             .collect();
 
-        let chains: BTreeMap<String, String> = self
+        let chains: BTreeMap<String, String> = settings
             .profile_chains
             .iter()
             .filter_map(|coin_type| {
@@ -146,26 +176,116 @@ impl ENSService {
             fresh: chrono::offset::Utc::now().timestamp_millis(),
             resolver: EIP55Address(resolved.resolver),
             ccip_urls: resolved.ccip_urls,
+            backend: resolved.backend,
             errors: resolved
                 .invalid
                 .iter()
                 .map(|(key, value)| (key.name(), value.to_string()))
                 .collect(),
+            error_codes: resolved
+                .invalid
+                .iter()
+                .map(|(key, value)| (key.name(), RecordError::from(value)))
+                .collect(),
+            partial: resolved.partial,
+            safety: None,
+            expires_at: None,
         };
 
-        // Update metrics
-        // TODO: +1 on cache hit popularity discover
+        // records missing from a partial profile aren't changes
+        if !value.partial {
+            self.observe(|observer| observer.profile_resolved(&value));
+        }
+
+        let ttl = settings.cache_ttl.unwrap_or(600);
+        let ttl = if value.partial {
+            ttl.min(settings.partial_cache_ttl)
+        } else {
+            ttl
+        };
 
         let response =
-            serde_json::to_string(&value).map_err(|err| ProfileError::Other(err.to_string()))?;
+            envelope::encode(&value, PROFILE_SCHEMA_VERSION, self.cache_compression, ttl)
+                .map_err(|err| ProfileError::Other(err.to_string()))?;
 
-        self.cache
-            .set(&cache_key, &response, self.cache_ttl.unwrap_or(600))
-            .await
-            .map_err(|CacheError::Other(err)| {
-                ProfileError::Other(format!("cache set failed: {}", err))
-            })?;
+        let value = Profile {
+            expires_at: envelope::decode_header(&response)
+                .ok()
+                .map(|(header, _)| header.expires_at),
+            ..value
+        };
+
+        // A failing cache should degrade performance, not availability
+        if let Err(err) = self.cache.set(&cache_key, &response, ttl).await {
+            warn!(name = name, "failed to cache profile: {err}");
+        } else {
+            let namespace = self.route(&name).namespace;
+
+            self.index_node(namespace, namehash(&name), &cache_key, ttl)
+                .await;
+        }
+
+        Ok(self.with_safety(value))
+    }
+
+    /// Adds the safety report, which isn't cached as the protected names change
+    fn with_safety(&self, mut profile: Profile) -> Profile {
+        if self.safety {
+            profile.safety = Some(SafetyReport::of(
+                &profile.name,
+                self.protected_names.as_deref(),
+            ));
+        }
+
+        profile
+    }
+
+    /// Decodes a cached profile, treating undecodable entries as a miss.
+    ///
+    /// Entries from before the versioned envelope (plain JSON) are rewritten
+    /// in the current encoding, keeping their remaining TTL.
+    async fn decode_cached_profile(&self, cache_key: &str, value: &[u8]) -> Option<Profile> {
+        let error = match envelope::decode::<Profile>(value, PROFILE_SCHEMA_VERSION) {
+            Ok((header, profile)) => {
+                return Some(Profile {
+                    expires_at: Some(header.expires_at),
+                    ..profile
+                })
+            }
+            Err(EnvelopeError::NotAnEnvelope) => match serde_json::from_slice::<Profile>(value) {
+                Ok(profile) => {
+                    self.migrate_cached_profile(cache_key, &profile).await;
+
+                    return Some(profile);
+                }
+                Err(err) => EnvelopeError::Decode(err.to_string()),
+            },
+            Err(err) => err,
+        };
+
+        warn!(cache_key, "skipping undecodable cached profile: {error}");
+        self.observe(|observer| observer.cache_decode_failed(cache_key, &error));
+
+        None
+    }
+
+    async fn migrate_cached_profile(&self, cache_key: &str, profile: &Profile) {
+        let Ok(Some(ttl)) = self.cache.ttl(cache_key).await else {
+            return;
+        };
+
+        let Ok(encoded) =
+            envelope::encode(profile, PROFILE_SCHEMA_VERSION, self.cache_compression, ttl)
+        else {
+            return;
+        };
+
+        if let Err(err) = self.cache.set(cache_key, &encoded, ttl).await {
+            warn!(cache_key, "failed to migrate cached profile: {err}");
+
+            return;
+        }
 
-        Ok(value)
+        self.observe(|observer| observer.cache_migrated(cache_key));
     }
 }
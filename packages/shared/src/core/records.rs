@@ -4,20 +4,35 @@ use std::sync::Arc;
 use ethers::middleware::MiddlewareBuilder;
 use ethers::prelude::Address;
 use ethers_ccip_read::CCIPReadMiddleware;
-use tracing::instrument;
+use tracing::{debug, instrument};
 
 use crate::core::error::ProfileError;
 use crate::core::lookup_data::LookupInfo;
-use crate::core::ENSService;
+use crate::core::{ENSService, Lease};
+use crate::models::lookup::revert::decode_revert;
 use crate::models::lookup::{ENSLookup, ENSLookupError, LookupState};
+use crate::utils::deadline::{self, Deadline};
 
-use super::resolvers::universal::resolve_universal;
+use super::resolvers::registry::find_closest_resolver;
+use super::resolvers::resolve;
+use super::resolvers::universal::{resolve_batched, CallError, UniversalResolverResult};
+
+/// ENS CCIP unwrapper is limited to 50 sub-requests, i.e. per request
+pub const MAX_RECORDS_PER_CALL: usize = 50;
 
 pub struct ResolvedCalldata {
     pub resolver: Address,
     pub ccip_urls: Vec<String>,
     pub records: HashMap<ENSLookup, String>,
     pub invalid: HashMap<ENSLookup, ENSLookupError>,
+    /// Some records timed out or their call failed, see [`ENSLookupError::is_transient`]
+    pub partial: bool,
+    /// Undecoded result of every record that didn't time out
+    pub raw: Vec<(ENSLookup, UniversalResolverResult)>,
+    /// RPC url the records were resolved through
+    pub rpc_url: String,
+    /// Backend the records were resolved through, see [`crate::core::routing::Routing`]
+    pub backend: Option<String>,
 }
 
 impl ENSService {
@@ -36,8 +51,41 @@ impl ENSService {
 
         // let cache_key = format!("n:{name}");
 
-        let rpc = self.rpc.get_instance();
-        let rpc = rpc.wrap_into(CCIPReadMiddleware::new);
+        let route = self.route(&name);
+        let settings = self.settings();
+
+        let mut deadline = Deadline::after(settings.lookup_timeout);
+
+        let chunks: Vec<_> = calldata.chunks(MAX_RECORDS_PER_CALL).collect();
+
+        let permit = self.acquire().await?;
+
+        // a batch takes a provider slot of its own, see [`crate::batch::CallBatcher`],
+        // so a batched lookup only takes one once its calls are in, not while waiting on them
+        let mut batched = vec![None; chunks.len()];
+
+        if let (Some(batcher), Some(universal_resolver)) = (route.batcher, route.universal_resolver)
+        {
+            for (chunk, batched) in chunks.iter().zip(&mut batched) {
+                match deadline
+                    .run(resolve_batched(&name, chunk, universal_resolver, batcher))
+                    .await
+                {
+                    Some(Ok(res)) => *batched = res,
+                    Some(Err(err)) => return Err(err),
+                    // the chunks left time out below
+                    None => break,
+                }
+            }
+        }
+
+        // held until the records are decoded, which may fetch offchain metadata too
+        let lease = Lease::of(route.rpc, permit).await;
+
+        let rpc_url = lease.provider.url().to_string();
+        debug!(%name, %rpc_url, backend = route.backend, "resolving records");
+
+        let rpc = lease.provider.clone().wrap_into(CCIPReadMiddleware::new);
 
         // If the value is in the cache, return it
         // if !fresh {
@@ -55,22 +103,50 @@ impl ENSService {
 
         let rpc = Arc::new(rpc);
 
-        // ENS CCIP unwrapper is limited to 50 sub-requests, i.e. per request
         let mut resolves = Vec::new();
+        let mut timed_out: Vec<&ENSLookup> = Vec::new();
 
         // TODO: make 50 chunk size configurable later
-        for chunk in calldata.chunks(50) {
-            resolves.push(resolve_universal(&name, chunk, &rpc, &self.universal_resolver).await?);
+        for (chunk, batched) in chunks.into_iter().zip(batched) {
+            let resolve = resolve(
+                &name,
+                chunk,
+                &rpc,
+                route.universal_resolver,
+                route.registry,
+                batched,
+                settings.call_timeout,
+            );
+
+            match deadline.run(resolve).await {
+                Some(Ok(resolved)) => resolves.push((chunk, resolved)),
+                Some(Err(ProfileError::Timeout)) | None => timed_out.extend(chunk),
+                Some(Err(err)) => return Err(err),
+            }
         }
 
-        let Some((_, resolver, ccip_urls)) = resolves.first() else {
-            return Err(ProfileError::ImplementationError(String::new()));
-        };
+        let (resolver, ccip_urls) = match resolves.first() {
+            Some((_, (_, resolver, ccip_urls))) => (*resolver, ccip_urls.clone()),
+            None if timed_out.is_empty() => {
+                return Err(ProfileError::ImplementationError(String::new()))
+            }
+            // every record timed out, the resolver is still reported with them, bounded by
+            // the call timeout as the lookup's deadline has likely passed
+            None => {
+                let closest = deadline::timeout(
+                    settings.call_timeout,
+                    find_closest_resolver(&lease.provider, route.registry, &name),
+                )
+                .await
+                .ok_or(ProfileError::Timeout)??;
+
+                let Some((_, resolver)) = closest else {
+                    return Err(ProfileError::NotFound);
+                };
 
-        let data = resolves
-            .iter()
-            .flat_map(|(data, _, _)| data)
-            .collect::<Vec<_>>();
+                (resolver, Vec::new())
+            }
+        };
 
         let mut results: HashMap<ENSLookup, String> = HashMap::new();
         let mut errors: HashMap<ENSLookup, ENSLookupError> = HashMap::default();
@@ -82,34 +158,62 @@ impl ENSService {
             arweave_gateway: self.arweave_gateway.clone(),
         };
 
-        // Assume results & calldata have the same length
-        // Look through all calldata and decode the results at the same index
-        for (index, calldata) in calldata.iter().enumerate() {
-            let res = data[index];
-            // TODO: think about this
-            //  current behaviour ignores all errors from a resolver
-            let result = if res.success {
-                calldata.decode(&res.data, &lookup_state).await
-            } else {
-                Ok(String::new())
-            };
-
-            match result {
-                Ok(result) if !result.is_empty() => {
-                    results.insert(calldata.clone(), result);
-                }
-                Err(error) if !matches!(error, ENSLookupError::CCIPError { .. }) => {
-                    errors.insert(calldata.clone(), error);
+        // Results & calldata of a chunk have the same length,
+        // decode the results at the same index
+        let mut raw = Vec::with_capacity(calldata.len());
+
+        for (chunk, (data, _, _)) in resolves {
+            for (calldata, res) in chunk.iter().zip(data) {
+                let result = if res.success {
+                    // decoding may fetch offchain metadata, e.g. of an NFT avatar
+                    deadline
+                        .run(calldata.decode(&res.data, &lookup_state))
+                        .await
+                        .unwrap_or(Err(ENSLookupError::Timeout))
+                } else if let Some(error) = &res.error {
+                    Err(match error {
+                        CallError::Gateway(message) => ENSLookupError::CCIPError {
+                            status: None,
+                            message: message.clone(),
+                        },
+                        CallError::Timeout => ENSLookupError::Timeout,
+                        CallError::Rpc(message) => ENSLookupError::RPCError(message.clone()),
+                    })
+                } else {
+                    decode_revert(&res.data).map_or_else(|| Ok(String::new()), Err)
+                };
+
+                raw.push((calldata.clone(), res));
+
+                match result {
+                    Ok(result) if !result.is_empty() => {
+                        results.insert(calldata.clone(), result);
+                    }
+                    Ok(_) => {}
+                    // lenient lookups report failing resolvers and gateways as unset records
+                    Err(error) if settings.lenient_records && error.is_resolver_failure() => {}
+                    Err(error) => {
+                        errors.insert(calldata.clone(), error);
+                    }
                 }
-                _ => {}
             }
         }
 
+        for calldata in timed_out {
+            errors.insert(calldata.clone(), ENSLookupError::Timeout);
+        }
+
+        let partial = errors.values().any(ENSLookupError::is_transient);
+
         let value = ResolvedCalldata {
-            resolver: *resolver,
-            ccip_urls: ccip_urls.clone(),
+            resolver,
+            ccip_urls,
             records: results,
             invalid: errors,
+            partial,
+            raw,
+            rpc_url,
+            backend: route.backend.map(ToString::to_string),
         };
 
         // let response = serde_json::to_string(&value)
@@ -145,3 +249,42 @@ impl ENSService {
         Ok(record.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::abi::{self, Token};
+    use ethers_core::types::H160;
+
+    use crate::core::lookup_data::LookupInfo;
+    use crate::core::testing::{rpc, service, REGISTRY};
+    use crate::models::lookup::{ENSLookup, ENSLookupError};
+
+    const RESOLVER: H160 = H160::repeat_byte(3);
+
+    #[tokio::test]
+    async fn test_timed_out_records() {
+        // the universal resolver never answers, every name's resolver in the registry is `RESOLVER`
+        let rpc = rpc(|to, _| (to == REGISTRY).then(|| abi::encode(&[Token::Address(RESOLVER)])));
+        let service = service(&rpc.await);
+
+        let calldata = [ENSLookup::Addr, ENSLookup::Text("com.github".to_string())];
+
+        let resolved = service
+            .resolve_records(LookupInfo::Name("luc.eth".to_string()), &calldata, false)
+            .await
+            .unwrap();
+
+        // the records are missing, the resolver is found in the registry instead
+        assert!(resolved.partial);
+        assert_eq!(resolved.resolver, RESOLVER);
+        assert!(resolved.records.is_empty());
+        assert!(resolved.raw.is_empty());
+
+        for calldata in &calldata {
+            assert!(matches!(
+                resolved.invalid.get(calldata),
+                Some(ENSLookupError::Timeout)
+            ));
+        }
+    }
+}
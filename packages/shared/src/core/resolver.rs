@@ -0,0 +1,160 @@
+use ethers::middleware::MiddlewareBuilder;
+use ethers::prelude::Address;
+use ethers_ccip_read::CCIPReadMiddleware;
+use futures::future::join;
+use hex_literal::hex;
+use serde::Serialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::core::error::ProfileError;
+use crate::core::resolvers::interfaces::ResolverInterfaces;
+use crate::core::resolvers::registry::find_closest_resolver;
+use crate::core::resolvers::resolve;
+use crate::core::resolvers::universal::OFFCHAIN_DNS_RESOLVER;
+use crate::core::ENSService;
+use crate::utils::eip55::EIP55Address;
+
+// Known mainnet PublicResolver deployments, newest first
+const PUBLIC_RESOLVERS: [([u8; 20], &str); 5] = [
+    (hex!("231b0Ee14048e9dCcD1d247744d114a4EB5E8E63"), "v3"),
+    (hex!("4976fb03C32e5B8cfe2b6cCB31c09Ba78EBaBa41"), "v2"),
+    (hex!("DaaF96c344f63131acadD0Ea35170E7892d3dfBA"), "v1"),
+    (hex!("226159d592E2b063810a10Ebf6dcbADA94Ed68b8"), "legacy"),
+    (hex!("1da022710dF5002339274AaDEe8D58218e9D6AB5"), "legacy"),
+];
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResolverKind {
+    /// One of the ENS PublicResolver deployments, `v3` being the current one
+    PublicResolver {
+        version: String,
+    },
+    /// Resolves DNS names from their DNS records
+    OffchainDns,
+    /// Answers through CCIP-Read gateways
+    Offchain,
+    Custom,
+}
+
+impl ResolverKind {
+    fn of(resolver: Address, offchain: bool) -> Self {
+        if let Some((_, version)) = PUBLIC_RESOLVERS
+            .iter()
+            .find(|(address, _)| Address::from(*address) == resolver)
+        {
+            return Self::PublicResolver {
+                version: (*version).to_string(),
+            };
+        }
+
+        if resolver == *OFFCHAIN_DNS_RESOLVER {
+            return Self::OffchainDns;
+        }
+
+        if offchain {
+            return Self::Offchain;
+        }
+
+        Self::Custom
+    }
+}
+
+/// Which resolver answers for a name, and what it's capable of
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ResolverInfo {
+    #[schema(example = "luc.eth")]
+    pub name: String,
+    #[schema(value_type = String, example = "0x231b0Ee14048e9dCcD1d247744d114a4EB5E8E63")]
+    pub resolver: EIP55Address,
+    /// Name the resolver is set on, a parent of `name` when found through wildcard resolution
+    #[schema(example = "luc.eth")]
+    pub resolver_name: String,
+    pub wildcard: bool,
+    pub kind: ResolverKind,
+    pub interfaces: ResolverInterfaces,
+    /// Gateways the name was resolved through
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ccip_urls: Vec<String>,
+    /// Backend the name is resolved on, for names routed to another registry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "gnosis")]
+    pub backend: Option<String>,
+}
+
+impl ENSService {
+    /// Finds the resolver of a normalized name the way ENSIP-10 does,
+    /// and checks which resolver interfaces it supports.
+    #[instrument(skip(self))]
+    pub async fn resolver_info(&self, name: String) -> Result<ResolverInfo, ProfileError> {
+        let route = self.route(&name);
+        let lease = self.lease(route.rpc).await?;
+        let rpc = &lease.provider;
+
+        let Some((resolver_name, resolver)) =
+            find_closest_resolver(rpc, route.registry, &name).await?
+        else {
+            return Err(ProfileError::NotFound);
+        };
+
+        let ccip = rpc.clone().wrap_into(CCIPReadMiddleware::new);
+
+        // a failing lookup still tells which resolver is set, just not whether it's offchain
+        let (interfaces, resolved) = join(
+            ResolverInterfaces::of(rpc, resolver),
+            resolve(
+                &name,
+                &[],
+                &ccip,
+                route.universal_resolver,
+                route.registry,
+                None,
+                self.settings().call_timeout,
+            ),
+        )
+        .await;
+
+        let ccip_urls = resolved
+            .map(|(_, _, ccip_urls)| ccip_urls)
+            .unwrap_or_default();
+
+        Ok(ResolverInfo {
+            wildcard: resolver_name != name,
+            kind: ResolverKind::of(resolver, !ccip_urls.is_empty()),
+            name,
+            resolver: EIP55Address(resolver),
+            resolver_name,
+            interfaces,
+            ccip_urls,
+            backend: route.backend.map(ToString::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::prelude::Address;
+
+    use super::ResolverKind;
+
+    #[test]
+    fn test_resolver_kind() {
+        let public_resolver = "0x231b0Ee14048e9dCcD1d247744d114a4EB5E8E63"
+            .parse::<Address>()
+            .unwrap();
+
+        assert!(matches!(
+            ResolverKind::of(public_resolver, false),
+            ResolverKind::PublicResolver { version } if version == "v3"
+        ));
+        assert!(matches!(
+            ResolverKind::of(Address::repeat_byte(1), true),
+            ResolverKind::Offchain
+        ));
+        assert!(matches!(
+            ResolverKind::of(Address::repeat_byte(1), false),
+            ResolverKind::Custom
+        ));
+    }
+}
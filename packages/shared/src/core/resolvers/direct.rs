@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use ethers::prelude::ProviderError::JsonRpcClientError;
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes};
+use ethers_ccip_read::{CCIPReadMiddlewareError, CCIPRequest};
+use ethers_core::abi::{self, ParamType, Token};
+use futures::future::join_all;
+use hex_literal::hex;
+use tracing::{debug, instrument};
+
+use crate::core::error::ProfileError;
+use crate::core::resolvers::interfaces::{supports_interface, EXTENDED_INTERFACE};
+use crate::core::resolvers::registry::find_closest_resolver;
+use crate::core::resolvers::universal::{urls_from_request, CallError, UniversalResolverResult};
+use crate::core::CCIPProvider;
+use crate::models::lookup::ENSLookup;
+use crate::utils::deadline;
+use crate::utils::dns::dns_encode;
+use crate::utils::name::namehash;
+use crate::utils::vec::dedup_ord;
+
+// multicall(bytes[])
+const MULTICALL_SELECTOR: [u8; 4] = hex!("ac9650d8");
+// resolve(bytes,bytes)
+const RESOLVE_SELECTOR: [u8; 4] = hex!("9061b923");
+
+/// Resolves `data` for `name` without a universal resolver, returning the same as
+/// [`super::universal::resolve_universal`].
+///
+/// The resolver is found by walking up the registry as ENSIP-10 does. Plain resolvers answer
+/// every record in a single `multicall`, or with a call per record when that reverts, so one
+/// failing record doesn't fail the others. Extended resolvers are called through
+/// `resolve(bytes,bytes)` per record, their offchain lookups (CCIP-Read) are followed by the
+/// provider. Every call, gateway requests included, times out after `call_timeout`; a
+/// record's own call that times out or fails is reported in its result, see [`CallError`].
+#[instrument(skip(provider))]
+pub async fn resolve_direct(
+    name: &str,
+    data: &[ENSLookup],
+    provider: &CCIPProvider,
+    registry: &Address,
+    call_timeout: Option<Duration>,
+) -> Result<(Vec<UniversalResolverResult>, Address, Vec<String>), ProfileError> {
+    let Some((resolver_name, resolver)) =
+        find_closest_resolver(provider.inner(), registry, name).await?
+    else {
+        return Err(ProfileError::NotFound);
+    };
+
+    let extended = supports_interface(provider.inner(), resolver, EXTENDED_INTERFACE).await;
+
+    // a resolver set on a parent can only answer for the name through ENSIP-10
+    if resolver_name != name && !extended {
+        return Err(ProfileError::NotFound);
+    }
+
+    let node = namehash(name);
+    let calldata: Vec<Vec<u8>> = data.iter().map(|lookup| lookup.calldata(&node)).collect();
+
+    let calls = if extended {
+        let dns_encoded = dns_encode(name).map_err(ProfileError::DNSEncodeError)?;
+
+        let calls = calldata.into_iter().map(|calldata| {
+            let call = [
+                RESOLVE_SELECTOR.as_slice(),
+                &abi::encode(&[Token::Bytes(dns_encoded.clone()), Token::Bytes(calldata)]),
+            ]
+            .concat();
+
+            call_record(provider, resolver, call, call_timeout)
+        });
+
+        join_all(calls)
+            .await
+            .into_iter()
+            .map(|(result, requests)| (unwrap_resolved(result), requests))
+            .collect()
+    } else {
+        multicall(provider, resolver, calldata, call_timeout).await?
+    };
+
+    let mut results = Vec::with_capacity(calls.len());
+    let mut ccip_urls = Vec::new();
+
+    for (result, requests) in calls {
+        results.push(result);
+        ccip_urls.extend(requests.iter().flat_map(urls_from_request));
+    }
+
+    Ok((results, resolver, dedup_ord(&ccip_urls)))
+}
+
+/// Calls every record in a single `multicall`, or on its own when that reverts,
+/// e.g. because the resolver doesn't implement `multicall` or one of the records reverted
+async fn multicall(
+    provider: &CCIPProvider,
+    resolver: Address,
+    calldata: Vec<Vec<u8>>,
+    call_timeout: Option<Duration>,
+) -> Result<Vec<(UniversalResolverResult, Vec<CCIPRequest>)>, ProfileError> {
+    let call = [
+        MULTICALL_SELECTOR.as_slice(),
+        &abi::encode(&[Token::Array(
+            calldata.iter().cloned().map(Token::Bytes).collect(),
+        )]),
+    ]
+    .concat();
+
+    let (result, mut requests) = call_resolver(provider, resolver, call, call_timeout).await?;
+
+    if result.success {
+        let decoded = abi::decode(
+            &[ParamType::Array(Box::new(ParamType::Bytes))],
+            &result.data,
+        )
+        .ok()
+        .and_then(|decoded| decoded.into_iter().next())
+        .and_then(Token::into_array)
+        .filter(|results| results.len() == calldata.len());
+
+        if let Some(results) = decoded {
+            return Ok(results
+                .into_iter()
+                .map(|result| {
+                    let data = result.into_bytes().unwrap_or_default();
+
+                    // the requests of the whole call, reported once
+                    (
+                        UniversalResolverResult {
+                            success: true,
+                            data,
+                            error: None,
+                        },
+                        std::mem::take(&mut requests),
+                    )
+                })
+                .collect());
+        }
+    }
+
+    debug!(%resolver, "multicall failed, calling every record on its own");
+
+    Ok(join_all(
+        calldata
+            .into_iter()
+            .map(|calldata| call_record(provider, resolver, calldata, call_timeout)),
+    )
+    .await)
+}
+
+/// Calls the resolver for a single record, a timed out or failed call is reported as that
+/// record's result so the other records are still resolved
+async fn call_record(
+    provider: &CCIPProvider,
+    resolver: Address,
+    data: Vec<u8>,
+    call_timeout: Option<Duration>,
+) -> (UniversalResolverResult, Vec<CCIPRequest>) {
+    call_resolver(provider, resolver, data, call_timeout)
+        .await
+        .unwrap_or_else(|err| {
+            debug!(%resolver, error = %err, "record call failed");
+
+            let error = match err {
+                ProfileError::Timeout => CallError::Timeout,
+                err => CallError::Rpc(err.to_string()),
+            };
+
+            (
+                UniversalResolverResult {
+                    success: false,
+                    data: Vec::new(),
+                    error: Some(error),
+                },
+                Vec::new(),
+            )
+        })
+}
+
+/// Calls the resolver, a revert is the result of the call rather than an error
+async fn call_resolver(
+    provider: &CCIPProvider,
+    resolver: Address,
+    data: Vec<u8>,
+    call_timeout: Option<Duration>,
+) -> Result<(UniversalResolverResult, Vec<CCIPRequest>), ProfileError> {
+    let mut transaction = TypedTransaction::default();
+
+    transaction.set_to(resolver);
+    transaction.set_data(Bytes::from(data));
+
+    let result = deadline::timeout(call_timeout, provider.call_ccip(&transaction, None))
+        .await
+        .ok_or(ProfileError::Timeout)?;
+
+    match result {
+        Ok((data, requests)) => Ok((
+            UniversalResolverResult {
+                success: true,
+                data: data.to_vec(),
+                error: None,
+            },
+            requests,
+        )),
+        Err(CCIPReadMiddlewareError::MiddlewareError(err)) => {
+            let revert = match &err {
+                JsonRpcClientError(rpc_err) => rpc_err
+                    .as_error_response()
+                    .and_then(|response| response.as_revert_data()),
+                _ => None,
+            };
+
+            match revert {
+                Some(data) => Ok((
+                    UniversalResolverResult {
+                        success: false,
+                        data: data.to_vec(),
+                        error: None,
+                    },
+                    Vec::new(),
+                )),
+                None => Err(ProfileError::RPCError(err)),
+            }
+        }
+        // the gateway's status isn't known here, only that the lookup failed
+        Err(err) => {
+            debug!(%resolver, error = %err, "offchain lookup failed");
+
+            Ok((
+                UniversalResolverResult {
+                    success: false,
+                    data: Vec::new(),
+                    error: Some(CallError::Gateway(err.to_string())),
+                },
+                Vec::new(),
+            ))
+        }
+    }
+}
+
+/// `resolve(bytes,bytes)` returns the record's result wrapped in `bytes`
+fn unwrap_resolved(result: UniversalResolverResult) -> UniversalResolverResult {
+    if !result.success {
+        return result;
+    }
+
+    match abi::decode(&[ParamType::Bytes], &result.data).as_deref() {
+        Ok([Token::Bytes(data)]) => UniversalResolverResult {
+            success: true,
+            data: data.clone(),
+            error: None,
+        },
+        _ => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::providers::{Http, Provider};
+    use ethers_ccip_read::CCIPReadMiddleware;
+    use ethers_core::abi::ParamType;
+    use ethers_core::types::Address;
+
+    use crate::core::resolvers::direct::resolve_direct;
+    use crate::core::resolvers::registry::ENS_REGISTRY;
+    use crate::models::lookup::ENSLookup;
+
+    #[tokio::test]
+    async fn test_resolve_direct() {
+        let provider = Provider::<Http>::try_from("https://rpc.ankr.com/eth").unwrap();
+
+        let (results, _, _) = resolve_direct(
+            "luc.eth",
+            &[ENSLookup::Addr, ENSLookup::StaticText("com.github")],
+            &CCIPReadMiddleware::new(Arc::new(provider)),
+            &ENS_REGISTRY,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let address = ethers_core::abi::decode(&[ParamType::Address], &results[0].data)
+            .unwrap()
+            .first()
+            .unwrap()
+            .clone()
+            .into_address()
+            .unwrap();
+
+        assert_eq!(
+            address,
+            "0x225f137127d9067788314bc7fcc1f36746a3c3B5"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert!(results[1].success);
+    }
+}
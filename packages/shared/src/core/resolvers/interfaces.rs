@@ -0,0 +1,97 @@
+use ethers::middleware::Middleware;
+use ethers::prelude::Address;
+use ethers::providers::{Http, Provider};
+use ethers_core::abi::{self, ParamType, Token};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
+use ethers_core::types::Bytes;
+use futures::future::join_all;
+use hex_literal::hex;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// supportsInterface(bytes4)
+const SUPPORTS_INTERFACE_SELECTOR: [u8; 4] = hex!("01ffc9a7");
+
+const ADDR_INTERFACE: [u8; 4] = hex!("3b3b57de");
+const MULTICOIN_INTERFACE: [u8; 4] = hex!("f1cb7e06");
+const TEXT_INTERFACE: [u8; 4] = hex!("59d1d43c");
+const CONTENTHASH_INTERFACE: [u8; 4] = hex!("bc1c58d1");
+const ABI_INTERFACE: [u8; 4] = hex!("2203ab56");
+const PUBKEY_INTERFACE: [u8; 4] = hex!("c8690233");
+const NAME_INTERFACE: [u8; 4] = hex!("691f3431");
+// ENSIP-10 resolve(bytes,bytes)
+pub(crate) const EXTENDED_INTERFACE: [u8; 4] = hex!("9061b923");
+
+/// Resolver interfaces a resolver reports to support through ERC-165
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ResolverInterfaces {
+    pub addr: bool,
+    pub multicoin: bool,
+    pub text: bool,
+    pub contenthash: bool,
+    pub abi: bool,
+    pub pubkey: bool,
+    pub name: bool,
+    /// ENSIP-10 `resolve(bytes,bytes)`, needed for wildcard and offchain resolution
+    pub extended: bool,
+}
+
+impl ResolverInterfaces {
+    pub async fn of(rpc: &Provider<Http>, resolver: Address) -> Self {
+        let supported = join_all(
+            [
+                ADDR_INTERFACE,
+                MULTICOIN_INTERFACE,
+                TEXT_INTERFACE,
+                CONTENTHASH_INTERFACE,
+                ABI_INTERFACE,
+                PUBKEY_INTERFACE,
+                NAME_INTERFACE,
+                EXTENDED_INTERFACE,
+            ]
+            .map(|interface| supports_interface(rpc, resolver, interface)),
+        )
+        .await;
+
+        let [addr, multicoin, text, contenthash, abi, pubkey, name, extended] = supported[..]
+        else {
+            unreachable!("every interface should've been checked");
+        };
+
+        Self {
+            addr,
+            multicoin,
+            text,
+            contenthash,
+            abi,
+            pubkey,
+            name,
+            extended,
+        }
+    }
+}
+
+/// Reverting and non-ERC-165 resolvers support nothing
+pub(crate) async fn supports_interface(
+    rpc: &Provider<Http>,
+    resolver: Address,
+    interface: [u8; 4],
+) -> bool {
+    let mut transaction = TypedTransaction::default();
+
+    transaction.set_to(resolver);
+
+    let encoded = abi::encode(&[Token::FixedBytes(interface.to_vec())]);
+    transaction.set_data(Bytes::from(
+        [&SUPPORTS_INTERFACE_SELECTOR, encoded.as_slice()].concat(),
+    ));
+
+    let Ok(res) = rpc.call(&transaction, None).await else {
+        return false;
+    };
+
+    matches!(
+        abi::decode(&[ParamType::Bool], &res).as_deref(),
+        Ok([Token::Bool(true)])
+    )
+}
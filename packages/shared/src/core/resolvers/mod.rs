@@ -1,2 +1,46 @@
+use std::time::Duration;
+
+use ethers::types::{Address, Bytes};
+
+use crate::core::error::ProfileError;
+use crate::core::CCIPProvider;
+use crate::models::lookup::ENSLookup;
+
+use self::direct::resolve_direct;
+use self::universal::{resolve_universal, UniversalResolverResult};
+
+pub mod direct;
+pub mod interfaces;
+pub mod registry;
 pub mod reverse;
 pub mod universal;
+
+/// Resolves `data` for `name` through the universal resolver or, without one, through the
+/// name's resolver found in `registry`, see [`resolve_direct`].
+///
+/// `batched` is the response of a batched universal resolver call, see
+/// [`universal::resolve_batched`].
+pub async fn resolve(
+    name: &str,
+    data: &[ENSLookup],
+    provider: &CCIPProvider,
+    universal_resolver: Option<&Address>,
+    registry: &Address,
+    batched: Option<Bytes>,
+    call_timeout: Option<Duration>,
+) -> Result<(Vec<UniversalResolverResult>, Address, Vec<String>), ProfileError> {
+    match universal_resolver {
+        Some(universal_resolver) => {
+            resolve_universal(
+                name,
+                data,
+                provider,
+                universal_resolver,
+                batched,
+                call_timeout,
+            )
+            .await
+        }
+        None => resolve_direct(name, data, provider, registry, call_timeout).await,
+    }
+}
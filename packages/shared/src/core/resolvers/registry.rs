@@ -0,0 +1,68 @@
+use ethers::middleware::Middleware;
+use ethers::prelude::{Address, ProviderError, H256};
+use ethers::providers::{Http, Provider};
+use ethers_core::abi::{self, AbiEncode, ParamType, Token};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
+use ethers_core::types::Bytes;
+use hex_literal::hex;
+use tracing::instrument;
+
+use crate::utils::name::namehash;
+
+/// ENS registry (with fallback), deployed at the same address on mainnet and the testnets
+pub const ENS_REGISTRY: Address = Address(hex!("00000000000C2E074eC69A0dFb2997BA6C7d2e1e"));
+
+// resolver(bytes32)
+const RESOLVER_SELECTOR: [u8; 4] = hex!("0178b8bf");
+
+/// Resolver set on `node` in the registry, the zero address if there is none
+#[instrument(skip(rpc))]
+pub async fn find_resolver(
+    rpc: &Provider<Http>,
+    registry: &Address,
+    node: &H256,
+) -> Result<Address, ProviderError> {
+    let mut transaction = TypedTransaction::default();
+
+    transaction.set_to(*registry);
+
+    let encoded = abi::encode(&[Token::FixedBytes(node.encode())]);
+    transaction.set_data(Bytes::from(
+        [&RESOLVER_SELECTOR, encoded.as_slice()].concat(),
+    ));
+
+    let res = rpc.call(&transaction, None).await?;
+
+    let address = abi::decode(&[ParamType::Address], &res)
+        .ok()
+        .and_then(|decoded| decoded.into_iter().next())
+        .and_then(Token::into_address)
+        .unwrap_or_default();
+
+    Ok(address)
+}
+
+/// Walks up from `name` to the closest name with a resolver set, as ENSIP-10 wildcard resolution does.
+///
+/// Returns that name and its resolver, or `None` if neither the name nor any parent has one.
+pub async fn find_closest_resolver(
+    rpc: &Provider<Http>,
+    registry: &Address,
+    name: &str,
+) -> Result<Option<(String, Address)>, ProviderError> {
+    let mut current = name;
+
+    loop {
+        let resolver = find_resolver(rpc, registry, &namehash(current)).await?;
+
+        if !resolver.is_zero() {
+            return Ok(Some((current.to_string(), resolver)));
+        }
+
+        let Some((_, parent)) = current.split_once('.') else {
+            return Ok(None);
+        };
+
+        current = parent;
+    }
+}
@@ -1,17 +1,16 @@
 use ethers::middleware::Middleware;
 use ethers::prelude::{Address, ProviderError, H256};
-use ethers::providers::{namehash, Provider};
-use ethers_contract::providers::Http;
+use ethers::providers::{namehash, Http, Provider};
 use ethers_core::abi;
 use ethers_core::abi::{AbiEncode, ParamType, Token};
 use ethers_core::types::transaction::eip2718::TypedTransaction;
 use ethers_core::types::Bytes;
 use hex_literal::hex;
-use lazy_static::lazy_static;
 use thiserror::Error;
 use tracing::instrument;
 
-use crate::core::resolvers::universal::resolve_universal;
+use crate::core::resolvers::registry::find_resolver;
+use crate::core::resolvers::resolve;
 use crate::core::CCIPProvider;
 use crate::models::lookup::{addr, ENSLookup};
 
@@ -33,55 +32,75 @@ pub enum ReverseResolveError {
     AbiDecodeError(#[from] abi::Error),
 }
 
-lazy_static! {
-    static ref BASE_REGISTRY: Address = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e"
-        .parse()
-        .expect("should be a valid address");
-}
-
 const REVERSE_NAME_SUFFIX: &str = "addr.reverse";
 
-const RESOLVE_SELECTOR: [u8; 4] = hex!("0178b8bf");
 const NAME_SELECTOR: [u8; 4] = hex!("691f3431");
 
-#[instrument(skip(rpc))]
-async fn find_resolver(
+// node(address)
+const NODE_SELECTOR: [u8; 4] = hex!("bffbe61c");
+
+/// Node of an address' reverse record, asked from the reverse registrar when there is one,
+/// for deployments that don't keep reverse records under `addr.reverse`
+pub async fn reverse_node(
     rpc: &Provider<Http>,
-    namehash: &H256,
-) -> Result<Address, ReverseResolveError> {
+    address: &Address,
+    reverse_registrar: Option<&Address>,
+) -> Result<H256, ReverseResolveError> {
+    let Some(reverse_registrar) = reverse_registrar else {
+        return Ok(namehash(&format!(
+            "{}.{REVERSE_NAME_SUFFIX}",
+            hex::encode(address.as_bytes())
+        )));
+    };
+
     let mut transaction = TypedTransaction::default();
 
-    transaction.set_to(*BASE_REGISTRY);
+    transaction.set_to(*reverse_registrar);
 
-    let encoded = abi::encode(&[Token::FixedBytes(namehash.encode())]);
-    transaction.set_data(Bytes::from(
-        [&RESOLVE_SELECTOR, encoded.as_slice()].concat(),
-    ));
+    let encoded = abi::encode(&[Token::Address(*address)]);
+    transaction.set_data(Bytes::from([&NODE_SELECTOR, encoded.as_slice()].concat()));
 
     let res = rpc.call(&transaction, None).await?;
 
-    let address = abi::decode(&[ParamType::Address], &res)?
-        .first()
-        .unwrap()
-        .clone()
-        .into_address()
-        .unwrap();
+    let node = abi::decode(&[ParamType::FixedBytes(32)], &res)?
+        .into_iter()
+        .next()
+        .and_then(Token::into_fixed_bytes)
+        .map(|node| H256::from_slice(&node))
+        .unwrap_or_default();
 
-    Ok(address)
+    Ok(node)
 }
 
+/// Primary name of an address, verified by resolving the name's address, see
+/// [`crate::core::resolvers::resolve`] for `universal_resolver`
 #[instrument(skip(rpc))]
 pub async fn resolve_reverse(
     rpc: &CCIPProvider,
     address: &Address,
-    universal_resolver: &Address,
+    registry: &Address,
+    reverse_registrar: Option<&Address>,
+    universal_resolver: Option<&Address>,
 ) -> Result<String, ReverseResolveError> {
-    let reverse_namehash = namehash(&format!(
-        "{}.{REVERSE_NAME_SUFFIX}",
-        hex::encode(address.as_bytes())
-    ));
+    let name = reverse_name(rpc.inner(), address, registry, reverse_registrar).await?;
+
+    verify_primary_name(rpc, &name, address, registry, universal_resolver).await?;
 
-    let resolver = find_resolver(rpc.inner(), &reverse_namehash).await?;
+    Ok(name)
+}
+
+/// Name the reverse record of an address points at, which isn't its primary name
+/// until [`verify_primary_name`] confirms the name resolves back to the address
+#[instrument(skip(rpc))]
+pub async fn reverse_name(
+    rpc: &Provider<Http>,
+    address: &Address,
+    registry: &Address,
+    reverse_registrar: Option<&Address>,
+) -> Result<String, ReverseResolveError> {
+    let reverse_namehash = reverse_node(rpc, address, reverse_registrar).await?;
+
+    let resolver = find_resolver(rpc, registry, &reverse_namehash).await?;
 
     if resolver.is_zero() {
         return Err(ReverseResolveError::MissingPrimaryName);
@@ -94,7 +113,7 @@ pub async fn resolve_reverse(
     let encoded = abi::encode(&[Token::FixedBytes(reverse_namehash.encode())]);
     transaction.set_data(Bytes::from([&NAME_SELECTOR, encoded.as_slice()].concat()));
 
-    let res = rpc.inner().call(&transaction, None).await?;
+    let res = rpc.call(&transaction, None).await?;
 
     let name = abi::decode(&[ParamType::String], &res)?
         .first()
@@ -103,14 +122,34 @@ pub async fn resolve_reverse(
         .into_string()
         .unwrap();
 
-    let (mut res, _, _) = resolve_universal(&name, &[ENSLookup::Addr], rpc, universal_resolver)
-        .await
-        .map_err(|err| ReverseResolveError::AddressLookupError(err.to_string()))?;
+    Ok(name)
+}
 
-    let addr_result = res.remove(0);
-    if !addr_result.success {
+/// Checks that `name` resolves to `address`, on the deployment of `registry` and `universal_resolver`
+#[instrument(skip(rpc))]
+pub async fn verify_primary_name(
+    rpc: &CCIPProvider,
+    name: &str,
+    address: &Address,
+    registry: &Address,
+    universal_resolver: Option<&Address>,
+) -> Result<(), ReverseResolveError> {
+    // only forward lookups of names are batched
+    let (res, _, _) = resolve(
+        name,
+        &[ENSLookup::Addr],
+        rpc,
+        universal_resolver,
+        registry,
+        None,
+        None,
+    )
+    .await
+    .map_err(|err| ReverseResolveError::AddressLookupError(err.to_string()))?;
+
+    let Some(addr_result) = res.into_iter().next().filter(|result| result.success) else {
         return Err(ReverseResolveError::AddressMismatch);
-    }
+    };
 
     let decoded = addr::decode(&addr_result.data)
         .await
@@ -120,7 +159,7 @@ pub async fn resolve_reverse(
         return Err(ReverseResolveError::AddressMismatch);
     }
 
-    Ok(name)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -130,6 +169,7 @@ mod tests {
     use ethers::prelude::{Http, Provider};
     use ethers_ccip_read::CCIPReadMiddleware;
 
+    use crate::core::resolvers::registry::ENS_REGISTRY;
     use crate::core::resolvers::reverse::resolve_reverse;
 
     #[tokio::test]
@@ -144,9 +184,13 @@ mod tests {
                 &"0xb8c2C29ee19D8307cb7255e1Cd9CbDE883A267d5"
                     .parse()
                     .unwrap(),
-                &"0x8cab227b1162f03b8338331adaad7aadc83b895e"
-                    .parse()
-                    .unwrap(),
+                &ENS_REGISTRY,
+                None,
+                Some(
+                    &"0x8cab227b1162f03b8338331adaad7aadc83b895e"
+                        .parse()
+                        .unwrap()
+                ),
             )
             .await
             .ok(),
@@ -159,9 +203,13 @@ mod tests {
                 &"0x2B5c7025998f88550Ef2fEce8bf87935f542C190"
                     .parse()
                     .unwrap(),
-                &"0x8cab227b1162f03b8338331adaad7aadc83b895e"
-                    .parse()
-                    .unwrap(),
+                &ENS_REGISTRY,
+                None,
+                Some(
+                    &"0x8cab227b1162f03b8338331adaad7aadc83b895e"
+                        .parse()
+                        .unwrap()
+                ),
             )
             .await
             .ok(),
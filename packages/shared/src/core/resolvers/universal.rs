@@ -1,42 +1,56 @@
+use std::time::Duration;
 use std::vec;
 
 use ethers::prelude::ProviderError::JsonRpcClientError;
-use ethers::{
-    providers::namehash,
-    types::{transaction::eip2718::TypedTransaction, Address, Bytes},
-};
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes};
 use ethers_ccip_read::{CCIPReadMiddlewareError, CCIPRequest};
 use ethers_core::abi;
 use ethers_core::abi::{ParamType, Token};
 use ethers_core::types::H160;
 use hex_literal::hex;
 use lazy_static::lazy_static;
-use tracing::{instrument, span};
+use tracing::{debug, instrument, warn};
 
+use crate::batch::{CallBatcher, CallOutcome};
 use crate::core::error::ProfileError;
 use crate::core::CCIPProvider;
 use crate::models::lookup::ENSLookup;
+use crate::utils::deadline;
 use crate::utils::dns::dns_encode;
+use crate::utils::name::namehash;
 use crate::utils::vec::dedup_ord;
 
 lazy_static! {
-    static ref OFFCHAIN_DNS_RESOLVER: Address =
+    pub(crate) static ref OFFCHAIN_DNS_RESOLVER: Address =
         Address::from(hex!("F142B308cF687d4358410a4cB885513b30A42025"));
 }
 
+// OffchainLookup(address,string[],bytes,bytes4,bytes)
+const OFFCHAIN_LOOKUP_SELECTOR: [u8; 4] = hex!("556f1830");
+
 #[derive(Debug, Clone)]
 pub struct UniversalResolverResult {
-    pub(crate) success: bool,
-    pub(crate) data: Vec<u8>,
+    pub success: bool,
+    pub data: Vec<u8>,
+    /// Why the call failed before the resolver could answer, only set by
+    /// [`super::direct::resolve_direct`], the universal resolver reverts instead
+    pub error: Option<CallError>,
 }
 
-#[instrument(skip(provider))]
-pub async fn resolve_universal(
-    name: &str,
-    data: &[ENSLookup],
-    provider: &CCIPProvider,
-    universal_resolver: &H160,
-) -> Result<(Vec<UniversalResolverResult>, Address, Vec<String>), ProfileError> {
+/// Why a single record's call failed, without failing the other records
+#[derive(Debug, Clone)]
+pub enum CallError {
+    /// The offchain lookup (CCIP-Read) failed
+    Gateway(String),
+    /// The call took longer than the call timeout
+    Timeout,
+    /// The RPC failed to answer the call
+    Rpc(String),
+}
+
+/// Encodes the universal resolver's `resolve(bytes,bytes[])` call for `data` of `name`,
+/// as sent by [`resolve_universal`].
+pub fn resolve_calldata(name: &str, data: &[ENSLookup]) -> Result<Bytes, ProfileError> {
     let name_hash = namehash(name);
 
     // Prepare the variables
@@ -56,41 +70,68 @@ pub async fn resolve_universal(
     // resolve(bytes node, bytes[] data)
     let resolve_selector = hex_literal::hex!("206c74c9").to_vec();
 
+    Ok(Bytes::from([resolve_selector, encoded_data].concat()))
+}
+
+/// Calls the universal resolver for `data` of `name` through `batcher`, aggregated with
+/// the calls of other names.
+///
+/// `None` when the name needs an offchain lookup (CCIP-Read) or the batch failed, the
+/// name is then resolved on its own by [`resolve_universal`].
+#[instrument(skip(batcher))]
+pub async fn resolve_batched(
+    name: &str,
+    data: &[ENSLookup],
+    universal_resolver: &H160,
+    batcher: &dyn CallBatcher,
+) -> Result<Option<Bytes>, ProfileError> {
+    let transaction_data = resolve_calldata(name, data)?;
+
+    match batcher.call(*universal_resolver, transaction_data).await {
+        Ok(CallOutcome::Success(res)) => Ok(Some(res)),
+        Ok(CallOutcome::Reverted(data)) if data.starts_with(&OFFCHAIN_LOOKUP_SELECTOR) => Ok(None),
+        // same as a reverting eth_call in resolve_universal
+        Ok(CallOutcome::Reverted(_)) => Err(ProfileError::NotFound),
+        Err(err) => {
+            warn!(name, "batched call failed, calling on its own: {err}");
+
+            Ok(None)
+        }
+    }
+}
+
+/// Resolves `data` for `name` through the universal resolver.
+///
+/// `batched` is the response of [`resolve_batched`], without one the universal resolver is
+/// called on its own. That call, including its gateway requests, fails with
+/// [`ProfileError::Timeout`] after `call_timeout`.
+#[instrument(skip(provider, batched))]
+pub async fn resolve_universal(
+    name: &str,
+    data: &[ENSLookup],
+    provider: &CCIPProvider,
+    universal_resolver: &H160,
+    batched: Option<Bytes>,
+    call_timeout: Option<Duration>,
+) -> Result<(Vec<UniversalResolverResult>, Address, Vec<String>), ProfileError> {
+    let transaction_data = resolve_calldata(name, data)?;
+
     // Create the transaction
     let mut typed_transaction = TypedTransaction::default();
 
-    // Prepare transaction data
-    let transaction_data = [resolve_selector, encoded_data].concat();
-
     // Set up the transaction
     typed_transaction.set_to(*universal_resolver);
-    typed_transaction.set_data(Bytes::from(transaction_data));
-
-    let span = span!(tracing::Level::INFO, "ccip_call", name = name);
-
-    // Call the transaction
-    let (res, ccip_requests) =
-        provider
-            .call_ccip(&typed_transaction, None)
-            .await
-            .map_err(|err| {
-                let CCIPReadMiddlewareError::MiddlewareError(provider_error) = err else {
-                    return ProfileError::CCIPError(err);
-                };
-
-                let JsonRpcClientError(rpc_err) = &provider_error else {
-                    return ProfileError::RPCError(provider_error);
-                };
-
-                // TODO: better error handling
-                if rpc_err.as_error_response().is_some() {
-                    return ProfileError::NotFound;
-                }
-
-                ProfileError::RPCError(provider_error)
-            })?;
+    typed_transaction.set_data(transaction_data);
 
-    drop(span);
+    let (res, ccip_requests) = match batched {
+        Some(res) => (res, Vec::new()),
+        None => deadline::timeout(
+            call_timeout,
+            call_universal(name, provider, &typed_transaction),
+        )
+        .await
+        .ok_or(ProfileError::Timeout)??,
+    };
 
     // Abi Decode
     let result = abi::decode(
@@ -137,7 +178,11 @@ pub async fn resolve_universal(
                 .into_bytes()
                 .expect("result[0][1] elements should be bytes");
 
-            UniversalResolverResult { success, data }
+            UniversalResolverResult {
+                success,
+                data,
+                error: None,
+            }
         })
         .collect();
 
@@ -161,7 +206,47 @@ pub async fn resolve_universal(
     ))
 }
 
-fn urls_from_request(request: &CCIPRequest) -> Vec<String> {
+#[instrument(skip(provider, transaction))]
+async fn call_universal(
+    name: &str,
+    provider: &CCIPProvider,
+    transaction: &TypedTransaction,
+) -> Result<(Bytes, Vec<CCIPRequest>), ProfileError> {
+    // Call the transaction
+    let result = provider.call_ccip(transaction, None).await.map_err(|err| {
+        let CCIPReadMiddlewareError::MiddlewareError(provider_error) = err else {
+            return ProfileError::CCIPError(err);
+        };
+
+        let JsonRpcClientError(rpc_err) = &provider_error else {
+            return ProfileError::RPCError(provider_error);
+        };
+
+        // TODO: better error handling
+        if rpc_err.as_error_response().is_some() {
+            return ProfileError::NotFound;
+        }
+
+        ProfileError::RPCError(provider_error)
+    });
+
+    match &result {
+        Ok((_, requests)) => {
+            for request in requests {
+                debug!(
+                    urls = ?urls_from_request(request),
+                    calldata = %hex::encode(&request.calldata),
+                    "offchain lookup"
+                );
+            }
+        }
+        Err(err) => debug!(error = %err, "universal resolver call failed"),
+    }
+
+    result
+}
+
+pub(crate) fn urls_from_request(request: &CCIPRequest) -> Vec<String> {
     if request.calldata.len() < 4 {
         return Vec::new();
     }
@@ -232,6 +317,8 @@ mod tests {
             &calldata,
             &CCIPReadMiddleware::new(Arc::new(provider)),
             &Address::from_str("0x8cab227b1162f03b8338331adaad7aadc83b895e").unwrap(),
+            None,
+            None,
         )
         .await
         .unwrap();
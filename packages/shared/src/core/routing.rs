@@ -0,0 +1,177 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use ethers::providers::{Http, Provider};
+use ethers_core::types::H160;
+
+use crate::batch::CallBatcher;
+use crate::core::ENSService;
+use crate::utils::factory::Factory;
+
+/// ENS-compatible deployment names under a suffix are resolved on, e.g. `.gno` names on Gnosis chain
+pub struct Backend {
+    /// Reported on profiles resolved through the backend
+    pub name: String,
+    pub rpc: Box<dyn Factory<Arc<Provider<Http>>>>,
+    /// Without a universal resolver names are resolved by walking the registry
+    pub universal_resolver: Option<H160>,
+    pub registry: H160,
+    /// Reverse registrar of the deployment, see [`ENSService::reverse_registrar`]
+    pub reverse_registrar: Option<H160>,
+    /// Prefix of the cache keys of names resolved through the backend, so they're
+    /// cached, indexed and purged apart from the service's own names
+    pub namespace: String,
+}
+
+/// Backends by the suffix, a TLD or any parent name, of the names they resolve
+#[derive(Default)]
+pub struct Routing {
+    routes: Vec<(String, Backend)>,
+}
+
+impl Routing {
+    pub fn new(routes: impl IntoIterator<Item = (String, Backend)>) -> Self {
+        let mut routes: Vec<_> = routes.into_iter().collect();
+
+        // the longest matching suffix wins, e.g. `base.eth` over `eth`
+        routes.sort_by_key(|(suffix, _)| Reverse(suffix.len()));
+
+        Self { routes }
+    }
+
+    /// Backend of a normalized name, `None` when it's resolved by the service itself
+    pub fn backend(&self, name: &str) -> Option<&Backend> {
+        self.routes
+            .iter()
+            .find(|(suffix, _)| {
+                name == suffix
+                    || name
+                        .strip_suffix(suffix.as_str())
+                        .is_some_and(|rest| rest.ends_with('.'))
+            })
+            .map(|(_, backend)| backend)
+    }
+
+    /// Every backend once, also when it's routed to for several suffixes
+    pub fn backends(&self) -> impl Iterator<Item = &Backend> {
+        let mut seen = HashSet::new();
+
+        self.routes
+            .iter()
+            .map(|(_, backend)| backend)
+            .filter(move |backend| seen.insert(backend.name.as_str()))
+    }
+}
+
+/// What a name is resolved through, its backend or the service's own provider and contracts
+pub(crate) struct Route<'a> {
+    /// Name of the backend, unset for the service itself
+    pub backend: Option<&'a str>,
+    pub rpc: &'a dyn Factory<Arc<Provider<Http>>>,
+    pub universal_resolver: Option<&'a H160>,
+    pub registry: &'a H160,
+    /// Only calls to the service's own universal resolver are batched
+    pub batcher: Option<&'a dyn CallBatcher>,
+    /// Prefix of the cache keys, empty for the service itself
+    pub namespace: &'a str,
+}
+
+impl ENSService {
+    pub(crate) fn route(&self, name: &str) -> Route<'_> {
+        match self.routing.backend(name) {
+            Some(backend) => Route {
+                backend: Some(&backend.name),
+                rpc: backend.rpc.as_ref(),
+                universal_resolver: backend.universal_resolver.as_ref(),
+                registry: &backend.registry,
+                batcher: None,
+                namespace: &backend.namespace,
+            },
+            None => Route {
+                backend: None,
+                rpc: self.rpc.as_ref(),
+                universal_resolver: self.universal_resolver.as_ref(),
+                registry: &self.registry,
+                batcher: self.batcher.as_deref(),
+                namespace: "",
+            },
+        }
+    }
+
+    /// Cache key of the profile of a normalized name
+    pub fn profile_key(&self, name: &str) -> String {
+        format!("{}n:{name}", self.route(name).namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ethers::providers::{Http, Provider};
+    use ethers_core::types::H160;
+
+    use super::{Backend, Routing};
+    use crate::core::testing::service;
+    use crate::utils::factory::SimpleFactory;
+
+    fn backend(name: &str) -> Backend {
+        let provider = Provider::<Http>::try_from("http://localhost:8545").unwrap();
+
+        Backend {
+            name: name.to_string(),
+            rpc: Box::new(SimpleFactory::from(Arc::new(provider))),
+            universal_resolver: None,
+            registry: H160::zero(),
+            reverse_registrar: None,
+            namespace: format!("{name}:"),
+        }
+    }
+
+    #[test]
+    fn test_backend() {
+        let routing = Routing::new([
+            ("gno".to_string(), backend("gnosis")),
+            ("eth".to_string(), backend("mainnet")),
+            ("base.eth".to_string(), backend("base")),
+        ]);
+
+        let backend = |name| routing.backend(name).map(|backend| backend.name.as_str());
+
+        assert_eq!(backend("luc.gno"), Some("gnosis"));
+        assert_eq!(backend("gno"), Some("gnosis"));
+        assert_eq!(backend("luc.base.eth"), Some("base"));
+        assert_eq!(backend("luc.eth"), Some("mainnet"));
+        assert_eq!(backend("luc.xgno"), None);
+        assert_eq!(backend("luc.sh"), None);
+    }
+
+    #[test]
+    fn test_backends() {
+        let routing = Routing::new([
+            ("gno".to_string(), backend("gnosis")),
+            ("eth".to_string(), backend("mainnet")),
+            ("base.eth".to_string(), backend("base")),
+            ("xdai".to_string(), backend("gnosis")),
+        ]);
+
+        let mut names = routing
+            .backends()
+            .map(|backend| backend.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+
+        assert_eq!(names, ["base", "gnosis", "mainnet"]);
+    }
+
+    #[test]
+    fn test_profile_key() {
+        let mut service = service("http://localhost:8545");
+        service.routing = Routing::new([("gno".to_string(), backend("gnosis"))]);
+
+        // routed names are kept apart from the service's own
+        assert_eq!(service.profile_key("luc.gno"), "gnosis:n:luc.gno");
+        assert_eq!(service.profile_key("luc.eth"), "n:luc.eth");
+    }
+}
@@ -0,0 +1,134 @@
+//! Mock JSON-RPC endpoints and services resolving on them, for tests
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ethers::providers::{Http, Provider};
+use ethers_core::types::{Bytes, H160};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::cache::PassthroughCacheLayer;
+use crate::core::routing::Routing;
+use crate::core::{ENSService, Settings};
+use crate::utils::factory::SimpleFactory;
+
+pub const UNIVERSAL_RESOLVER: H160 = H160::repeat_byte(1);
+pub const REGISTRY: H160 = H160::repeat_byte(2);
+
+/// JSON-RPC endpoint answering `eth_call`s with `answer(to, data)`, calls it
+/// has no answer for never return
+pub async fn rpc(
+    answer: impl Fn(H160, &[u8]) -> Option<Vec<u8>> + Send + Sync + 'static,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let answer = Arc::new(answer);
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let answer = answer.clone();
+
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+
+                loop {
+                    let mut chunk = [0; 4096];
+                    let read = socket.read(&mut chunk).await.unwrap_or(0);
+                    if read == 0 {
+                        return;
+                    }
+                    buf.extend_from_slice(&chunk[..read]);
+
+                    let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                        continue;
+                    };
+
+                    let head = String::from_utf8_lossy(&buf[..end]).to_lowercase();
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .and_then(|length| length.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+
+                    if buf.len() < end + 4 + length {
+                        continue;
+                    }
+
+                    let request: Value =
+                        serde_json::from_slice(&buf[end + 4..end + 4 + length]).unwrap();
+                    buf.drain(..end + 4 + length);
+
+                    let call = &request["params"][0];
+                    let to: H160 = serde_json::from_value(call["to"].clone()).unwrap_or_default();
+                    let data: Bytes = serde_json::from_value(call["data"].clone())
+                        .or_else(|_| serde_json::from_value(call["input"].clone()))
+                        .unwrap_or_default();
+
+                    let Some(result) = answer(to, &data) else {
+                        std::future::pending::<()>().await;
+
+                        return;
+                    };
+
+                    let body = json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": format!("0x{}", hex::encode(result)),
+                    })
+                    .to_string();
+
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+
+                    if socket.write_all(response.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    url
+}
+
+/// Service resolving on `rpc_url` through [`UNIVERSAL_RESOLVER`] and [`REGISTRY`], uncached
+pub fn service(rpc_url: &str) -> ENSService {
+    let provider = Provider::<Http>::try_from(rpc_url).unwrap();
+
+    ENSService {
+        cache: Box::new(PassthroughCacheLayer {}),
+        batcher: None,
+        limiter: None,
+        discovery: None,
+        healer: None,
+        observer: None,
+        popularity: None,
+        protected_names: None,
+        rpc: Box::new(SimpleFactory::from(Arc::new(provider))),
+        opensea_api_key: String::new(),
+        ipfs_gateway: String::new(),
+        arweave_gateway: String::new(),
+        safety: false,
+        universal_resolver: Some(UNIVERSAL_RESOLVER),
+        registry: REGISTRY,
+        reverse_registrar: None,
+        routing: Routing::default(),
+        settings: RwLock::new(Arc::new(Settings {
+            profile_records: Arc::from(Vec::new()),
+            profile_chains: Arc::from(Vec::new()),
+            lenient_records: false,
+            lookup_timeout: Some(Duration::from_secs(5)),
+            call_timeout: Some(Duration::from_millis(100)),
+            max_bulk_size: 10,
+            cache_ttl: None,
+            partial_cache_ttl: 30,
+        })),
+        cache_compression: false,
+        cache_node_index: false,
+    }
+}
@@ -0,0 +1,14 @@
+use std::sync::Arc;
+
+use ethers_core::types::H256;
+
+/// Finds the labels of labelhashes, e.g. from a rainbow table
+pub trait LabelHealer: Send + Sync {
+    fn heal(&self, labelhash: &H256) -> Option<String>;
+}
+
+impl<T: LabelHealer + ?Sized> LabelHealer for Arc<T> {
+    fn heal(&self, labelhash: &H256) -> Option<String> {
+        self.as_ref().heal(labelhash)
+    }
+}
@@ -1,7 +1,13 @@
+pub mod batch;
 pub mod cache;
 pub mod core;
+pub mod healer;
+pub mod limiter;
 pub mod meta;
 pub mod models;
+pub mod observer;
 pub mod patterns;
+pub mod popularity;
+pub mod safety;
 pub mod utils;
 pub mod discovery;
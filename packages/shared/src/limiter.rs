@@ -0,0 +1,20 @@
+use std::any::Any;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+/// Slot to resolve from chain, released when dropped
+pub type Permit = Box<dyn Any + Send + Sync>;
+
+#[derive(Error, Debug, Clone)]
+pub enum LimitError {
+    #[error("Too many lookups in progress, retry in {retry_after} seconds")]
+    Overloaded { retry_after: u64 },
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait Limiter: Send + Sync {
+    /// Waits for a slot to resolve from chain, or sheds the lookup when too many are waiting
+    async fn acquire(&self) -> Result<Permit, LimitError>;
+}
@@ -8,6 +8,7 @@ use lazy_static::lazy_static;
 use thiserror::Error;
 use tracing::instrument;
 
+use crate::core::error::{RecordError, RecordErrorCode};
 use crate::core::CCIPProvider;
 use crate::models::eip155::EIP155Error;
 use crate::models::multicoin::cointype::coins::CoinType;
@@ -17,6 +18,7 @@ use super::multicoin::decoding::MulticoinDecoderError;
 pub mod addr;
 pub mod image;
 pub mod multicoin;
+pub mod revert;
 pub mod text;
 pub mod contenthash;
 
@@ -37,11 +39,73 @@ pub enum ENSLookupError {
     #[error("EIP155: {0}")]
     EIP155Error(#[from] EIP155Error),
 
-    #[error("CCIP resolution error ({}): {}", status, message)]
-    CCIPError { status: u16, message: String },
+    /// `status` is unset when the gateway couldn't be reached or answered with something invalid
+    #[error("CCIP resolution error{}: {}", status.map(|status| format!(" ({status})")).unwrap_or_default(), message)]
+    CCIPError {
+        status: Option<u16>,
+        message: String,
+    },
 
     #[error("ContentHashDecodeError")]
     ContentHashDecodeError(#[from] contenthash::ContentHashDecodeError),
+
+    #[error("Timed out")]
+    Timeout,
+
+    #[error("RPC error: {0}")]
+    RPCError(String),
+
+    #[error("Resolver reverted: {0}")]
+    Reverted(String),
+
+    #[error("Resolver error: {0}")]
+    ResolverError(String),
+}
+
+impl ENSLookupError {
+    pub fn code(&self) -> RecordErrorCode {
+        match self {
+            ENSLookupError::AbiDecodeError
+            | ENSLookupError::AbiError(_)
+            | ENSLookupError::MulticoinDecoder(_)
+            | ENSLookupError::ContentHashDecodeError(_) => RecordErrorCode::DecodeFailed,
+            ENSLookupError::Unsupported(_) => RecordErrorCode::Unsupported,
+            ENSLookupError::EIP155Error(_) => RecordErrorCode::MetadataFailed,
+            ENSLookupError::CCIPError { .. } => RecordErrorCode::CcipGatewayError,
+            ENSLookupError::Timeout => RecordErrorCode::Timeout,
+            ENSLookupError::RPCError(_) => RecordErrorCode::RpcUnavailable,
+            ENSLookupError::Reverted(_) => RecordErrorCode::ResolverReverted,
+            ENSLookupError::ResolverError(_) => RecordErrorCode::ResolverError,
+        }
+    }
+
+    /// Failures that may be gone on the next lookup, they make the profile partial
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ENSLookupError::Timeout | ENSLookupError::RPCError(_))
+    }
+
+    /// Failures of the resolver or its gateway, rather than of enstate decoding the result
+    pub fn is_resolver_failure(&self) -> bool {
+        matches!(
+            self,
+            ENSLookupError::CCIPError { .. }
+                | ENSLookupError::Reverted(_)
+                | ENSLookupError::ResolverError(_)
+        )
+    }
+}
+
+impl From<&ENSLookupError> for RecordError {
+    fn from(error: &ENSLookupError) -> Self {
+        RecordError {
+            code: error.code(),
+            message: error.to_string(),
+            status: match error {
+                ENSLookupError::CCIPError { status, .. } => *status,
+                _ => None,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -155,7 +219,7 @@ pub fn abi_decode_universal_ccip(
         };
 
         ENSLookupError::CCIPError {
-            status: status.as_u32() as u16,
+            status: Some(status.as_u32() as u16),
             message: message.to_string(),
         }
     })
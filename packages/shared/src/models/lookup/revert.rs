@@ -0,0 +1,136 @@
+use ethers_core::abi::{self, ParamType, Token};
+use hex_literal::hex;
+
+use super::ENSLookupError;
+
+// Error(string)
+const ERROR_SELECTOR: [u8; 4] = hex!("08c379a0");
+// Panic(uint256)
+const PANIC_SELECTOR: [u8; 4] = hex!("4e487b71");
+// ResolverError(bytes)
+const RESOLVER_ERROR_SELECTOR: [u8; 4] = hex!("95c0c752");
+// HttpError((uint16,string)[])
+const HTTP_ERRORS_SELECTOR: [u8; 4] = hex!("ca7a4e75");
+// HttpError(uint16,string)
+const HTTP_ERROR_SELECTOR: [u8; 4] = hex!("01800152");
+// UnsupportedResolverProfile(bytes4)
+const UNSUPPORTED_PROFILE_SELECTOR: [u8; 4] = hex!("7b1c461b");
+
+/// Decodes the revert data of a failed resolver sub-call.
+///
+/// Returns `None` when the resolver simply doesn't implement the record,
+/// i.e. empty revert data or `UnsupportedResolverProfile`, which is reported as unset.
+pub fn decode_revert(data: &[u8]) -> Option<ENSLookupError> {
+    if data.is_empty() {
+        return None;
+    }
+
+    let Some((selector, params)) = data.split_first_chunk::<4>() else {
+        return Some(ENSLookupError::Reverted(format!("0x{}", hex::encode(data))));
+    };
+
+    let error = match *selector {
+        ERROR_SELECTOR => match decode_single(params, ParamType::String) {
+            Some(Token::String(reason)) => ENSLookupError::Reverted(reason),
+            _ => ENSLookupError::Reverted("malformed revert reason".to_string()),
+        },
+        PANIC_SELECTOR => match decode_single(params, ParamType::Uint(256)) {
+            Some(Token::Uint(code)) => ENSLookupError::Reverted(format!("panic 0x{code:x}")),
+            _ => ENSLookupError::Reverted("panic".to_string()),
+        },
+        RESOLVER_ERROR_SELECTOR => match decode_single(params, ParamType::Bytes) {
+            // the resolver's own revert, wrapped by the universal resolver
+            Some(Token::Bytes(inner)) => ENSLookupError::ResolverError(
+                decode_revert(&inner)
+                    .map_or_else(|| "no reason".to_string(), |error| error.to_string()),
+            ),
+            _ => ENSLookupError::ResolverError("malformed error".to_string()),
+        },
+        HTTP_ERRORS_SELECTOR => match decode_single(
+            params,
+            ParamType::Array(Box::new(ParamType::Tuple(vec![
+                ParamType::Uint(16),
+                ParamType::String,
+            ]))),
+        ) {
+            Some(Token::Array(errors)) => match errors.into_iter().next() {
+                Some(Token::Tuple(error)) => http_error(&error),
+                _ => http_error(&[]),
+            },
+            _ => http_error(&[]),
+        },
+        HTTP_ERROR_SELECTOR => match abi::decode(&[ParamType::Uint(16), ParamType::String], params)
+        {
+            Ok(error) => http_error(&error),
+            Err(_) => http_error(&[]),
+        },
+        UNSUPPORTED_PROFILE_SELECTOR => return None,
+        _ => ENSLookupError::Reverted(format!("custom error 0x{}", hex::encode(selector))),
+    };
+
+    Some(error)
+}
+
+fn decode_single(data: &[u8], kind: ParamType) -> Option<Token> {
+    abi::decode(&[kind], data).ok()?.into_iter().next()
+}
+
+fn http_error(error: &[Token]) -> ENSLookupError {
+    match error {
+        [Token::Uint(status), Token::String(message)] => ENSLookupError::CCIPError {
+            status: Some(status.as_u32() as u16),
+            message: message.to_string(),
+        },
+        _ => ENSLookupError::CCIPError {
+            status: None,
+            message: "malformed gateway error".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers_core::abi::{self, Token};
+    use ethers_core::types::U256;
+
+    use super::*;
+
+    fn encode(selector: [u8; 4], tokens: &[Token]) -> Vec<u8> {
+        [selector.as_slice(), &abi::encode(tokens)].concat()
+    }
+
+    #[test]
+    fn test_decode_revert() {
+        assert!(decode_revert(&[]).is_none());
+        assert!(decode_revert(&encode(
+            UNSUPPORTED_PROFILE_SELECTOR,
+            &[Token::FixedBytes(vec![0x59, 0xd1, 0xd4, 0x3c])]
+        ))
+        .is_none());
+
+        let reason = encode(ERROR_SELECTOR, &[Token::String("nope".to_string())]);
+
+        assert!(matches!(
+            decode_revert(&reason),
+            Some(ENSLookupError::Reverted(reason)) if reason == "nope"
+        ));
+        assert!(matches!(
+            decode_revert(&encode(RESOLVER_ERROR_SELECTOR, &[Token::Bytes(reason)])),
+            Some(ENSLookupError::ResolverError(message)) if message == "Resolver reverted: nope"
+        ));
+        assert!(matches!(
+            decode_revert(&encode(
+                HTTP_ERRORS_SELECTOR,
+                &[Token::Array(vec![Token::Tuple(vec![
+                    Token::Uint(U256::from(500)),
+                    Token::String("Internal Server Error".to_string()),
+                ])])]
+            )),
+            Some(ENSLookupError::CCIPError { status: Some(500), message }) if message == "Internal Server Error"
+        ));
+        assert!(matches!(
+            decode_revert(&[0xde, 0xad, 0xbe, 0xef]),
+            Some(ENSLookupError::Reverted(message)) if message == "custom error 0xdeadbeef"
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+use crate::cache::envelope::EnvelopeError;
+use crate::core::Profile;
+
+/// Receives notable events from [`crate::core::ENSService`], e.g. to export them as metrics.
+///
+/// Every method defaults to a no-op, so implementors only override what they need.
+pub trait Observer: Send + Sync {
+    /// A cached entry in a legacy format was read and rewritten in the current one
+    fn cache_migrated(&self, _key: &str) {}
+
+    /// A cached entry could not be decoded and was treated as a miss
+    fn cache_decode_failed(&self, _key: &str, _error: &EnvelopeError) {}
+
+    /// A complete profile was resolved from chain, rather than read from the cache
+    fn profile_resolved(&self, _profile: &Profile) {}
+}
+
+/// Forwards every event to each observer in order.
+impl Observer for Vec<Box<dyn Observer>> {
+    fn cache_migrated(&self, key: &str) {
+        for observer in self {
+            observer.cache_migrated(key);
+        }
+    }
+
+    fn cache_decode_failed(&self, key: &str, error: &EnvelopeError) {
+        for observer in self {
+            observer.cache_decode_failed(key, error);
+        }
+    }
+
+    fn profile_resolved(&self, profile: &Profile) {
+        for observer in self {
+            observer.profile_resolved(profile);
+        }
+    }
+}
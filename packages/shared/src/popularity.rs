@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PopularityKind {
+    Name,
+    Address,
+}
+
+impl PopularityKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Address => "address",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PopularEntry {
+    pub key: String,
+    /// Time-decayed number of lookups
+    pub score: f64,
+}
+
+#[derive(Error, Debug)]
+pub enum PopularityError {
+    #[error("Unknown window {0}")]
+    UnknownWindow(String),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait Popularity: Send + Sync {
+    /// Counts a lookup of a normalized name or an address, awaited by the lookup
+    /// so it should return quickly
+    async fn record_hit(&self, kind: PopularityKind, key: &str) -> Result<(), PopularityError>;
+
+    /// Up to `limit` most looked up keys in `window`, most popular first
+    async fn top(
+        &self,
+        kind: PopularityKind,
+        window: &str,
+        limit: usize,
+    ) -> Result<Vec<PopularEntry>, PopularityError>;
+}
@@ -0,0 +1,392 @@
+use serde::{Deserialize, Serialize};
+use unicode_script::{Script, ScriptExtension, UnicodeScript};
+use utoipa::ToSchema;
+
+/// Names that lookalikes are reported for, e.g. the most popular ones
+pub trait ProtectedNames: Send + Sync {
+    /// A protected name with this [`skeleton`]
+    fn with_skeleton(&self, skeleton: &str) -> Option<String>;
+}
+
+/// Whether a name can be mistaken for another, for wallets to warn before a transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SafetyReport {
+    /// No issues were found
+    pub safe: bool,
+    pub issues: Vec<SafetyIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SafetyIssue {
+    pub kind: SafetyIssueKind,
+    /// Index of the offending label, unset when the issue is about the whole name
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub label: Option<usize>,
+    pub message: String,
+    /// What the label or name looks like
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[schema(example = "paypal.eth")]
+    pub confusable_with: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SafetyIssueKind {
+    /// A label mixes scripts, e.g. a Cyrillic `а` among Latin letters
+    MixedScript,
+    /// A label only has letters of a single script that all look like Latin ones
+    WholeScriptConfusable,
+    /// A label has characters that don't render, e.g. zero-width spaces
+    InvisibleCharacter,
+    /// The name looks like one of the protected (e.g. popular) names
+    Lookalike,
+}
+
+impl SafetyReport {
+    /// Analyzes a normalized name, reporting lookalikes of `protected` names
+    pub fn of(name: &str, protected: Option<&dyn ProtectedNames>) -> Self {
+        let mut issues = Vec::new();
+
+        for (index, label) in name.split('.').enumerate() {
+            if let Some(character) = label
+                .chars()
+                .enumerate()
+                .find(|(position, _)| is_invisible(label, *position))
+                .map(|(_, character)| character)
+            {
+                issues.push(SafetyIssue {
+                    kind: SafetyIssueKind::InvisibleCharacter,
+                    label: Some(index),
+                    message: format!(
+                        "Label {index} contains the invisible character U+{:04X}",
+                        u32::from(character)
+                    ),
+                    confusable_with: None,
+                });
+            }
+
+            if resolved_scripts(label).is_empty() {
+                issues.push(SafetyIssue {
+                    kind: SafetyIssueKind::MixedScript,
+                    label: Some(index),
+                    message: format!(
+                        "Label {index} mixes the {} scripts",
+                        scripts(label).join(", ")
+                    ),
+                    confusable_with: None,
+                });
+            } else if let Some(latin) = whole_script_confusable(label) {
+                issues.push(SafetyIssue {
+                    kind: SafetyIssueKind::WholeScriptConfusable,
+                    label: Some(index),
+                    message: format!(
+                        "Label {index} is written in {} but looks like the Latin {latin}",
+                        scripts(label).join(", ")
+                    ),
+                    confusable_with: Some(latin),
+                });
+            }
+        }
+
+        if let Some(original) = protected
+            .and_then(|protected| protected.with_skeleton(&skeleton(name)))
+            .filter(|original| original != name)
+        {
+            issues.push(SafetyIssue {
+                kind: SafetyIssueKind::Lookalike,
+                label: None,
+                message: format!("Looks like {original}"),
+                confusable_with: Some(original),
+            });
+        }
+
+        Self {
+            safe: issues.is_empty(),
+            issues,
+        }
+    }
+}
+
+/// What a name looks like: confusable characters replaced by the Latin ones they look like
+/// and invisible characters removed, so lookalikes share a skeleton.
+pub fn skeleton(name: &str) -> String {
+    name.chars()
+        .enumerate()
+        .filter(|(position, _)| !is_invisible(name, *position))
+        .map(|(_, character)| latin_lookalike(character).unwrap_or(character))
+        .collect()
+}
+
+/// Scripts every character of the label could belong to, empty when it mixes scripts.
+///
+/// Han is combined with the scripts it's written with in Japanese and Korean,
+/// so those aren't reported as mixed.
+fn resolved_scripts(label: &str) -> ScriptExtension {
+    label
+        .chars()
+        .map(|character| augment(character.script_extension()))
+        .fold(ScriptExtension::default(), |scripts, character| {
+            scripts.intersection(character)
+        })
+}
+
+fn augment(scripts: ScriptExtension) -> ScriptExtension {
+    let han: ScriptExtension = Script::Han.into();
+
+    if scripts.is_common() || scripts.is_inherited() {
+        return scripts;
+    }
+
+    if scripts.contains_script(Script::Han) {
+        return [
+            Script::Hiragana,
+            Script::Katakana,
+            Script::Hangul,
+            Script::Bopomofo,
+        ]
+        .into_iter()
+        .fold(scripts, |scripts, script| scripts.union(script.into()));
+    }
+
+    if [
+        Script::Hiragana,
+        Script::Katakana,
+        Script::Hangul,
+        Script::Bopomofo,
+    ]
+    .into_iter()
+    .any(|script| scripts.contains_script(script))
+    {
+        return scripts.union(han);
+    }
+
+    scripts
+}
+
+/// Names of the scripts used in a label, in order of appearance
+fn scripts(label: &str) -> Vec<String> {
+    let mut scripts: Vec<String> = Vec::new();
+
+    for script in label.chars().map(|character| character.script()) {
+        if matches!(script, Script::Common | Script::Inherited | Script::Unknown) {
+            continue;
+        }
+
+        let name = script.full_name().to_string();
+
+        if !scripts.contains(&name) {
+            scripts.push(name);
+        }
+    }
+
+    scripts
+}
+
+/// The Latin label a single-script, non-Latin label looks like, if every letter has a lookalike
+fn whole_script_confusable(label: &str) -> Option<String> {
+    let scripts = resolved_scripts(label);
+
+    if scripts.is_common() || scripts.is_inherited() || scripts.contains_script(Script::Latin) {
+        return None;
+    }
+
+    let mut latin = String::with_capacity(label.len());
+
+    for character in label.chars() {
+        if character.is_alphabetic() {
+            latin.push(latin_lookalike(character)?);
+        } else {
+            latin.push(character);
+        }
+    }
+
+    Some(latin)
+}
+
+/// Whether the character at `position` doesn't render, joiners are allowed where they
+/// are needed: between emoji, and after letters of scripts that use them (e.g. Persian)
+fn is_invisible(text: &str, position: usize) -> bool {
+    let mut characters = text.chars().skip(position.saturating_sub(1));
+
+    let (previous, character) = if position == 0 {
+        (None, characters.next())
+    } else {
+        (characters.next(), characters.next())
+    };
+
+    let Some(character) = character else {
+        return false;
+    };
+
+    let next = characters.next();
+
+    match character {
+        // zero width joiner, in emoji sequences
+        '\u{200D}' => [previous, next]
+            .iter()
+            .any(|neighbour| neighbour.map_or(true, |c| c.is_alphanumeric() || c == '.')),
+        // zero width non-joiner, in e.g. Persian and Indic scripts
+        '\u{200C}' => previous.map_or(true, |c| c.is_ascii() || !c.is_alphabetic()),
+        // tags, only in flag emoji sequences
+        '\u{E0020}'..='\u{E007F}' => !text.contains('\u{1F3F4}'),
+        '\u{00AD}'
+        | '\u{034F}'
+        | '\u{061C}'
+        | '\u{115F}'
+        | '\u{1160}'
+        | '\u{17B4}'
+        | '\u{17B5}'
+        | '\u{180B}'..='\u{180F}'
+        | '\u{200B}'
+        | '\u{200E}'
+        | '\u{200F}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{206F}'
+        | '\u{2800}'
+        | '\u{3164}'
+        | '\u{FE00}'..='\u{FE0E}'
+        | '\u{FEFF}'
+        | '\u{FFA0}'
+        | '\u{1D173}'..='\u{1D17A}'
+        | '\u{E0000}'..='\u{E001F}'
+        | '\u{E0080}'..='\u{E0FFF}' => true,
+        _ => false,
+    }
+}
+
+/// Latin letter or digit a character is commonly mistaken for
+fn latin_lookalike(character: char) -> Option<char> {
+    let latin = match character {
+        'a'..='z' | '0'..='9' => return Some(character),
+        // fullwidth forms
+        '\u{FF41}'..='\u{FF5A}' => {
+            return char::from_u32(u32::from(character) - 0xFF41 + u32::from('a'))
+        }
+        '\u{FF10}'..='\u{FF19}' => {
+            return char::from_u32(u32::from(character) - 0xFF10 + u32::from('0'))
+        }
+        // Cyrillic
+        'а' => 'a',
+        'ь' => 'b',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'е' => 'e',
+        'һ' => 'h',
+        'і' => 'i',
+        'ӏ' => 'l',
+        'ј' => 'j',
+        'к' => 'k',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'ѵ' => 'v',
+        'ԝ' => 'w',
+        'х' => 'x',
+        'у' | 'ү' => 'y',
+        // Greek
+        'α' => 'a',
+        'ϲ' => 'c',
+        'ι' => 'i',
+        'ϳ' => 'j',
+        'κ' => 'k',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'υ' => 'u',
+        'ν' => 'v',
+        'ω' => 'w',
+        'χ' => 'x',
+        'γ' => 'y',
+        // Armenian
+        'ց' => 'g',
+        'հ' => 'h',
+        'ո' => 'n',
+        'օ' => 'o',
+        'զ' => 'q',
+        'ս' => 'u',
+        // Latin lookalikes of Latin letters
+        'ɑ' => 'a',
+        'ƅ' => 'b',
+        'ɡ' => 'g',
+        'ı' | 'ɩ' => 'i',
+        'ȷ' => 'j',
+        'ℓ' => 'l',
+        'ſ' => 'f',
+        'ʋ' => 'v',
+        _ => return None,
+    };
+
+    Some(latin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Popular;
+
+    impl ProtectedNames for Popular {
+        fn with_skeleton(&self, skeleton: &str) -> Option<String> {
+            (skeleton == "apple.eth").then(|| "apple.eth".to_string())
+        }
+    }
+
+    fn kinds(name: &str) -> Vec<(SafetyIssueKind, Option<usize>)> {
+        SafetyReport::of(name, Some(&Popular))
+            .issues
+            .into_iter()
+            .map(|issue| (issue.kind, issue.label))
+            .collect()
+    }
+
+    #[test]
+    fn test_safe() {
+        assert!(SafetyReport::of("luc.eth", Some(&Popular)).safe);
+        assert!(SafetyReport::of("apple.eth", Some(&Popular)).safe);
+        // Japanese and emoji sequences are fine
+        assert!(kinds("ひらがなカタカナ漢字.eth").is_empty());
+        assert!(kinds("👨\u{200D}👩\u{200D}👧.eth").is_empty());
+    }
+
+    #[test]
+    fn test_mixed_script() {
+        // Cyrillic а
+        assert_eq!(
+            kinds("аpple.eth"),
+            [
+                (SafetyIssueKind::MixedScript, Some(0)),
+                (SafetyIssueKind::Lookalike, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_whole_script_confusable() {
+        let report = SafetyReport::of("аррӏе.eth", None);
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(
+            report.issues[0].kind,
+            SafetyIssueKind::WholeScriptConfusable
+        );
+        assert_eq!(report.issues[0].confusable_with.as_deref(), Some("apple"));
+
+        // Cyrillic without lookalikes for every letter
+        assert!(kinds("привет.eth").is_empty());
+    }
+
+    #[test]
+    fn test_invisible() {
+        assert_eq!(
+            kinds("app\u{200B}le.eth"),
+            [
+                (SafetyIssueKind::InvisibleCharacter, Some(0)),
+                (SafetyIssueKind::Lookalike, None)
+            ]
+        );
+        assert_eq!(
+            kinds("lu\u{200D}c.eth"),
+            [(SafetyIssueKind::InvisibleCharacter, Some(0))]
+        );
+    }
+}
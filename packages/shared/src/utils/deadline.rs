@@ -0,0 +1,67 @@
+use std::future::Future;
+use std::pin::pin;
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use futures_timer::Delay;
+
+/// Point in time by which every step of a lookup should be done.
+///
+/// Without a timeout the deadline never passes.
+pub struct Deadline(Option<Delay>);
+
+impl Deadline {
+    pub fn after(timeout: Option<Duration>) -> Self {
+        Self(timeout.map(Delay::new))
+    }
+
+    /// Runs `future` until it completes, or returns `None` once the deadline has passed
+    pub async fn run<F: Future>(&mut self, future: F) -> Option<F::Output> {
+        let Some(delay) = &mut self.0 else {
+            return Some(future.await);
+        };
+
+        match future::select(pin!(future), delay).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+/// Runs `future` for at most `timeout`
+pub async fn timeout<F: Future>(timeout: Option<Duration>, future: F) -> Option<F::Output> {
+    Deadline::after(timeout).run(future).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_timer::Delay;
+
+    use super::{timeout, Deadline};
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let mut deadline = Deadline::after(Some(Duration::from_millis(50)));
+
+        assert_eq!(deadline.run(async { 1 }).await, Some(1));
+        assert_eq!(
+            deadline
+                .run(async {
+                    Delay::new(Duration::from_millis(200)).await;
+
+                    2
+                })
+                .await,
+            None
+        );
+        // the deadline stays passed
+        assert_eq!(
+            deadline.run(Delay::new(Duration::from_millis(10))).await,
+            None
+        );
+
+        assert_eq!(timeout(None, async { 3 }).await, Some(3));
+    }
+}
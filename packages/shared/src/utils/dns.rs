@@ -1,3 +1,5 @@
+use crate::utils::name::decode_label;
+
 /// Encodes a domain name into its binary representation according to the DNS
 /// wire format. Each label (i.e., substring separated by dots) in the domain
 /// is prefixed with its length, and the encoded domain name is terminated
@@ -10,7 +12,7 @@
 /// # Returns
 ///
 /// * A `Result` containing the encoded domain name as a `Vec<u8>` on success, or an error message
-///   as a `String` if any of the labels in the domain name are too long (exceeding 63 characters).
+///   as a `String` if any of the labels in the domain name are too long (exceeding 63 characters, encoded labels excepted).
 ///
 /// # Example
 ///
@@ -26,7 +28,8 @@ pub fn dns_encode(domain: &str) -> Result<Vec<u8>, String> {
 
     for label in labels {
         let label_len = label.len();
-        if label_len > 63 {
+        // encoded labels (`[<labelhash>]`) are passed on as is, for the resolver to decode
+        if label_len > 63 && decode_label(label).is_none() {
             return Err(format!("label is too long: {}", label));
         }
 
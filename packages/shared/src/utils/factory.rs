@@ -1,14 +1,21 @@
 use async_trait::async_trait;
 
+use crate::limiter::Permit;
+
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
-pub trait Factory<T>: Send + Sync {
+pub trait Factory<T: Send + 'static>: Send + Sync {
     fn get_instance(&self) -> T;
+
+    /// Waits for an instance with spare capacity, reserved until the permit is dropped
+    async fn lease(&self) -> (T, Option<Permit>) {
+        (self.get_instance(), None)
+    }
 }
 
 pub struct SimpleFactory<T: Send + Sync + Clone>(T);
 
-impl<T: Send + Sync + Clone> Factory<T> for SimpleFactory<T> {
+impl<T: Send + Sync + Clone + 'static> Factory<T> for SimpleFactory<T> {
     fn get_instance(&self) -> T {
         self.0.clone()
     }
@@ -1,6 +1,8 @@
 pub mod data_url;
+pub mod deadline;
 pub mod dns;
 pub mod eip55;
 pub mod factory;
+pub mod name;
 pub mod sha256;
 pub mod vec;
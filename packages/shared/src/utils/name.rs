@@ -0,0 +1,341 @@
+use ethers::types::{Bytes, H256};
+use ethers::utils::keccak256;
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+use utoipa::ToSchema;
+
+use crate::utils::dns::dns_encode;
+
+/// Longest label that fits the DNS wire format
+pub const MAX_LABEL_BYTES: usize = 63;
+
+/// Everything about a name that can be derived without touching the chain
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NameInfo {
+    /// Name as given
+    #[schema(example = "Luc.eth")]
+    pub input: String,
+    /// Normalized name, as used for lookups
+    #[schema(example = "luc.eth")]
+    pub name: String,
+    /// Normalized name for display, e.g. with `Ξ` instead of `ξ` outside of Greek labels
+    #[schema(example = "luc.eth")]
+    pub beautified: String,
+    pub labels: Vec<LabelInfo>,
+    #[schema(value_type = String)]
+    pub namehash: H256,
+    /// DNS wire format, unset when a label is longer than 63 bytes (encoded labels excepted)
+    #[schema(value_type = Option<String>, example = "0x036c75630365746800")]
+    pub dns_encoded: Option<Bytes>,
+    #[schema(example = "eth")]
+    pub parent: Option<String>,
+    #[schema(example = "eth")]
+    pub tld: String,
+    /// The name has no `errors`
+    pub valid: bool,
+    pub errors: Vec<NameError>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LabelInfo {
+    #[schema(example = "luc")]
+    pub label: String,
+    #[schema(value_type = String)]
+    pub labelhash: H256,
+    /// Length in characters (unicode code points)
+    pub length: usize,
+    /// Length in UTF-8 bytes
+    pub bytes: usize,
+    /// The label is written as `[<labelhash>]`, its preimage is unknown
+    pub encoded: bool,
+}
+
+/// Why a name can't be resolved or registered as is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct NameError {
+    pub code: NameErrorCode,
+    /// Index of the offending label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NameErrorCode {
+    Empty,
+    EmptyLabel,
+    /// Longer than 63 bytes, it can't be DNS encoded
+    LabelTooLong,
+    DisallowedCharacter,
+    /// Underscores are only allowed at the start of a label
+    MisplacedUnderscore,
+    /// The third and fourth characters of a label can't both be hyphens
+    InvalidHyphens,
+}
+
+impl NameError {
+    fn new(code: NameErrorCode, label: Option<usize>, message: String) -> Self {
+        Self {
+            code,
+            label,
+            message,
+        }
+    }
+}
+
+/// Lowercases and NFC-normalizes a name.
+///
+/// This is only a rough approximation of ENSIP-15 normalization, use [`NameInfo::errors`]
+/// to find characters it can't fix.
+pub fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().nfc().collect()
+}
+
+/// Normalized name for display, `ξ` becomes `Ξ` in every label that isn't Greek.
+pub fn beautify(name: &str) -> String {
+    name.split('.')
+        .map(|label| {
+            let greek = label
+                .chars()
+                .any(|c| c != 'ξ' && ('\u{0370}'..='\u{03FF}').contains(&c));
+
+            if greek {
+                label.to_string()
+            } else {
+                label.replace('ξ', "Ξ")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Hash of a label, or the hash an encoded label (`[<labelhash>]`) stands for
+pub fn labelhash(label: &str) -> H256 {
+    decode_label(label).unwrap_or_else(|| H256::from(keccak256(label.as_bytes())))
+}
+
+/// Namehash of a name, encoded labels are used as their labelhash as is
+pub fn namehash(name: &str) -> H256 {
+    if name.is_empty() {
+        return H256::zero();
+    }
+
+    name.rsplit('.').fold(H256::zero(), |node, label| {
+        H256::from(keccak256([node.as_bytes(), labelhash(label).as_bytes()].concat()))
+    })
+}
+
+/// Labelhash of an encoded label, a label whose preimage is unknown written as `[<labelhash>]`
+pub fn decode_label(label: &str) -> Option<H256> {
+    let hash = label.strip_prefix('[')?.strip_suffix(']')?;
+
+    if hash.len() != 64 {
+        return None;
+    }
+
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hash, &mut bytes).ok()?;
+
+    Some(H256::from(bytes))
+}
+
+pub fn encode_label(labelhash: &H256) -> String {
+    format!("[{}]", hex::encode(labelhash.as_bytes()))
+}
+
+/// Name one level up, e.g. `eth` for `luc.eth`, unset for a TLD
+pub fn parent(name: &str) -> Option<&str> {
+    name.split_once('.').map(|(_, parent)| parent)
+}
+
+impl NameInfo {
+    pub fn of(input: &str) -> Self {
+        let name = normalize(input);
+
+        let labels: Vec<LabelInfo> = name
+            .split('.')
+            .map(|label| LabelInfo {
+                label: label.to_string(),
+                labelhash: labelhash(label),
+                length: label.chars().count(),
+                bytes: label.len(),
+                encoded: decode_label(label).is_some(),
+            })
+            .collect();
+
+        let errors = validate(&name, &labels);
+
+        Self {
+            input: input.to_string(),
+            beautified: beautify(&name),
+            namehash: namehash(&name),
+            dns_encoded: dns_encode(&name).ok().map(Bytes::from),
+            parent: parent(&name).map(ToString::to_string),
+            tld: name.rsplit('.').next().unwrap_or_default().to_string(),
+            valid: errors.is_empty(),
+            errors,
+            labels,
+            name,
+        }
+    }
+}
+
+fn validate(name: &str, labels: &[LabelInfo]) -> Vec<NameError> {
+    if name.is_empty() {
+        return vec![NameError::new(
+            NameErrorCode::Empty,
+            None,
+            "Name is empty".to_string(),
+        )];
+    }
+
+    let mut errors = Vec::new();
+
+    for (index, LabelInfo { label, bytes, encoded, .. }) in labels.iter().enumerate() {
+        if *encoded {
+            continue;
+        }
+
+        if label.is_empty() {
+            errors.push(NameError::new(
+                NameErrorCode::EmptyLabel,
+                Some(index),
+                format!("Label {index} is empty"),
+            ));
+
+            continue;
+        }
+
+        if *bytes > MAX_LABEL_BYTES {
+            errors.push(NameError::new(
+                NameErrorCode::LabelTooLong,
+                Some(index),
+                format!(
+                    "Label {index} is {bytes} bytes long, at most {MAX_LABEL_BYTES} are allowed"
+                ),
+            ));
+        }
+
+        if let Some(character) = label.chars().find(|c| is_disallowed(*c)) {
+            errors.push(NameError::new(
+                NameErrorCode::DisallowedCharacter,
+                Some(index),
+                format!("Label {index} contains the disallowed character {character:?}"),
+            ));
+        }
+
+        if label.trim_start_matches('_').contains('_') {
+            errors.push(NameError::new(
+                NameErrorCode::MisplacedUnderscore,
+                Some(index),
+                format!("Label {index} has an underscore after its start"),
+            ));
+        }
+
+        if label.chars().skip(2).take(2).eq(['-', '-']) {
+            errors.push(NameError::new(
+                NameErrorCode::InvalidHyphens,
+                Some(index),
+                format!("Label {index} has hyphens as its third and fourth characters"),
+            ));
+        }
+    }
+
+    errors
+}
+
+fn is_disallowed(c: char) -> bool {
+    if c.is_ascii() {
+        return !(c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '$'));
+    }
+
+    // including the full stops some input methods use instead of `.`
+    c.is_whitespace() || c.is_control() || matches!(c, '\u{3002}' | '\u{FF0E}' | '\u{FF61}')
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::H256;
+
+    use super::*;
+
+    #[test]
+    fn test_name_info() {
+        let info = NameInfo::of(" Luc.ETH");
+
+        assert_eq!(info.name, "luc.eth");
+        assert!(info.valid);
+        assert_eq!(info.parent.as_deref(), Some("eth"));
+        assert_eq!(info.tld, "eth");
+        assert_eq!(
+            info.labels[1].labelhash,
+            "0x4f5b812789fc606be1b3b16908db13fc7a9adf7ca72641f84d75b47069d3d7f0"
+                .parse::<H256>()
+                .unwrap()
+        );
+        assert_eq!(
+            info.dns_encoded.unwrap().as_ref(),
+            [3, b'l', b'u', b'c', 3, b'e', b't', b'h', 0]
+        );
+        assert_eq!(
+            NameInfo::of("eth").namehash,
+            "0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+                .parse::<H256>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_name_errors() {
+        let codes = |name: &str| {
+            NameInfo::of(name)
+                .errors
+                .into_iter()
+                .map(|error| (error.code, error.label))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(codes(""), [(NameErrorCode::Empty, None)]);
+        assert_eq!(codes("luc..eth"), [(NameErrorCode::EmptyLabel, Some(1))]);
+        assert_eq!(
+            codes("l uc.eth"),
+            [(NameErrorCode::DisallowedCharacter, Some(0))]
+        );
+        assert_eq!(
+            codes("l_uc.eth"),
+            [(NameErrorCode::MisplacedUnderscore, Some(0))]
+        );
+        assert!(codes("_luc.eth").is_empty());
+        assert_eq!(
+            codes("xn--luc.eth"),
+            [(NameErrorCode::InvalidHyphens, Some(0))]
+        );
+        assert_eq!(
+            codes(&format!("{}.eth", "a".repeat(64))),
+            [(NameErrorCode::LabelTooLong, Some(0))]
+        );
+    }
+
+    #[test]
+    fn test_encoded_labels() {
+        let eth = "[4f5b812789fc606be1b3b16908db13fc7a9adf7ca72641f84d75b47069d3d7f0]";
+
+        assert_eq!(namehash(&format!("luc.{eth}")), namehash("luc.eth"));
+        assert_eq!(namehash("luc.eth"), ethers::providers::namehash("luc.eth"));
+        assert_eq!(encode_label(&labelhash("eth")), eth);
+        assert_eq!(decode_label("[4f5b]"), None);
+
+        let info = NameInfo::of(&format!("luc.{eth}"));
+
+        assert!(info.valid);
+        assert!(info.labels[1].encoded);
+        assert_eq!(info.dns_encoded.unwrap().len(), 1 + 3 + 1 + 66 + 1);
+    }
+
+    #[test]
+    fn test_beautify() {
+        assert_eq!(beautify("ξ.eth"), "Ξ.eth");
+        assert_eq!(beautify("αξ.eth"), "αξ.eth");
+    }
+}